@@ -0,0 +1,168 @@
+use sea_orm::{ConnectOptions, Database, DatabaseConnection};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::config::{full_jitter, MAX_BACKOFF};
+use crate::migrations::setup_database;
+use crate::services::database::DbService;
+use crate::services::github_api::GitHubApiClient;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+// 未指定schema时使用的默认schema
+const DEFAULT_SCHEMA: &str = "public";
+
+/// 同步所需的数据库服务和GitHub客户端
+///
+/// 通过`SyncContextBuilder`显式传入数据库URL和GitHub令牌构建，不依赖`.env`、配置文件或环境变量，
+/// 便于以编程方式驱动同步；命令行程序则把从环境中读取的配置传给同一个构建器。
+pub struct SyncContext {
+    pub db: DbService,
+    pub github: GitHubApiClient,
+}
+
+impl SyncContext {
+    pub fn builder(database_url: impl Into<String>) -> SyncContextBuilder {
+        SyncContextBuilder::new(database_url)
+    }
+}
+
+/// `SyncContext`的构建器
+pub struct SyncContextBuilder {
+    database_url: String,
+    schema: String,
+    github_token: Option<String>,
+    migrate: bool,
+    connect_retries: u32,
+    connect_interval: Duration,
+    sqlx_logging_level: log::LevelFilter,
+}
+
+impl SyncContextBuilder {
+    pub fn new(database_url: impl Into<String>) -> Self {
+        Self {
+            database_url: database_url.into(),
+            schema: DEFAULT_SCHEMA.to_string(),
+            github_token: None,
+            migrate: true,
+            connect_retries: 0,
+            connect_interval: Duration::from_secs(2),
+            sqlx_logging_level: log::LevelFilter::Off,
+        }
+    }
+
+    // 数据库schema，连接的search_path指向该schema
+    pub fn schema(mut self, schema: impl Into<String>) -> Self {
+        self.schema = schema.into();
+        self
+    }
+
+    // GitHub令牌；为None时GitHub客户端从配置文件或环境变量的令牌池中读取令牌。
+    // 命令行程序使用令牌池，该方法供以编程方式嵌入时使用
+    #[allow(dead_code)]
+    pub fn github_token(mut self, token: Option<String>) -> Self {
+        self.github_token = token;
+        self
+    }
+
+    // 连接后是否执行迁移，只读连接应关闭
+    pub fn migrate(mut self, migrate: bool) -> Self {
+        self.migrate = migrate;
+        self
+    }
+
+    // 连接失败时的重试次数和间隔
+    pub fn connect_retries(mut self, retries: u32, interval: Duration) -> Self {
+        self.connect_retries = retries;
+        self.connect_interval = interval;
+        self
+    }
+
+    // 输出SQL语句的日志级别
+    pub fn sqlx_logging_level(mut self, level: log::LevelFilter) -> Self {
+        self.sqlx_logging_level = level;
+        self
+    }
+
+    // 连接数据库（按需执行迁移）并创建GitHub客户端
+    pub async fn build(self) -> Result<SyncContext, BoxError> {
+        let mut connect_options = ConnectOptions::new(self.database_url);
+        connect_options
+            .set_schema_search_path(self.schema.clone())
+            .sqlx_logging_level(self.sqlx_logging_level);
+        let conn = connect_with_retries(
+            connect_options,
+            self.migrate.then_some(self.schema.as_str()),
+            self.connect_retries,
+            self.connect_interval,
+        )
+        .await?;
+
+        let github = match self.github_token {
+            Some(token) => GitHubApiClient::new().with_token(token),
+            None => GitHubApiClient::new(),
+        };
+
+        Ok(SyncContext {
+            db: DbService::new(conn, self.schema),
+            github,
+        })
+    }
+}
+
+// 连接数据库，`schema`不为None时同时设置该schema下的表结构；失败时按`interval`（叠加抖动）重试`retries`次，
+// 每次尝试都输出日志，容器编排中本工具先于Postgres启动时可以等待数据库就绪而不是直接退出
+pub async fn connect_with_retries(
+    connect_options: ConnectOptions,
+    schema: Option<&str>,
+    retries: u32,
+    interval: Duration,
+) -> Result<DatabaseConnection, BoxError> {
+    let attempts = retries + 1;
+    for attempt in 1..=attempts {
+        if attempts > 1 {
+            info!("连接数据库（第 {}/{} 次尝试）", attempt, attempts);
+        }
+        match try_connect(connect_options.clone(), schema).await {
+            Ok(conn) => return Ok(conn),
+            Err(e) if attempt < attempts => {
+                // 叠加随机抖动，多个实例同时等待数据库时错开重试；等待时间不超过MAX_BACKOFF
+                let wait = (interval + full_jitter(interval / 2)).min(MAX_BACKOFF);
+                warn!("连接数据库失败: {}，{:.1} 秒后重试", e, wait.as_secs_f64());
+                tokio::time::sleep(wait).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("至少会尝试连接一次")
+}
+
+// 连接数据库并设置表结构（只尝试一次）
+async fn try_connect(
+    connect_options: ConnectOptions,
+    schema: Option<&str>,
+) -> Result<DatabaseConnection, BoxError> {
+    let conn = Database::connect(connect_options).await?;
+
+    // 设置数据库表结构
+    if let Some(schema) = schema {
+        let result = setup_database(&conn, schema)
+            .await
+            .map_err(|e| e.to_string());
+        match result {
+            Ok(_) => info!("数据库表结构设置完成"),
+            Err(e) => {
+                // 如果是约束已存在的错误，则可以继续执行
+                if e.contains("already exists") {
+                    warn!("数据库表结构已存在，跳过创建: {}", e);
+                } else {
+                    // 对于其他错误，记录并返回
+                    error!("设置数据库表结构失败: {}", e);
+                    return Err(format!("数据库设置失败: {}", e).into());
+                }
+            }
+        }
+    }
+
+    Ok(conn)
+}
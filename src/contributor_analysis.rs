@@ -1,24 +1,878 @@
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, Timelike, Utc};
+use once_cell::sync::Lazy;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
 use std::path::Path;
+use std::sync::Mutex;
 use tokio::process::Command as TokioCommand;
 use tracing::{debug, error, info, warn};
 
+use crate::services::github_api::is_excluded_web_flow;
+
 // 贡献者分析结果
+// JSON字段名属于对外发布的数据契约，修改字段时需保持序列化后的键名不变（由测试固定）
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ContributorAnalysis {
     pub email: Option<String>,
     pub from_china: bool,
     pub common_timezone: String,
+    /// 常用时区的UTC偏移（分钟），如+05:30为330，无法识别时为0
+    #[serde(default)]
+    pub timezone_offset_minutes: i32,
+    #[serde(default)]
+    pub classification_source: ClassificationSource,
+    /// 使用中国时区的提交占比，作为来自中国的概率
+    #[serde(default)]
+    pub china_probability: f64,
+    /// 概率落在不确定区间内，判定结果不可靠
+    #[serde(default)]
+    pub uncertain: bool,
+    /// 按提交者本地时间统计的每小时（0-23）提交数，序列化为长度24的数组（没有提交的小时为0），
+    /// 也能读取旧版本输出的小时到提交数的映射
+    #[serde(
+        default,
+        serialize_with = "serialize_commit_hours",
+        deserialize_with = "deserialize_commit_hours"
+    )]
+    pub commit_hours: HashMap<u32, usize>,
+    /// 每小时提交数占总提交数的比例，便于比较提交量不同的贡献者
+    #[serde(default)]
+    pub commit_hours_normalized: HashMap<u32, f64>,
+    /// 各时区的提交数
+    #[serde(default)]
+    pub timezone_stats: HashMap<String, usize>,
+    /// 提交时区分布的香农熵（比特），越高说明提交时区越分散、判定越不可靠
+    #[serde(default)]
+    pub timezone_entropy: f64,
+    /// 参与分析的提交数
+    #[serde(default)]
+    pub commits_count: usize,
+    /// 分析结果的数据来源
+    #[serde(default)]
+    pub data_source: DataSource,
+    /// 用户公开所属的中国组织
+    #[serde(default)]
+    pub china_orgs: Vec<String>,
+    /// 最早的提交时间（作者本地时间）
+    #[serde(default)]
+    pub first_commit_at: Option<DateTime<FixedOffset>>,
+    /// 最近的提交时间（作者本地时间）
+    #[serde(default)]
+    pub last_commit_at: Option<DateTime<FixedOffset>>,
+    /// 日期无法解析而未参与分析的提交数
+    #[serde(default)]
+    pub unparsed_commits: usize,
+    /// 作者日期没有可用时区、改用提交者日期时区的提交数
+    #[serde(default)]
+    pub committer_timezone_commits: usize,
+    /// 结合资料位置或邮箱域名从常用时区的候选国家/地区中选出的最可能结果
+    #[serde(default)]
+    pub region: Option<String>,
+    /// 仓库README中提到中国城市或公司，已按较低权重计入来自中国的概率
+    #[serde(default)]
+    pub readme_hint: bool,
+}
+
+impl ContributorAnalysis {
+    /// 每小时（0-23）的提交数，没有提交的小时为0
+    pub fn commit_hours_vec(&self) -> [usize; 24] {
+        dense_commit_hours(&self.commit_hours)
+    }
+
+    /// 当来自中国的概率落在`[0.5-gap, 0.5+gap]`区间内时标记为不确定
+    pub fn mark_uncertain(&mut self, confidence_gap: f64) {
+        self.uncertain = self.classification_source == ClassificationSource::Heuristic
+            && (self.china_probability - 0.5).abs() <= confidence_gap;
+    }
+
+    /// 判定结果的置信度（0-1）：来自覆盖列表为1，没有提交记录为0，
+    /// 其余为来自中国的概率与0.5的距离（乘以2归一化）
+    pub fn confidence_score(&self) -> f64 {
+        if self.classification_source == ClassificationSource::Override {
+            return 1.0;
+        }
+        if self.commits_count == 0 {
+            return 0.0;
+        }
+        ((self.china_probability - 0.5).abs() * 2.0).min(1.0)
+    }
+
+    /// 用模拟结果替换判定：来自中国的概率设为`probability`，是否来自中国按该概率随机抽取，
+    /// 覆盖列表中的判定保持不变
+    pub fn simulate_china(&mut self, probability: f64) {
+        if self.classification_source == ClassificationSource::Override {
+            return;
+        }
+        self.china_probability = probability;
+        self.from_china = rand::thread_rng().gen_bool(probability);
+        self.uncertain = false;
+        self.classification_source = ClassificationSource::Simulated;
+    }
+
+    /// 各判定信号的得分（0-1，越高越倾向来自中国），按信号名称列出，用于解释判定结果：
+    /// 时区占比总是存在，组织、地区消歧和覆盖列表只在对应信号生效时出现
+    pub fn classification_signals(&self) -> Vec<(String, f64)> {
+        let score = |china: bool| if china { 1.0 } else { 0.0 };
+
+        let mut signals = vec![("timezone_share".to_string(), self.china_probability)];
+        if !self.china_orgs.is_empty() {
+            signals.push(("china_org".to_string(), 1.0));
+        }
+        if let Some(region) = &self.region {
+            signals.push(("region_hint".to_string(), score(region == "CN")));
+        }
+        if self.readme_hint {
+            signals.push(("readme_location".to_string(), 1.0));
+        }
+        match self.classification_source {
+            ClassificationSource::Override => {
+                signals.push(("override".to_string(), score(self.from_china)))
+            }
+            ClassificationSource::Simulated => {
+                signals.push(("simulated".to_string(), score(self.from_china)))
+            }
+            ClassificationSource::Heuristic => {}
+        }
+        signals
+    }
+
+    /// 判定的国家/地区代码（ISO-3166）：来自中国时为CN，否则为消歧得到的其他国家/地区，未知时为None
+    pub fn region_code(&self) -> Option<&str> {
+        match (self.from_china, self.region.as_deref()) {
+            (true, _) => Some("CN"),
+            (false, Some(region)) if region != "CN" => Some(region),
+            _ => None,
+        }
+    }
+
+    /// 常用时区对应多个国家/地区时，用资料中的位置或邮箱域名选出其中一个，
+    /// 常用时区为中国时区时据此修正是否来自中国；没有可用线索时保持不变
+    pub fn disambiguate_region(&mut self, location: Option<&str>, email: Option<&str>) {
+        if self.classification_source != ClassificationSource::Heuristic
+            || !self.china_orgs.is_empty()
+        {
+            return;
+        }
+
+        let candidates = timezone_candidate_countries(&self.common_timezone);
+        if candidates.len() < 2 {
+            return;
+        }
+
+        let Some(region) = location
+            .and_then(country_from_location)
+            .or_else(|| email.and_then(country_from_email))
+            .filter(|code| candidates.contains(code))
+        else {
+            return;
+        };
+
+        self.region = Some(region.to_string());
+        if is_china_timezone(&self.common_timezone) {
+            self.from_china = region == "CN";
+        }
+    }
+
+    /// 用户是中国组织的公开成员时判定为来自中国
+    pub fn apply_org_membership(&mut self, china_orgs: Vec<String>) {
+        if china_orgs.is_empty() || self.classification_source != ClassificationSource::Heuristic {
+            return;
+        }
+        self.from_china = true;
+        self.china_orgs = china_orgs;
+    }
+
+    /// 丢弃提交占比低于`min_share`的时区（如出差时的零星提交），并据此重新计算判定结果
+    pub fn apply_min_timezone_share(&mut self, min_share: f64) {
+        if self.classification_source != ClassificationSource::Heuristic {
+            return;
+        }
+
+        let total: usize = self.timezone_stats.values().sum();
+        let kept: HashMap<String, usize> = self
+            .timezone_stats
+            .iter()
+            .filter(|(_, &count)| count as f64 / total as f64 >= min_share)
+            .map(|(tz, &count)| (tz.clone(), count))
+            .collect();
+        // 阈值过高导致所有时区都被丢弃时保留原结果
+        if kept.is_empty() || kept.len() == self.timezone_stats.len() {
+            return;
+        }
+
+        let kept_total: usize = kept.values().sum();
+        let china_commits: usize = kept
+            .iter()
+            .filter(|(tz, _)| is_china_timezone(tz))
+            .map(|(_, &count)| count)
+            .sum();
+
+        self.china_probability = china_commits as f64 / kept_total as f64;
+        self.from_china =
+            self.china_probability >= DEFAULT_CHINA_THRESHOLD || !self.china_orgs.is_empty();
+        self.common_timezone = most_common_timezone(&kept);
+        self.timezone_offset_minutes = parse_timezone_offset(&self.common_timezone).unwrap_or(0);
+        self.timezone_entropy = timezone_entropy(&kept);
+        self.timezone_stats = kept;
+    }
+
+    /// 按概率阈值重新判定：来自中国的概率不低于`threshold`时判定为来自中国，
+    /// 属于中国组织或来自覆盖列表的判定保持不变
+    pub fn apply_china_threshold(&mut self, threshold: f64) {
+        if self.classification_source != ClassificationSource::Heuristic
+            || !self.china_orgs.is_empty()
+            || self.commits_count == 0
+        {
+            return;
+        }
+        self.from_china = self.china_probability >= threshold;
+    }
+
+    /// 仓库README中提到中国城市或公司时，按`README_HINT_WEIGHT`把该线索混入来自中国的概率：
+    /// p = (1 - w)·p + w；只作用于有提交记录的启发式判定，同一结果只混入一次
+    pub fn apply_readme_location_hint(&mut self, hints: &[String]) {
+        if hints.is_empty()
+            || self.readme_hint
+            || self.classification_source != ClassificationSource::Heuristic
+            || self.commits_count == 0
+        {
+            return;
+        }
+        self.china_probability =
+            (1.0 - README_HINT_WEIGHT) * self.china_probability + README_HINT_WEIGHT;
+        self.readme_hint = true;
+    }
+
+    /// 时区熵超过阈值时同样标记为不确定（如提交来自CI或历史被改写）
+    pub fn mark_high_entropy(&mut self, max_timezone_entropy: f64) {
+        if self.classification_source == ClassificationSource::Heuristic
+            && self.timezone_entropy > max_timezone_entropy
+        {
+            self.uncertain = true;
+        }
+    }
+}
+
+// 小时分布的JSON格式：新格式为长度24的数组，旧版本输出的是小时到提交数的映射
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CommitHoursJson {
+    Dense(Vec<usize>),
+    Sparse(HashMap<u32, usize>),
+}
+
+impl From<CommitHoursJson> for HashMap<u32, usize> {
+    fn from(json: CommitHoursJson) -> Self {
+        match json {
+            CommitHoursJson::Dense(counts) => counts
+                .into_iter()
+                .enumerate()
+                .filter(|(_, count)| *count > 0)
+                .map(|(hour, count)| (hour as u32, count))
+                .collect(),
+            CommitHoursJson::Sparse(hours) => hours,
+        }
+    }
+}
+
+/// 把稀疏的小时分布展开为长度24的数组，超出0-23的小时被忽略
+fn dense_commit_hours(hours: &HashMap<u32, usize>) -> [usize; 24] {
+    let mut dense = [0; 24];
+    for (&hour, &count) in hours {
+        if let Some(slot) = dense.get_mut(hour as usize) {
+            *slot += count;
+        }
+    }
+    dense
+}
+
+fn serialize_commit_hours<S: serde::Serializer>(
+    hours: &HashMap<u32, usize>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    dense_commit_hours(hours).serialize(serializer)
+}
+
+fn deserialize_commit_hours<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<HashMap<u32, usize>, D::Error> {
+    CommitHoursJson::deserialize(deserializer).map(Into::into)
+}
+
+/// 从存储的JSON中读取小时分布，兼容数组和映射两种格式
+pub fn commit_hours_from_json(value: serde_json::Value) -> Option<HashMap<u32, usize>> {
+    serde_json::from_value::<CommitHoursJson>(value)
+        .ok()
+        .map(Into::into)
+}
+
+/// 最近一次提交是否在参考时间之前的`window`时间内，没有提交时间的贡献者视为不活跃
+pub fn is_active(
+    analysis: &ContributorAnalysis,
+    reference_date: DateTime<Utc>,
+    window: chrono::Duration,
+) -> bool {
+    analysis
+        .last_commit_at
+        .is_some_and(|last| last >= reference_date - window)
+}
+
+/// 找出提交最多的时区，提交数相同时取字典序较小的时区，保证结果在多次运行间一致
+fn most_common_timezone(timezone_count: &HashMap<String, usize>) -> String {
+    timezone_count
+        .iter()
+        .max_by(|(tz_a, count_a), (tz_b, count_b)| count_a.cmp(count_b).then(tz_b.cmp(tz_a)))
+        .map(|(tz, _)| tz.clone())
+        .unwrap_or_else(|| "Unknown".to_string())
+}
+
+/// 计算时区分布的香农熵（以2为底）
+fn timezone_entropy(timezone_count: &HashMap<String, usize>) -> f64 {
+    // 只有一个时区时熵为0
+    let total: usize = timezone_count.values().sum();
+    if total == 0 || timezone_count.len() <= 1 {
+        return 0.0;
+    }
+
+    timezone_count
+        .values()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+// 判定结果来源
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClassificationSource {
+    /// 基于时区的启发式判定
+    #[default]
+    Heuristic,
+    /// 来自人工维护的覆盖列表
+    Override,
+    /// 模拟数据，仅用于开发报表和下游系统
+    Simulated,
+}
+
+// 分析结果的数据来源
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DataSource {
+    /// 基于本地Git历史中的提交
+    #[default]
+    GitHistory,
+    /// 本地历史中没有该作者的提交（如提交均被squash合并），只有GitHub资料
+    ProfileOnly,
+    /// 基于GitHub API返回的提交时间，没有提交小时分布
+    GithubApi,
+}
+
+impl DataSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataSource::GitHistory => "git_history",
+            DataSource::ProfileOnly => "profile_only",
+            DataSource::GithubApi => "github_api",
+        }
+    }
+}
+
+// 中国贡献者判定覆盖列表，用于修正已知的误判
+// 文件格式: {"logins": {"octocat": true}, "emails": {"someone@example.com": false}}
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ChinaOverrides {
+    #[serde(default)]
+    pub logins: HashMap<String, bool>,
+    #[serde(default)]
+    pub emails: HashMap<String, bool>,
+}
+
+impl ChinaOverrides {
+    /// 从JSON文件加载覆盖列表，登录名和邮箱均不区分大小写
+    pub fn load(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let overrides: ChinaOverrides = serde_json::from_str(&contents)?;
+
+        let overrides = ChinaOverrides {
+            logins: overrides
+                .logins
+                .into_iter()
+                .map(|(k, v)| (k.to_lowercase(), v))
+                .collect(),
+            emails: overrides
+                .emails
+                .into_iter()
+                .map(|(k, v)| (k.to_lowercase(), v))
+                .collect(),
+        };
+
+        info!(
+            "从 {} 加载了 {} 条登录名覆盖和 {} 条邮箱覆盖",
+            path,
+            overrides.logins.len(),
+            overrides.emails.len()
+        );
+
+        Ok(overrides)
+    }
+
+    /// 查找覆盖结果，登录名优先于邮箱
+    pub fn lookup(&self, login: Option<&str>, email: Option<&str>) -> Option<bool> {
+        login
+            .and_then(|l| self.logins.get(&l.to_lowercase()))
+            .or_else(|| email.and_then(|e| self.emails.get(&e.to_lowercase())))
+            .copied()
+    }
+
+    /// 将覆盖结果应用到分析结果，返回是否命中覆盖列表
+    pub fn apply(&self, login: Option<&str>, analysis: &mut ContributorAnalysis) -> bool {
+        match self.lookup(login, analysis.email.as_deref()) {
+            Some(from_china) => {
+                analysis.from_china = from_china;
+                analysis.china_probability = if from_china { 1.0 } else { 0.0 };
+                analysis.uncertain = false;
+                analysis.classification_source = ClassificationSource::Override;
+                true
+            }
+            None => false,
+        }
+    }
 }
 
-// 中国相关时区
-const CHINA_TIMEZONES: [&str; 4] = ["+0800", "+08:00", "CST", "Asia/Shanghai"];
+// 中国相关的时区名称
+const CHINA_TIMEZONE_NAMES: [&str; 2] = ["CST", "Asia/Shanghai"];
+
+// 中国时区的UTC偏移（分钟）
+const CHINA_OFFSET_MINUTES: i32 = 8 * 60;
+
+// 未配置概率阈值时使用的默认阈值：多数提交使用中国时区才判定为来自中国
+pub const DEFAULT_CHINA_THRESHOLD: f64 = 0.5;
+
+// 按偏移聚类时的容差（分钟）：相差不足30分钟的偏移视为同一时区，
+// 相差30分钟及以上的偏移（如+05:00与+05:30）仍能区分
+const TIMEZONE_CLUSTER_TOLERANCE_MINUTES: i32 = 30;
 
-/// 判断时区是否可能是中国时区
+/// 解析时区的UTC偏移（分钟），支持`+hhmm`、`+hh:mm`、`Z`和中国相关的时区名称，无法识别时返回None
+pub fn parse_timezone_offset(timezone: &str) -> Option<i32> {
+    let timezone = timezone.trim();
+    if timezone == "Z" {
+        return Some(0);
+    }
+    if CHINA_TIMEZONE_NAMES
+        .iter()
+        .any(|&name| timezone.contains(name))
+    {
+        return Some(CHINA_OFFSET_MINUTES);
+    }
+
+    let (sign, digits) = timezone.split_at_checked(1)?;
+    let sign = match sign {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let digits: String = digits.chars().filter(|c| *c != ':').collect();
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    if minutes >= 60 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// 判断时区是否可能是中国时区：UTC偏移与+08:00相差不足`TIMEZONE_CLUSTER_TOLERANCE_MINUTES`
 fn is_china_timezone(timezone: &str) -> bool {
-    CHINA_TIMEZONES.iter().any(|&tz| timezone.contains(tz))
+    parse_timezone_offset(timezone).is_some_and(|offset| {
+        (offset - CHINA_OFFSET_MINUTES).abs() < TIMEZONE_CLUSTER_TOLERANCE_MINUTES
+    })
+}
+
+// 常见UTC偏移对应的候选国家/地区（ISO-3166），按可能性大致排序，含夏令时
+const TIMEZONE_COUNTRIES: [(&str, &[&str]); 21] = [
+    ("-08:00", &["US", "CA", "MX"]),
+    ("-07:00", &["US", "CA", "MX"]),
+    ("-06:00", &["US", "CA", "MX"]),
+    ("-05:00", &["US", "CA", "CO", "PE"]),
+    ("-04:00", &["US", "CA", "CL", "VE"]),
+    ("-03:00", &["BR", "AR", "UY", "CL"]),
+    ("+00:00", &["GB", "PT", "IE", "IS"]),
+    ("+01:00", &["DE", "FR", "GB", "ES", "IT", "NL", "PL", "SE"]),
+    (
+        "+02:00",
+        &["DE", "FR", "ES", "IT", "NL", "PL", "SE", "FI", "UA", "ZA"],
+    ),
+    ("+03:00", &["RU", "TR", "UA", "FI", "IL", "SA"]),
+    ("+03:30", &["IR"]),
+    ("+04:30", &["AF"]),
+    ("+05:30", &["IN", "LK"]),
+    ("+05:45", &["NP"]),
+    ("+06:30", &["MM"]),
+    ("+07:00", &["VN", "TH", "ID"]),
+    (
+        "+08:00",
+        &["CN", "TW", "HK", "SG", "MY", "PH", "AU", "MO", "MN"],
+    ),
+    ("+09:00", &["JP", "KR"]),
+    ("+09:30", &["AU"]),
+    ("+10:00", &["AU"]),
+    ("+12:00", &["NZ"]),
+];
+
+// 资料位置中的关键字（小写）对应的国家/地区
+const LOCATION_KEYWORDS: [(&str, &str); 44] = [
+    ("china", "CN"),
+    ("中国", "CN"),
+    ("beijing", "CN"),
+    ("北京", "CN"),
+    ("shanghai", "CN"),
+    ("上海", "CN"),
+    ("shenzhen", "CN"),
+    ("深圳", "CN"),
+    ("hangzhou", "CN"),
+    ("杭州", "CN"),
+    ("guangzhou", "CN"),
+    ("广州", "CN"),
+    ("chengdu", "CN"),
+    ("成都", "CN"),
+    ("wuhan", "CN"),
+    ("nanjing", "CN"),
+    ("taiwan", "TW"),
+    ("taipei", "TW"),
+    ("台湾", "TW"),
+    ("台北", "TW"),
+    ("hong kong", "HK"),
+    ("hongkong", "HK"),
+    ("香港", "HK"),
+    ("macau", "MO"),
+    ("澳门", "MO"),
+    ("singapore", "SG"),
+    ("新加坡", "SG"),
+    ("malaysia", "MY"),
+    ("kuala lumpur", "MY"),
+    ("philippines", "PH"),
+    ("manila", "PH"),
+    ("australia", "AU"),
+    ("perth", "AU"),
+    ("sydney", "AU"),
+    ("melbourne", "AU"),
+    ("mongolia", "MN"),
+    ("japan", "JP"),
+    ("tokyo", "JP"),
+    ("korea", "KR"),
+    ("seoul", "KR"),
+    ("india", "IN"),
+    ("bangalore", "IN"),
+    ("united states", "US"),
+    ("canada", "CA"),
+];
+
+// 中国常用的邮箱服务商
+const CHINA_EMAIL_DOMAINS: [&str; 8] = [
+    "qq.com",
+    "163.com",
+    "126.com",
+    "sina.com",
+    "aliyun.com",
+    "foxmail.com",
+    "yeah.net",
+    "139.com",
+];
+
+// README中提到时说明仓库主要团队可能在中国的公司名称，城市名称使用LOCATION_KEYWORDS中的中国城市
+const README_COMPANY_KEYWORDS: [&str; 22] = [
+    "alibaba",
+    "阿里巴巴",
+    "ant group",
+    "蚂蚁集团",
+    "tencent",
+    "腾讯",
+    "baidu",
+    "百度",
+    "bytedance",
+    "字节跳动",
+    "huawei",
+    "华为",
+    "xiaomi",
+    "小米",
+    "meituan",
+    "美团",
+    "netease",
+    "网易",
+    "pingcap",
+    "jd.com",
+    "京东",
+    "didi",
+];
+
+// README位置线索在来自中国的概率中所占的权重
+const README_HINT_WEIGHT: f64 = 0.05;
+
+// 本地仓库中可能的README文件名
+const README_FILE_NAMES: [&str; 5] = [
+    "README.md",
+    "README",
+    "README.rst",
+    "README.txt",
+    "readme.md",
+];
+
+/// 读取本地仓库的README并提取其中的中国城市或公司线索，没有README时返回空列表
+pub fn local_readme_location_hints(repo_path: &str) -> Vec<String> {
+    README_FILE_NAMES
+        .iter()
+        .find_map(|name| std::fs::read_to_string(Path::new(repo_path).join(name)).ok())
+        .map(|readme| extract_location_keywords(&readme))
+        .unwrap_or_default()
+}
+
+/// 在README等文本中查找已知的中国城市和公司名称（不区分大小写），返回去重后的关键词
+///
+/// 英文关键词要求前后不是字母或数字，避免`baidu`之类的名称在更长的单词中误匹配；
+/// 泛指国家的“china”“中国”不计入，它们在文档中出现的原因太多。
+pub fn extract_location_keywords(text: &str) -> Vec<String> {
+    let text = text.to_lowercase();
+    let cities = LOCATION_KEYWORDS
+        .iter()
+        .filter(|(keyword, code)| *code == "CN" && *keyword != "china" && *keyword != "中国")
+        .map(|(keyword, _)| *keyword);
+
+    cities
+        .chain(README_COMPANY_KEYWORDS)
+        .filter(|keyword| {
+            if !keyword.is_ascii() {
+                return text.contains(keyword);
+            }
+            text.match_indices(keyword).any(|(start, _)| {
+                let before = text[..start].chars().next_back();
+                let after = text[start + keyword.len()..].chars().next();
+                !before.is_some_and(|c| c.is_ascii_alphanumeric())
+                    && !after.is_some_and(|c| c.is_ascii_alphanumeric())
+            })
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// 把时区字符串规范化为`+hh:mm`形式（与中国时区聚为一类的偏移规范化为+08:00），无法识别时返回None
+fn normalize_offset(timezone: &str) -> Option<String> {
+    if is_china_timezone(timezone) {
+        return Some("+08:00".to_string());
+    }
+    let offset = parse_timezone_offset(timezone)?;
+    // 偏移为0时使用+号，-00:00与+00:00相同
+    let sign = if offset < 0 { '-' } else { '+' };
+    Some(format!(
+        "{}{:02}:{:02}",
+        sign,
+        offset.abs() / 60,
+        offset.abs() % 60
+    ))
+}
+
+/// 时区对应的候选国家/地区（ISO-3166），如`+0800`可能是中国，也可能是新加坡、马来西亚、西澳等
+pub fn timezone_candidate_countries(timezone: &str) -> Vec<&'static str> {
+    let Some(offset) = normalize_offset(timezone) else {
+        return Vec::new();
+    };
+    TIMEZONE_COUNTRIES
+        .iter()
+        .find(|(tz, _)| *tz == offset)
+        .map(|(_, countries)| countries.to_vec())
+        .unwrap_or_default()
+}
+
+/// 从资料中的位置文字识别国家/地区
+fn country_from_location(location: &str) -> Option<&'static str> {
+    let location = location.to_lowercase();
+    LOCATION_KEYWORDS
+        .iter()
+        .find(|(keyword, _)| location.contains(keyword))
+        .map(|(_, code)| *code)
+}
+
+/// 从邮箱域名识别国家/地区：中国常用邮箱服务商，或国家/地区顶级域名
+fn country_from_email(email: &str) -> Option<&'static str> {
+    let domain = email_domain(email)?;
+    if CHINA_EMAIL_DOMAINS.contains(&domain.as_str()) {
+        return Some("CN");
+    }
+
+    let tld = domain.rsplit('.').next()?;
+    TIMEZONE_COUNTRIES
+        .iter()
+        .flat_map(|(_, countries)| countries.iter())
+        .find(|code| code.eq_ignore_ascii_case(tld))
+        .copied()
+}
+
+/// 邮箱的域名部分（小写），不是有效邮箱时返回None
+fn email_domain(email: &str) -> Option<String> {
+    let domain = email.rsplit_once('@')?.1.trim();
+    (!domain.is_empty()).then(|| domain.to_lowercase())
+}
+
+// 执行git命令时的全局选项
+#[derive(Debug, Clone, Default)]
+pub struct GitOptions {
+    /// 禁用提交签名校验，避免签名无效的提交被跳过
+    pub no_verify_commits: bool,
+    /// 使用仓库配置的log.date日期格式，而不是强制使用ISO 8601
+    pub respect_git_date_config: bool,
+    /// 只分析提交信息匹配该正则（POSIX扩展正则）的提交
+    pub commit_message_filter: Option<String>,
+}
+
+// 全局git选项实例
+static GIT_OPTIONS: Lazy<Mutex<GitOptions>> = Lazy::new(|| Mutex::new(GitOptions::default()));
+
+/// 设置执行git命令时使用的全局选项
+pub fn set_git_options(options: GitOptions) {
+    if options.no_verify_commits {
+        warn!("已禁用提交签名校验，分析时将忽略GPG签名");
+    }
+    if let Some(filter) = &options.commit_message_filter {
+        info!("只分析提交信息匹配 {} 的提交", filter);
+    }
+    *GIT_OPTIONS.lock().unwrap() = options;
+}
+
+/// 生成git log的格式参数，`prefix`为日期之前的其他占位符
+///
+/// 每行输出以`|`分隔的作者日期和提交者日期，作者日期没有可用时区时改用提交者日期。
+/// 默认通过`--date=iso-strict`强制输出ISO 8601日期，避免仓库的`log.date`配置改变输出格式。
+fn log_format_args(prefix: &str) -> Vec<String> {
+    let options = GIT_OPTIONS.lock().unwrap().clone();
+    let mut args = vec![format!("--format={}%ad|%cd", prefix)];
+    if !options.respect_git_date_config {
+        args.push("--date=iso-strict".to_string());
+    }
+    if let Some(filter) = &options.commit_message_filter {
+        args.push("--extended-regexp".to_string());
+        args.push(format!("--grep={}", filter));
+    }
+    args
+}
+
+/// 生成git log的`--author`参数
+///
+/// 设置了提交信息过滤时git按扩展正则匹配作者，需要转义邮箱中的`+`等元字符，
+/// 否则`123+user@users.noreply.github.com`这类邮箱匹配不到。
+fn author_args(author_email: &str) -> [String; 2] {
+    let options = GIT_OPTIONS.lock().unwrap().clone();
+    let pattern = if options.commit_message_filter.is_some() {
+        author_email.chars().fold(String::new(), |mut escaped, c| {
+            if "\\.^$|?*+()[]{}".contains(c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        })
+    } else {
+        author_email.to_string()
+    };
+    ["--author".to_string(), pattern]
+}
+
+/// 构建在指定仓库上执行的git命令，裸仓库（镜像）使用--git-dir，工作副本使用当前目录
+fn git_command(repo_path: &str) -> TokioCommand {
+    let options = GIT_OPTIONS.lock().unwrap().clone();
+    let mut command = TokioCommand::new("git");
+
+    if options.no_verify_commits {
+        command
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_NO_VERIFY", "1")
+            .args(["-c", "log.showSignature=false"]);
+    }
+
+    if Path::new(repo_path).join(".git").exists() {
+        command.current_dir(repo_path);
+    } else {
+        command.arg(format!("--git-dir={}", repo_path));
+    }
+    command
+}
+
+// 仓库使用的版本控制系统
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionControl {
+    Git,
+    Mercurial,
+}
+
+/// 根据仓库目录下的`.git`或`.hg`判断版本控制系统，裸仓库（镜像）视为Git
+pub fn detect_vcs(repo_path: &str) -> VersionControl {
+    let path = Path::new(repo_path);
+    if !path.join(".git").exists() && path.join(".hg").exists() {
+        VersionControl::Mercurial
+    } else {
+        VersionControl::Git
+    }
+}
+
+/// 构建在指定Mercurial仓库上执行的hg命令
+fn hg_command(repo_path: &str) -> TokioCommand {
+    let mut command = TokioCommand::new("hg");
+    // 忽略用户的hgrc，避免别名和扩展改变输出
+    command.env("HGPLAIN", "1").args(["-R", repo_path]);
+    command
+}
+
+/// 执行hg命令并返回标准输出，未安装hg时返回None
+async fn run_hg(repo_path: &str, args: &[&str]) -> Option<String> {
+    let output = match hg_command(repo_path).args(args).output().await {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            warn!("未安装hg，无法分析Mercurial仓库: {}", repo_path);
+            return None;
+        }
+        Err(e) => {
+            warn!("执行hg命令失败: {}", e);
+            return None;
+        }
+    };
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// 获取Mercurial仓库中作者的所有提交
+///
+/// Mercurial不支持提交信息过滤，设置了`--commit-message-filter`时仍分析所有提交。
+async fn get_hg_author_commits(
+    repo_path: &str,
+    author_email: &str,
+) -> Option<(Vec<CommitInfo>, usize)> {
+    if GIT_OPTIONS.lock().unwrap().commit_message_filter.is_some() {
+        debug!(
+            "Mercurial仓库不支持提交信息过滤，分析 {} 的所有提交",
+            author_email
+        );
+    }
+    let stdout = run_hg(
+        repo_path,
+        &[
+            "log",
+            "-u",
+            author_email,
+            "--template",
+            "{date|isodatesec}\n",
+        ],
+    )
+    .await?;
+
+    Some(parse_commit_lines(stdout.lines(), parse_commit_date))
 }
 
 /// 分析贡献者的时区统计
@@ -34,67 +888,173 @@ pub async fn analyze_contributor_timezone(
     debug!("分析作者 {} 的时区统计", author_email);
 
     // 获取提交时区分布
-    let commits = match get_author_commits(repo_path, author_email).await {
+    let commits = match detect_vcs(repo_path) {
+        VersionControl::Git => get_author_commits(repo_path, author_email).await,
+        VersionControl::Mercurial => get_hg_author_commits(repo_path, author_email).await,
+    };
+    let (commits, unparsed) = match commits {
         Some(commits) => commits,
         None => {
             warn!("无法获取作者提交: {}", author_email);
             return None;
         }
     };
+    if unparsed > 0 {
+        warn!(
+            "作者 {} 有 {} 个提交的日期无法解析，未参与分析",
+            author_email, unparsed
+        );
+    }
+
+    let mut analysis = build_contributor_analysis(author_email, &commits);
+    analysis.unparsed_commits = unparsed;
 
+    // 没有提交的作者（如提交均被squash合并）仍保留分析结果，避免从结果中丢失
     if commits.is_empty() {
-        warn!("作者没有提交记录: {}", author_email);
-        return None;
+        warn!("作者没有可用的提交记录，仅保留资料信息: {}", author_email);
+        analysis.data_source = DataSource::ProfileOnly;
     }
 
-    let mut has_china_timezone = false;
+    Some(analysis)
+}
+
+/// 根据作者的提交记录计算时区分析结果
+fn build_contributor_analysis(author_email: &str, commits: &[CommitInfo]) -> ContributorAnalysis {
+    let mut china_commits = 0;
     let mut timezone_count: HashMap<String, usize> = HashMap::new();
+    let mut commit_hours: HashMap<u32, usize> = HashMap::new();
 
     // 分析每个提交的时区
-    for commit in &commits {
+    for commit in commits {
         let timezone = &commit.timezone;
 
+        // 统计提交时间（提交者本地时间）的小时分布
+        *commit_hours.entry(commit.datetime.hour()).or_insert(0) += 1;
+
         // 更新时区统计
         *timezone_count.entry(timezone.clone()).or_insert(0) += 1;
 
         // 检查是否为中国时区
         if is_china_timezone(timezone) {
-            has_china_timezone = true;
+            china_commits += 1;
         }
     }
 
-    // 找出最常用的时区
-    let common_timezone = timezone_count
+    let china_probability = if commits.is_empty() {
+        0.0
+    } else {
+        china_commits as f64 / commits.len() as f64
+    };
+
+    // 归一化小时分布
+    let commit_hours_normalized = commit_hours
         .iter()
-        .max_by_key(|(_, &count)| count)
-        .map(|(tz, _)| tz.clone())
-        .unwrap_or_else(|| "Unknown".to_string());
+        .map(|(&hour, &count)| (hour, count as f64 / commits.len() as f64))
+        .collect();
+
+    // 找出最常用的时区
+    let common_timezone = most_common_timezone(&timezone_count);
+    let timezone_offset_minutes = parse_timezone_offset(&common_timezone).unwrap_or(0);
+
+    let timezone_entropy = timezone_entropy(&timezone_count);
 
-    let analysis = ContributorAnalysis {
+    ContributorAnalysis {
         email: Some(author_email.to_string()),
-        from_china: has_china_timezone,
+        from_china: !commits.is_empty() && china_probability >= DEFAULT_CHINA_THRESHOLD,
         common_timezone,
+        timezone_offset_minutes,
+        classification_source: ClassificationSource::Heuristic,
+        china_probability,
+        uncertain: false,
+        commit_hours,
+        commit_hours_normalized,
+        timezone_stats: timezone_count,
+        timezone_entropy,
+        commits_count: commits.len(),
+        data_source: DataSource::GitHistory,
+        china_orgs: Vec::new(),
+        first_commit_at: commits.iter().map(|c| c.datetime).min(),
+        last_commit_at: commits.iter().map(|c| c.datetime).max(),
+        unparsed_commits: 0,
+        committer_timezone_commits: commits
+            .iter()
+            .filter(|c| c.timezone_source == TimezoneSource::Committer)
+            .count(),
+        region: None,
+        readme_hint: false,
+    }
+}
+
+/// 根据时区分布（时区 -> 提交数）计算分析结果，用于没有本地克隆、只能通过API获取提交时区的情况
+pub fn build_analysis_from_timezone_stats(
+    author_email: &str,
+    timezone_stats: HashMap<String, usize>,
+) -> ContributorAnalysis {
+    let commits_count: usize = timezone_stats.values().sum();
+    let china_commits: usize = timezone_stats
+        .iter()
+        .filter(|(tz, _)| is_china_timezone(tz))
+        .map(|(_, &count)| count)
+        .sum();
+
+    let china_probability = if commits_count == 0 {
+        0.0
+    } else {
+        china_commits as f64 / commits_count as f64
     };
 
-    Some(analysis)
+    let common_timezone = most_common_timezone(&timezone_stats);
+    let timezone_offset_minutes = parse_timezone_offset(&common_timezone).unwrap_or(0);
+
+    ContributorAnalysis {
+        email: Some(author_email.to_string()),
+        from_china: commits_count > 0 && china_probability >= DEFAULT_CHINA_THRESHOLD,
+        common_timezone,
+        timezone_offset_minutes,
+        classification_source: ClassificationSource::Heuristic,
+        china_probability,
+        uncertain: false,
+        commit_hours: HashMap::new(),
+        commit_hours_normalized: HashMap::new(),
+        timezone_entropy: timezone_entropy(&timezone_stats),
+        timezone_stats,
+        commits_count,
+        data_source: DataSource::GithubApi,
+        china_orgs: Vec::new(),
+        first_commit_at: None,
+        last_commit_at: None,
+        unparsed_commits: 0,
+        committer_timezone_commits: 0,
+        region: None,
+        readme_hint: false,
+    }
 }
 
 #[derive(Debug)]
 struct CommitInfo {
     datetime: DateTime<FixedOffset>,
     timezone: String,
+    timezone_source: TimezoneSource,
+}
+
+// 提交时区的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimezoneSource {
+    /// 作者日期（%ad）
+    Author,
+    /// 作者日期没有可用的时区（如导入或打补丁的提交），改用提交者日期（%cd）
+    Committer,
 }
 
 /// 获取作者的所有提交
-async fn get_author_commits(repo_path: &str, author_email: &str) -> Option<Vec<CommitInfo>> {
-    let output = TokioCommand::new("git")
-        .current_dir(repo_path)
-        .args(&[
-            "log",
-            "--format=%aI", // ISO 8601 格式的作者日期
-            "--author",
-            author_email,
-        ])
+async fn get_author_commits(
+    repo_path: &str,
+    author_email: &str,
+) -> Option<(Vec<CommitInfo>, usize)> {
+    let output = git_command(repo_path)
+        .arg("log")
+        .args(log_format_args(""))
+        .args(author_args(author_email))
         .output()
         .await
         .ok()?;
@@ -104,44 +1064,251 @@ async fn get_author_commits(repo_path: &str, author_email: &str) -> Option<Vec<C
     }
 
     let stdout = String::from_utf8_lossy(&output.stdout);
-    let lines: Vec<&str> = stdout
-        .trim()
-        .split('\n')
-        .filter(|l| !l.is_empty())
-        .collect();
 
+    Some(parse_commit_lines(stdout.lines(), |line| {
+        let (author_date, committer_date) = line.rsplit_once('|').unwrap_or((line, ""));
+        parse_commit_dates(author_date, committer_date)
+    }))
+}
+
+/// 用`parse`解析每行一个的提交日期，返回解析成功的提交和无法解析的行数
+fn parse_commit_lines<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    parse: impl Fn(&str) -> Option<CommitInfo>,
+) -> (Vec<CommitInfo>, usize) {
     let mut commits = Vec::new();
+    let mut unparsed = 0;
 
-    for line in lines {
-        if let Ok(dt) = line.parse::<DateTime<FixedOffset>>() {
-            // 提取时区部分
-            let timezone = if let Some(pos) = line.rfind(|c| c == '+' || c == '-') {
-                line[pos..].to_string()
-            } else if line.contains("Z") {
-                "Z".to_string() // UTC
-            } else {
-                "Unknown".to_string()
-            };
+    for line in lines.map(str::trim).filter(|l| !l.is_empty()) {
+        match parse(line) {
+            Some(commit) => commits.push(commit),
+            None => {
+                debug!("无法解析提交日期: {}", line);
+                unparsed += 1;
+            }
+        }
+    }
 
-            commits.push(CommitInfo {
-                datetime: dt,
-                timezone,
-            });
+    (commits, unparsed)
+}
+
+// 使用仓库日期配置时尝试的其他日期格式（git的iso、default格式），
+// 以及时区不带冒号、日期和时间之间没有T等非严格的ISO写法
+const FALLBACK_DATE_FORMATS: [&str; 5] = [
+    "%Y-%m-%d %H:%M:%S %z",
+    "%a %b %e %H:%M:%S %Y %z",
+    "%Y-%m-%dT%H:%M:%S%z",
+    "%Y-%m-%d %H:%M:%S%z",
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+];
+
+/// 解析git的raw日期格式（`<Unix时间戳> <+hhmm>`）
+fn parse_raw_git_date(line: &str) -> Option<DateTime<FixedOffset>> {
+    let (timestamp, offset) = line.split_once(' ')?;
+    let timestamp: i64 = timestamp.parse().ok()?;
+    let offset = DateTime::parse_from_str(
+        &format!("1970-01-01 00:00:00 {}", offset),
+        "%Y-%m-%d %H:%M:%S %z",
+    )
+    .ok()?
+    .offset()
+    .to_owned();
+    DateTime::from_timestamp(timestamp, 0).map(|dt| dt.with_timezone(&offset))
+}
+
+/// 解析提交日期，优先按ISO 8601解析，失败时尝试其他带时区的格式
+fn parse_commit_date(line: &str) -> Option<CommitInfo> {
+    let line = line.trim();
+    let dt = line.parse::<DateTime<FixedOffset>>().ok().or_else(|| {
+        DateTime::parse_from_rfc2822(line).ok().or_else(|| {
+            FALLBACK_DATE_FORMATS
+                .iter()
+                .find_map(|fmt| DateTime::parse_from_str(line, fmt).ok())
+                .or_else(|| parse_raw_git_date(line))
+        })
+    })?;
+
+    // 提取时区部分
+    let timezone = if let Some(pos) = line.rfind(['+', '-']) {
+        line[pos..].to_string()
+    } else if line.contains("Z") {
+        "Z".to_string() // UTC
+    } else {
+        "Unknown".to_string()
+    };
+
+    Some(CommitInfo {
+        datetime: dt,
+        timezone,
+        timezone_source: TimezoneSource::Author,
+    })
+}
+
+/// 解析提交的作者日期，作者日期为空或无法解析出时区时改用提交者日期
+fn parse_commit_dates(author_date: &str, committer_date: &str) -> Option<CommitInfo> {
+    let author = parse_commit_date(author_date);
+    if author
+        .as_ref()
+        .is_some_and(|commit| parse_timezone_offset(&commit.timezone).is_some())
+    {
+        return author;
+    }
+
+    match parse_commit_date(committer_date)
+        .filter(|commit| parse_timezone_offset(&commit.timezone).is_some())
+    {
+        Some(commit) => {
+            debug!(
+                "作者日期 {:?} 没有可用的时区，改用提交者日期: {}",
+                author_date, committer_date
+            );
+            Some(CommitInfo {
+                timezone_source: TimezoneSource::Committer,
+                ..commit
+            })
+        }
+        None => author,
+    }
+}
+
+/// 单次遍历HEAD的提交历史（与逐个作者分析和shortlog一致），按作者邮箱分组计算所有贡献者的时区分析结果
+///
+/// 与逐个作者执行`git log --author`相比，只需要启动一个git进程，
+/// 结果按提交数量从多到少排序。
+pub async fn analyze_all_contributors_timezone(
+    repo_path: &str,
+) -> Option<Vec<ContributorAnalysis>> {
+    if !Path::new(repo_path).exists() {
+        error!("仓库路径不存在: {}", repo_path);
+        return None;
+    }
+
+    // Mercurial仓库只支持逐个作者分析
+    if detect_vcs(repo_path) == VersionControl::Mercurial {
+        return None;
+    }
+
+    let output = git_command(repo_path)
+        .args(["log", "HEAD"])
+        .args(log_format_args("%ae|"))
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // 按邮箱（不区分大小写）分组，保留首次出现的原始邮箱
+    let mut commits_by_author: HashMap<String, (String, Vec<CommitInfo>, usize)> = HashMap::new();
+
+    for line in stdout.lines().filter(|l| !l.is_empty()) {
+        // 每行为 邮箱|作者日期|提交者日期，邮箱中可能含有|，从右侧拆分
+        let mut fields = line.rsplitn(3, '|');
+        let (Some(committer_date), Some(author_date), Some(email)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if is_excluded_web_flow(None, Some(email)) {
+            continue;
+        }
+        let entry = commits_by_author
+            .entry(email.to_lowercase())
+            .or_insert_with(|| (email.to_string(), Vec::new(), 0));
+        // 日期无法解析的提交单独计数，避免作者因此从结果中消失
+        match parse_commit_dates(author_date, committer_date) {
+            Some(commit) => entry.1.push(commit),
+            None => {
+                debug!("无法解析提交日期: {}|{}", author_date, committer_date);
+                entry.2 += 1;
+            }
         }
     }
 
-    Some(commits)
+    let mut authors: Vec<(String, Vec<CommitInfo>, usize)> =
+        commits_by_author.into_values().collect();
+    authors.sort_by_key(|(_, commits, _)| std::cmp::Reverse(commits.len()));
+
+    let unparsed_total: usize = authors.iter().map(|(_, _, unparsed)| unparsed).sum();
+    info!("单次遍历提交历史得到 {} 个作者", authors.len());
+    if unparsed_total > 0 {
+        warn!("共有 {} 个提交的日期无法解析，未参与分析", unparsed_total);
+    }
+
+    Some(
+        authors
+            .iter()
+            .map(|(email, commits, unparsed)| {
+                let mut analysis = build_contributor_analysis(email, commits);
+                analysis.unparsed_commits = *unparsed;
+                if commits.is_empty() {
+                    analysis.data_source = DataSource::ProfileOnly;
+                }
+                analysis
+            })
+            .collect(),
+    )
+}
+
+/// 仓库贡献者的分析结果，分析失败的作者不会被静默丢弃
+#[derive(Debug, Default)]
+pub struct AnalysisResults {
+    pub analyses: Vec<ContributorAnalysis>,
+    /// 分析失败的作者及原因（邮箱, 错误信息），无法列出作者时邮箱位置为仓库路径
+    pub errors: Vec<(String, String)>,
 }
 
 /// 分析仓库的所有贡献者
-pub async fn analyze_repository_contributors(repo_path: &str) -> Vec<ContributorAnalysis> {
-    let mut results = Vec::new();
+pub async fn analyze_repository_contributors(
+    repo_path: &str,
+    overrides: &ChinaOverrides,
+    confidence_gap: Option<f64>,
+    max_timezone_entropy: Option<f64>,
+    min_timezone_share: Option<f64>,
+) -> AnalysisResults {
+    // 配置文件中校准过的概率阈值，未配置时使用默认阈值
+    let china_threshold = crate::config::get_china_threshold().unwrap_or(DEFAULT_CHINA_THRESHOLD);
+
+    // 优先使用单次遍历的方式一次性分析所有作者
+    if let Some(mut analyses) = analyze_all_contributors_timezone(repo_path).await {
+        for analysis in &mut analyses {
+            if let Some(min_share) = min_timezone_share {
+                analysis.apply_min_timezone_share(min_share);
+            }
+            analysis.apply_china_threshold(china_threshold);
+            let email = analysis.email.clone();
+            analysis.disambiguate_region(None, email.as_deref());
+            if let Some(gap) = confidence_gap {
+                analysis.mark_uncertain(gap);
+            }
+            if let Some(max_entropy) = max_timezone_entropy {
+                analysis.mark_high_entropy(max_entropy);
+            }
+            if overrides.apply(None, analysis) {
+                debug!("贡献者 {:?} 的判定来自覆盖列表", analysis.email);
+            }
+        }
+        return AnalysisResults {
+            analyses,
+            errors: Vec::new(),
+        };
+    }
+
+    warn!("单次遍历分析失败，回退到逐个作者分析: {}", repo_path);
+
+    let mut results = AnalysisResults::default();
 
     // 获取所有贡献者的邮箱
     let emails = match get_all_contributor_emails(repo_path).await {
         Some(emails) => emails,
         None => {
             error!("无法获取仓库贡献者邮箱: {}", repo_path);
+            results
+                .errors
+                .push((repo_path.to_string(), "无法获取仓库贡献者邮箱".to_string()));
             return results;
         }
     };
@@ -150,14 +1317,36 @@ pub async fn analyze_repository_contributors(repo_path: &str) -> Vec<Contributor
 
     // 分析每个贡献者
     for email in emails {
-        if let Some(analysis) = analyze_contributor_timezone(repo_path, &email).await {
-            debug!(
-                "分析完成: {} (可能来自中国: {})",
-                email,
-                if analysis.from_china { "是" } else { "否" }
-            );
-            results.push(analysis);
+        let Some(mut analysis) = analyze_contributor_timezone(repo_path, &email).await else {
+            results
+                .errors
+                .push((email, "无法获取作者的提交记录".to_string()));
+            continue;
+        };
+        if let Some(min_share) = min_timezone_share {
+            analysis.apply_min_timezone_share(min_share);
+        }
+        analysis.apply_china_threshold(china_threshold);
+        analysis.disambiguate_region(None, Some(&email));
+        if let Some(gap) = confidence_gap {
+            analysis.mark_uncertain(gap);
+        }
+        if let Some(max_entropy) = max_timezone_entropy {
+            analysis.mark_high_entropy(max_entropy);
         }
+        if overrides.apply(None, &mut analysis) {
+            debug!("贡献者 {} 的判定来自覆盖列表", email);
+        }
+        debug!(
+            "分析完成: {} (可能来自中国: {})",
+            email,
+            if analysis.from_china { "是" } else { "否" }
+        );
+        results.analyses.push(analysis);
+    }
+
+    if !results.errors.is_empty() {
+        warn!("{} 个作者分析失败: {}", results.errors.len(), repo_path);
     }
 
     results
@@ -165,9 +1354,12 @@ pub async fn analyze_repository_contributors(repo_path: &str) -> Vec<Contributor
 
 /// 获取所有贡献者的邮箱
 async fn get_all_contributor_emails(repo_path: &str) -> Option<Vec<String>> {
-    let output = TokioCommand::new("git")
-        .current_dir(repo_path)
-        .args(&["shortlog", "-sen", "HEAD"])
+    if detect_vcs(repo_path) == VersionControl::Mercurial {
+        return get_hg_contributor_emails(repo_path).await;
+    }
+
+    let output = git_command(repo_path)
+        .args(["shortlog", "-sen", "HEAD"])
         .output()
         .await
         .ok()?;
@@ -190,6 +1382,9 @@ async fn get_all_contributor_emails(repo_path: &str) -> Option<Vec<String>> {
         if let Some(email_start) = line.find('<') {
             if let Some(email_end) = line.find('>') {
                 let email = line[email_start + 1..email_end].trim().to_string();
+                if is_excluded_web_flow(None, Some(&email)) {
+                    continue;
+                }
                 emails.push(email);
             }
         }
@@ -198,10 +1393,44 @@ async fn get_all_contributor_emails(repo_path: &str) -> Option<Vec<String>> {
     Some(emails)
 }
 
+/// 获取Mercurial仓库中所有贡献者的邮箱，按首次出现的顺序去重
+async fn get_hg_contributor_emails(repo_path: &str) -> Option<Vec<String>> {
+    let stdout = run_hg(repo_path, &["log", "--template", "{author|email}\n"]).await?;
+
+    let mut seen = HashSet::new();
+    let emails = stdout
+        .lines()
+        .map(str::trim)
+        .filter(|email| {
+            !email.is_empty() && !is_excluded_web_flow(None, Some(email)) && seen.insert(*email)
+        })
+        .map(str::to_string)
+        .collect();
+
+    Some(emails)
+}
+
 /// 生成仓库贡献者分析报告
-pub async fn generate_contributors_report(repo_path: &str) -> ContributorsReport {
+pub async fn generate_contributors_report(
+    repo_path: &str,
+    overrides: &ChinaOverrides,
+    confidence_gap: Option<f64>,
+    max_timezone_entropy: Option<f64>,
+    min_timezone_share: Option<f64>,
+    active_window: chrono::Duration,
+) -> ContributorsReport {
     info!("正在为仓库 {} 生成贡献者分析报告", repo_path);
-    let all_analyses = analyze_repository_contributors(repo_path).await;
+    let AnalysisResults {
+        analyses: all_analyses,
+        errors: error_log,
+    } = analyze_repository_contributors(
+        repo_path,
+        overrides,
+        confidence_gap,
+        max_timezone_entropy,
+        min_timezone_share,
+    )
+    .await;
 
     // 获取中国贡献者和非中国贡献者的提交总数
     let china_commits: usize = all_analyses.iter().filter(|c| c.from_china).count();
@@ -214,11 +1443,63 @@ pub async fn generate_contributors_report(repo_path: &str) -> ContributorsReport
         0.0
     };
 
+    let overridden_contributors_count = all_analyses
+        .iter()
+        .filter(|c| c.classification_source == ClassificationSource::Override)
+        .count();
+
+    let uncertain_contributors_count = all_analyses.iter().filter(|c| c.uncertain).count();
+
+    let average_timezone_entropy = if all_analyses.is_empty() {
+        0.0
+    } else {
+        all_analyses.iter().map(|c| c.timezone_entropy).sum::<f64>() / all_analyses.len() as f64
+    };
+
+    let confidence_bands = ConfidenceBands::from_analyses(&all_analyses);
+    let concentration = contribution_concentration(&all_analyses);
+
+    // 按邮箱域名统计贡献者人数
+    let mut email_domain_stats = HashMap::new();
+    for domain in all_analyses
+        .iter()
+        .filter_map(|c| c.email.as_deref().and_then(email_domain))
+    {
+        *email_domain_stats.entry(domain).or_insert(0) += 1;
+    }
+
+    // 按最近提交时间区分活跃与不活跃的贡献者
+    let now = Utc::now();
+    let active: Vec<&ContributorAnalysis> = all_analyses
+        .iter()
+        .filter(|c| is_active(c, now, active_window))
+        .collect();
+    let active_contributors_count = active.len();
+    let inactive_contributors_count = all_analyses.len() - active_contributors_count;
+    let active_china = active.iter().filter(|c| c.from_china).count();
+    let china_percentage_active = if active.is_empty() {
+        0.0
+    } else {
+        active_china as f64 / active_contributors_count as f64 * 100.0
+    };
+
     ContributorsReport {
         total_contributors: all_analyses.len(),
         china_contributors_count: china_commits,
         non_china_contributors_count: non_china_commits,
         china_percentage,
+        overridden_contributors_count,
+        uncertain_contributors_count,
+        average_timezone_entropy,
+        confidence_bands,
+        concentration,
+        email_domain_stats,
+        failed_analyses: error_log.len(),
+        error_log,
+        active_contributors_count,
+        inactive_contributors_count,
+        china_percentage_active,
+        active_window_days: active_window.num_days(),
         contributors: all_analyses,
     }
 }
@@ -226,16 +1507,228 @@ pub async fn generate_contributors_report(repo_path: &str) -> ContributorsReport
 /// Error type for contributor analysis
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
+// 贡献者分析报告，JSON字段名同样属于对外数据契约
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ContributorsReport {
     pub total_contributors: usize,
     pub china_contributors_count: usize,
     pub non_china_contributors_count: usize,
     pub china_percentage: f64,
+    pub overridden_contributors_count: usize,
+    #[serde(default)]
+    pub uncertain_contributors_count: usize,
+    /// 所有贡献者时区熵的平均值，作为整体数据质量指标
+    #[serde(default)]
+    pub average_timezone_entropy: f64,
+    /// 按置信度划分的贡献者人数
+    #[serde(default)]
+    pub confidence_bands: ConfidenceBands,
+    /// 提交在贡献者之间的集中程度
+    #[serde(default)]
+    pub concentration: ConcentrationStats,
+    /// 各邮箱域名的贡献者人数
+    #[serde(default)]
+    pub email_domain_stats: HashMap<String, usize>,
+    /// 分析失败的作者数
+    #[serde(default)]
+    pub failed_analyses: usize,
+    /// 分析失败的作者及原因（邮箱, 错误信息）
+    #[serde(default)]
+    pub error_log: Vec<(String, String)>,
+    /// 活跃窗口内有提交的贡献者人数
+    #[serde(default)]
+    pub active_contributors_count: usize,
+    #[serde(default)]
+    pub inactive_contributors_count: usize,
+    /// 只统计活跃贡献者时中国贡献者的比例（百分比）
+    #[serde(default)]
+    pub china_percentage_active: f64,
+    /// 判断活跃所用的时间窗口（天）
+    #[serde(default)]
+    pub active_window_days: i64,
     pub contributors: Vec<ContributorAnalysis>,
 }
 
+// 按置信度划分的贡献者统计：高（>0.8）、中（0.5-0.8）、低（<0.5）
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ConfidenceBands {
+    pub high_confidence_count: usize,
+    pub medium_confidence_count: usize,
+    pub low_confidence_count: usize,
+    /// 只统计高置信度判定时中国贡献者的比例（百分比）
+    pub china_percentage_high_confidence: f64,
+}
+
+// 提交集中度统计，用于评估项目健康度
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ConcentrationStats {
+    /// 按提交数计算的基尼系数，0表示完全平均，接近1表示集中在少数人
+    pub gini: f64,
+    /// 贡献了80%提交所需的最少人数（巴士因子）
+    pub bus_factor: usize,
+    /// 提交最多的贡献者所占的提交比例（百分比）
+    pub top_pct: f64,
+}
+
+// 摘要中显示的邮箱域名数量
+const SUMMARY_TOP_EMAIL_DOMAINS: usize = 5;
+
+// Markdown报告中每个列表显示的贡献者数量
+const MARKDOWN_TOP_CONTRIBUTORS: usize = 10;
+
+// 巴士因子统计的提交占比阈值
+const BUS_FACTOR_SHARE: f64 = 0.8;
+
+/// 根据每个贡献者的提交数计算提交集中度，没有提交时各项为0
+pub fn contribution_concentration(analyses: &[ContributorAnalysis]) -> ConcentrationStats {
+    let mut commits: Vec<usize> = analyses.iter().map(|a| a.commits_count).collect();
+    let total: usize = commits.iter().sum();
+    if total == 0 {
+        return ConcentrationStats::default();
+    }
+
+    // 基尼系数：G = 2·Σ(i·x_i) / (n·Σx) - (n+1)/n，x按升序排列，i从1开始
+    commits.sort_unstable();
+    let n = commits.len() as f64;
+    let weighted: f64 = commits
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| (i + 1) as f64 * x as f64)
+        .sum();
+    let gini = 2.0 * weighted / (n * total as f64) - (n + 1.0) / n;
+
+    // 从提交最多的贡献者开始累加，直到达到阈值
+    let threshold = total as f64 * BUS_FACTOR_SHARE;
+    let mut cumulative = 0;
+    let mut bus_factor = 0;
+    for &count in commits.iter().rev() {
+        cumulative += count;
+        bus_factor += 1;
+        if cumulative as f64 >= threshold {
+            break;
+        }
+    }
+
+    let top = commits.last().copied().unwrap_or_default();
+    ConcentrationStats {
+        gini: gini.max(0.0),
+        bus_factor,
+        top_pct: top as f64 / total as f64 * 100.0,
+    }
+}
+
+/// 转义Markdown表格单元格中的特殊字符，避免`|`拆分单元格或`_`、`*`被解析为强调
+fn escape_markdown_cell(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '|' | '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' | '\r' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl ConfidenceBands {
+    fn from_analyses(analyses: &[ContributorAnalysis]) -> Self {
+        let mut bands = ConfidenceBands::default();
+        let mut high_confidence_china = 0;
+
+        for analysis in analyses {
+            let score = analysis.confidence_score();
+            if score > 0.8 {
+                bands.high_confidence_count += 1;
+                if analysis.from_china {
+                    high_confidence_china += 1;
+                }
+            } else if score >= 0.5 {
+                bands.medium_confidence_count += 1;
+            } else {
+                bands.low_confidence_count += 1;
+            }
+        }
+
+        if bands.high_confidence_count > 0 {
+            bands.china_percentage_high_confidence =
+                high_confidence_china as f64 / bands.high_confidence_count as f64 * 100.0;
+        }
+        bands
+    }
+}
+
 impl ContributorsReport {
+    /// 中国贡献者的提交数占全部提交数的比例（百分比）
+    pub fn china_commits_percentage(&self) -> f64 {
+        let total: usize = self.contributors.iter().map(|c| c.commits_count).sum();
+        if total == 0 {
+            return 0.0;
+        }
+        let china: usize = self
+            .contributors
+            .iter()
+            .filter(|c| c.from_china)
+            .map(|c| c.commits_count)
+            .sum();
+        china as f64 / total as f64 * 100.0
+    }
+
+    // 提示判定为来自中国、但常用时区同时对应其他国家/地区且无法消歧的贡献者
+    fn print_timezone_caveats(&self) {
+        let mut ambiguous: HashMap<String, usize> = HashMap::new();
+        for contributor in self.contributors.iter().filter(|c| {
+            c.from_china
+                && c.region.is_none()
+                && c.classification_source == ClassificationSource::Heuristic
+        }) {
+            if timezone_candidate_countries(&contributor.common_timezone).len() > 1 {
+                *ambiguous
+                    .entry(contributor.common_timezone.clone())
+                    .or_insert(0) += 1;
+            }
+        }
+
+        let mut ambiguous: Vec<(String, usize)> = ambiguous.into_iter().collect();
+        ambiguous.sort();
+        for (timezone, count) in ambiguous {
+            let others: Vec<&str> = timezone_candidate_countries(&timezone)
+                .into_iter()
+                .filter(|code| *code != "CN")
+                .collect();
+            info!(
+                "注意: 常用时区为 {} 的 {} 名中国贡献者也可能来自 {}",
+                timezone,
+                count,
+                others.join("/")
+            );
+        }
+    }
+
+    // 输出贡献者人数最多的邮箱域名，标出中国常用的邮箱服务商
+    fn print_top_email_domains(&self) {
+        let mut domains: Vec<(&String, &usize)> = self.email_domain_stats.iter().collect();
+        if domains.is_empty() {
+            return;
+        }
+        domains.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+        let top: Vec<String> = domains
+            .iter()
+            .take(SUMMARY_TOP_EMAIL_DOMAINS)
+            .map(|(domain, count)| {
+                if CHINA_EMAIL_DOMAINS.contains(&domain.as_str()) {
+                    format!("{} {} 人（中国邮箱）", domain, count)
+                } else {
+                    format!("{} {} 人", domain, count)
+                }
+            })
+            .collect();
+        info!("常用邮箱域名: {}", top.join(", "));
+    }
+
     pub fn print_summary(&self) {
         info!("贡献者分析报告摘要:");
         info!("--------------------------------------------------");
@@ -249,10 +1742,467 @@ impl ContributorsReport {
             self.non_china_contributors_count,
             100.0 - self.china_percentage
         );
+        if self.failed_analyses > 0 {
+            warn!("分析失败的作者: {} 人", self.failed_analyses);
+        }
+        if self.uncertain_contributors_count > 0 {
+            info!(
+                "其中 {} 人的判定处于不确定区间",
+                self.uncertain_contributors_count
+            );
+        }
+        info!("平均时区熵: {:.2} 比特", self.average_timezone_entropy);
+        self.print_timezone_caveats();
+        let unparsed_commits: usize = self.contributors.iter().map(|c| c.unparsed_commits).sum();
+        if unparsed_commits > 0 {
+            info!("日期无法解析的提交: {} 个", unparsed_commits);
+        }
+        info!(
+            "判定置信度: 高 {} 人, 中 {} 人, 低 {} 人",
+            self.confidence_bands.high_confidence_count,
+            self.confidence_bands.medium_confidence_count,
+            self.confidence_bands.low_confidence_count
+        );
+        info!(
+            "提交集中度: 基尼系数 {:.2}, {} 人贡献了80%的提交, 提交最多的贡献者占 {:.1}%",
+            self.concentration.gini, self.concentration.bus_factor, self.concentration.top_pct
+        );
+        self.print_top_email_domains();
+        info!(
+            "最近 {} 天内活跃的贡献者: {} 人, 不活跃: {} 人",
+            self.active_window_days,
+            self.active_contributors_count,
+            self.inactive_contributors_count
+        );
+        if self.active_contributors_count > 0 {
+            info!(
+                "活跃贡献者中的中国贡献者比例: {:.1}%",
+                self.china_percentage_active
+            );
+        }
+        if self.confidence_bands.high_confidence_count > 0 {
+            info!(
+                "高置信度判定中的中国贡献者比例: {:.1}%",
+                self.confidence_bands.china_percentage_high_confidence
+            );
+        }
+        if self.overridden_contributors_count > 0 {
+            info!(
+                "其中 {} 人的判定来自覆盖列表",
+                self.overridden_contributors_count
+            );
+        }
         info!("--------------------------------------------------");
     }
 
+    /// 输出GitHub风格的Markdown报告，包含摘要统计和中国/非中国贡献者的提交数排行，
+    /// 便于粘贴到issue或wiki中
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let _ = writeln!(md, "# 贡献者地理位置分析报告\n");
+
+        let _ = writeln!(md, "## 摘要\n");
+        let _ = writeln!(md, "| 指标 | 数值 |");
+        let _ = writeln!(md, "| --- | ---: |");
+        let mut stat = |name: &str, value: String| {
+            let _ = writeln!(md, "| {} | {} |", name, value);
+        };
+        stat("总贡献者", self.total_contributors.to_string());
+        stat(
+            "中国贡献者",
+            format!(
+                "{} ({:.1}%)",
+                self.china_contributors_count, self.china_percentage
+            ),
+        );
+        stat(
+            "非中国贡献者",
+            format!(
+                "{} ({:.1}%)",
+                self.non_china_contributors_count,
+                100.0 - self.china_percentage
+            ),
+        );
+        stat(
+            "不确定的判定",
+            self.uncertain_contributors_count.to_string(),
+        );
+        stat(
+            "来自覆盖列表的判定",
+            self.overridden_contributors_count.to_string(),
+        );
+        stat("分析失败的作者", self.failed_analyses.to_string());
+        stat(
+            "平均时区熵（比特）",
+            format!("{:.2}", self.average_timezone_entropy),
+        );
+        stat(
+            "判定置信度（高/中/低）",
+            format!(
+                "{} / {} / {}",
+                self.confidence_bands.high_confidence_count,
+                self.confidence_bands.medium_confidence_count,
+                self.confidence_bands.low_confidence_count
+            ),
+        );
+        stat("基尼系数", format!("{:.2}", self.concentration.gini));
+        stat("巴士因子", self.concentration.bus_factor.to_string());
+        stat(
+            "提交最多的贡献者占比",
+            format!("{:.1}%", self.concentration.top_pct),
+        );
+        stat(
+            &format!("最近 {} 天内活跃的贡献者", self.active_window_days),
+            format!(
+                "{} ({:.1}% 来自中国)",
+                self.active_contributors_count, self.china_percentage_active
+            ),
+        );
+
+        for (title, from_china) in [("中国贡献者", true), ("非中国贡献者", false)] {
+            let mut contributors: Vec<&ContributorAnalysis> = self
+                .contributors
+                .iter()
+                .filter(|c| c.from_china == from_china)
+                .collect();
+            contributors.sort_by(|a, b| {
+                b.commits_count
+                    .cmp(&a.commits_count)
+                    .then_with(|| a.email.cmp(&b.email))
+            });
+
+            let _ = writeln!(
+                md,
+                "\n## 提交最多的{}（前 {} 名）\n",
+                title, MARKDOWN_TOP_CONTRIBUTORS
+            );
+            if contributors.is_empty() {
+                let _ = writeln!(md, "无");
+                continue;
+            }
+            let _ = writeln!(
+                md,
+                "| # | 邮箱 | 提交数 | 常用时区 | 来自中国的概率 | 判定来源 |"
+            );
+            let _ = writeln!(md, "| ---: | --- | ---: | --- | ---: | --- |");
+            for (rank, c) in contributors
+                .iter()
+                .take(MARKDOWN_TOP_CONTRIBUTORS)
+                .enumerate()
+            {
+                let source = match c.classification_source {
+                    ClassificationSource::Heuristic => "启发式",
+                    ClassificationSource::Override => "覆盖列表",
+                    ClassificationSource::Simulated => "模拟数据",
+                };
+                let _ = writeln!(
+                    md,
+                    "| {} | {} | {} | {} | {:.2}{} | {} |",
+                    rank + 1,
+                    escape_markdown_cell(c.email.as_deref().unwrap_or("-")),
+                    c.commits_count,
+                    escape_markdown_cell(&c.common_timezone),
+                    c.china_probability,
+                    if c.uncertain { "（不确定）" } else { "" },
+                    source
+                );
+            }
+        }
+
+        md
+    }
+
     pub fn to_json(&self) -> Result<String> {
         Ok(serde_json::to_string_pretty(self)?)
     }
+
+    /// 输出不带缩进和换行的JSON，适合批量导出和网络传输
+    pub fn to_json_compact(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// 从`to_json`或`to_json_compact`的输出中读取报告，用于校验报告JSON可以完整还原
+    #[cfg(test)]
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    // 在临时目录中创建git仓库，按顺序提交(作者名, 作者邮箱, ISO 8601日期)
+    fn git_repo(name: &str, commits: &[(&str, &str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "contributor-analysis-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let git = |args: &[&str], envs: &[(&str, &str)]| {
+            let status = Command::new("git")
+                .current_dir(&dir)
+                .args(args)
+                .envs(envs.iter().copied())
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {:?}", args);
+        };
+        git(&["init", "-q", "-b", "main"], &[]);
+        for (author, email, date) in commits {
+            git(
+                &["commit", "-q", "--allow-empty", "-m", "commit"],
+                &[
+                    ("GIT_AUTHOR_NAME", author),
+                    ("GIT_AUTHOR_EMAIL", email),
+                    ("GIT_AUTHOR_DATE", date),
+                    ("GIT_COMMITTER_NAME", author),
+                    ("GIT_COMMITTER_EMAIL", email),
+                    ("GIT_COMMITTER_DATE", date),
+                ],
+            );
+        }
+        dir
+    }
+
+    // 按ISO 8601作者日期构造提交
+    fn commit(date: &str) -> CommitInfo {
+        parse_commit_dates(date, "").unwrap()
+    }
+
+    #[test]
+    fn commit_hours_vec_is_dense() {
+        let commits = [
+            commit("2024-01-01T02:10:00+08:00"),
+            commit("2024-01-02T02:50:00+08:00"),
+            commit("2024-01-03T15:00:00+08:00"),
+        ];
+        let analysis = build_contributor_analysis("a@example.com", &commits);
+
+        let mut expected = [0; 24];
+        expected[2] = 2;
+        expected[15] = 1;
+        assert_eq!(analysis.commit_hours_vec(), expected);
+
+        // 序列化为长度为24的数组，反序列化后得到相同的分布
+        let json = serde_json::to_value(&analysis).unwrap();
+        assert_eq!(json["commit_hours"], serde_json::json!(expected));
+        let parsed: ContributorAnalysis = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.commit_hours_vec(), expected);
+    }
+
+    #[test]
+    fn parse_commit_dates_falls_back_to_committer_timezone() {
+        // 作者日期没有时区时改用提交者日期
+        let info = parse_commit_dates("2024-03-01 10:00:00", "2024-03-01T12:30:00+05:30").unwrap();
+        assert_eq!(info.timezone, "+05:30");
+        assert_eq!(info.timezone_source, TimezoneSource::Committer);
+
+        // 作者日期为空时同样改用提交者日期
+        let info = parse_commit_dates("", "2024-03-01T12:30:00+08:00").unwrap();
+        assert_eq!(info.timezone, "+08:00");
+        assert_eq!(info.timezone_source, TimezoneSource::Committer);
+    }
+
+    #[test]
+    fn parse_commit_dates_prefers_author_timezone() {
+        let info =
+            parse_commit_dates("2024-03-01T10:00:00-05:00", "2024-03-01T12:30:00+08:00").unwrap();
+        assert_eq!(info.timezone, "-05:00");
+        assert_eq!(info.timezone_source, TimezoneSource::Author);
+    }
+
+    #[test]
+    fn parse_commit_dates_without_any_timezone() {
+        assert!(parse_commit_dates("", "").is_none());
+        assert!(parse_commit_dates("not a date", "also not a date").is_none());
+    }
+
+    #[test]
+    fn min_timezone_share_drops_one_off_noise() {
+        let mut commits: Vec<CommitInfo> = (0..200)
+            .map(|i| commit(&format!("2024-01-01T{:02}:00:00+08:00", i % 24)))
+            .collect();
+        commits.push(commit("2024-02-01T09:00:00-05:00"));
+        let mut analysis = build_contributor_analysis("a@example.com", &commits);
+        assert_eq!(analysis.timezone_stats.len(), 2);
+        assert!(analysis.china_probability < 1.0);
+
+        analysis.apply_min_timezone_share(0.01);
+
+        assert_eq!(analysis.timezone_stats.len(), 1);
+        assert_eq!(analysis.common_timezone, "+08:00");
+        assert_eq!(analysis.china_probability, 1.0);
+        assert_eq!(analysis.timezone_entropy, 0.0);
+        assert!(analysis.from_china);
+    }
+
+    #[test]
+    fn min_timezone_share_keeps_everything_when_all_would_be_dropped() {
+        let commits = [
+            commit("2024-01-01T10:00:00+08:00"),
+            commit("2024-01-02T10:00:00-05:00"),
+        ];
+        let mut analysis = build_contributor_analysis("a@example.com", &commits);
+
+        analysis.apply_min_timezone_share(0.9);
+
+        assert_eq!(analysis.timezone_stats.len(), 2);
+        assert_eq!(analysis.china_probability, 0.5);
+    }
+
+    #[test]
+    fn common_timezone_tie_break_is_stable() {
+        let stats: HashMap<String, usize> = [("+08:00", 3), ("-05:00", 3), ("+01:00", 1)]
+            .into_iter()
+            .map(|(tz, count)| (tz.to_string(), count))
+            .collect();
+        // HashMap的迭代顺序每次构建都可能不同，多次构建结果应一致
+        for _ in 0..20 {
+            let stats: HashMap<String, usize> = stats.clone().into_iter().collect();
+            assert_eq!(most_common_timezone(&stats), "+08:00");
+        }
+
+        let commits = [
+            commit("2024-01-01T10:00:00-05:00"),
+            commit("2024-01-02T10:00:00+08:00"),
+        ];
+        let analysis = build_contributor_analysis("a@example.com", &commits);
+        assert_eq!(analysis.common_timezone, "+08:00");
+        assert_eq!(most_common_timezone(&HashMap::new()), "Unknown");
+    }
+
+    #[tokio::test]
+    async fn web_flow_commits_are_excluded() {
+        assert!(is_excluded_web_flow(Some("web-flow"), None));
+        assert!(is_excluded_web_flow(None, Some("NoReply@GitHub.com")));
+        assert!(!is_excluded_web_flow(
+            Some("octocat"),
+            Some("1+octocat@users.noreply.github.com")
+        ));
+
+        let repo = git_repo(
+            "web-flow",
+            &[
+                ("Dev", "dev@example.com", "2024-01-01T10:00:00+08:00"),
+                ("GitHub", "noreply@github.com", "2024-01-02T10:00:00+00:00"),
+                ("Dev", "dev@example.com", "2024-01-03T10:00:00+08:00"),
+            ],
+        );
+        let repo_path = repo.to_string_lossy();
+
+        let emails = get_all_contributor_emails(&repo_path).await.unwrap();
+        assert_eq!(emails, vec!["dev@example.com".to_string()]);
+
+        let analyses = analyze_all_contributors_timezone(&repo_path).await.unwrap();
+        assert_eq!(analyses.len(), 1);
+        assert_eq!(analyses[0].email.as_deref(), Some("dev@example.com"));
+        assert_eq!(analyses[0].commits_count, 2);
+
+        std::fs::remove_dir_all(repo).unwrap();
+    }
+
+    // 用临时仓库生成包含中国和非中国贡献者的报告
+    async fn sample_report(name: &str) -> ContributorsReport {
+        let repo = git_repo(
+            name,
+            &[
+                ("Cn", "cn@example.com", "2024-01-01T10:00:00+08:00"),
+                ("Cn", "cn@example.com", "2024-01-02T11:00:00+08:00"),
+                ("Us", "us@example.org", "2024-01-03T09:00:00-05:00"),
+            ],
+        );
+        let report = generate_contributors_report(
+            &repo.to_string_lossy(),
+            &ChinaOverrides::default(),
+            Some(0.1),
+            None,
+            None,
+            chrono::Duration::days(365),
+        )
+        .await;
+        std::fs::remove_dir_all(repo).unwrap();
+        report
+    }
+
+    #[tokio::test]
+    async fn report_json_round_trips() {
+        let report = sample_report("round-trip").await;
+        assert_eq!(report.total_contributors, 2);
+        let expected = serde_json::to_value(&report).unwrap();
+
+        for json in [report.to_json().unwrap(), report.to_json_compact().unwrap()] {
+            let parsed = ContributorsReport::from_json(&json).unwrap();
+            assert_eq!(serde_json::to_value(&parsed).unwrap(), expected);
+        }
+        assert!(!report.to_json_compact().unwrap().contains('\n'));
+    }
+
+    // JSON对象的键（排序后）
+    fn json_keys(value: &serde_json::Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[tokio::test]
+    async fn json_key_sets_are_stable() {
+        let report = serde_json::to_value(sample_report("json-keys").await).unwrap();
+        assert_eq!(
+            json_keys(&report),
+            [
+                "active_contributors_count",
+                "active_window_days",
+                "average_timezone_entropy",
+                "china_contributors_count",
+                "china_percentage",
+                "china_percentage_active",
+                "concentration",
+                "confidence_bands",
+                "contributors",
+                "email_domain_stats",
+                "error_log",
+                "failed_analyses",
+                "inactive_contributors_count",
+                "non_china_contributors_count",
+                "overridden_contributors_count",
+                "total_contributors",
+                "uncertain_contributors_count",
+            ]
+        );
+        assert_eq!(
+            json_keys(&report["contributors"][0]),
+            [
+                "china_orgs",
+                "china_probability",
+                "classification_source",
+                "commit_hours",
+                "commit_hours_normalized",
+                "commits_count",
+                "committer_timezone_commits",
+                "common_timezone",
+                "data_source",
+                "email",
+                "first_commit_at",
+                "from_china",
+                "last_commit_at",
+                "readme_hint",
+                "region",
+                "timezone_entropy",
+                "timezone_offset_minutes",
+                "timezone_stats",
+                "uncertain",
+                "unparsed_commits",
+            ]
+        );
+    }
 }
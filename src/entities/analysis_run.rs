@@ -0,0 +1,36 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// 仓库的一次分析记录，保存分析报告和运行状态
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "analysis_runs")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub repository_id: String,
+    pub run_at: DateTime,
+    pub report_json: Json,
+    pub total_contributors: i32,
+    pub china_percentage: f32,
+    /// 运行状态：running、completed、failed、timed_out、invalidated
+    pub status: String,
+    pub error_message: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::program::Entity",
+        from = "Column::RepositoryId",
+        to = "super::program::Column::Id"
+    )]
+    Program,
+}
+
+impl Related<super::program::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Program.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
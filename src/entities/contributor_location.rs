@@ -11,8 +11,19 @@ pub struct Model {
     pub repository_id: String,
     pub user_id: i32,
     pub is_from_china: bool,
+    pub region_code: Option<String>,
     pub common_timezone: Option<String>,
+    pub uncertain: bool,
+    pub china_probability: Option<f64>,
+    pub commit_hours: Option<Json>,
+    pub commit_hours_normalized: Option<Json>,
+    pub timezone_stats: Option<Json>,
+    pub signals: Option<Json>,
+    pub data_source: String,
+    /// 提交统计的计算时间
     pub analyzed_at: DateTime,
+    /// 最近一次判定的时间，基于已存储数据重新判定时只更新该列
+    pub classified_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -61,8 +72,19 @@ impl From<(&str, i32, &crate::contributor_analysis::ContributorAnalysis)> for Ac
             repository_id: Set(repo_id.to_string()),
             user_id: Set(user_id),
             is_from_china: Set(analysis.from_china),
+            region_code: Set(analysis.region_code().map(str::to_string)),
             common_timezone: Set(Some(analysis.common_timezone.clone())),
+            uncertain: Set(analysis.uncertain),
+            china_probability: Set(Some(analysis.china_probability)),
+            commit_hours: Set(serde_json::to_value(analysis.commit_hours_vec()).ok()),
+            commit_hours_normalized: Set(
+                serde_json::to_value(&analysis.commit_hours_normalized).ok()
+            ),
+            timezone_stats: Set(serde_json::to_value(&analysis.timezone_stats).ok()),
+            signals: Set(serde_json::to_value(analysis.classification_signals()).ok()),
+            data_source: Set(analysis.data_source.as_str().to_string()),
             analyzed_at: Set(now),
+            classified_at: Set(Some(now)),
         }
     }
 }
@@ -0,0 +1,16 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// 登录名哈希与匿名登录名的映射，用于在匿名化输出后进行内部核对
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "login_anonymization_map")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub original_hash: String,
+    pub anonymized_login: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
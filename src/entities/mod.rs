@@ -1,10 +1,10 @@
+pub mod analysis_run;
+pub mod commit_file;
 pub mod contributor_location;
 pub mod github_user;
+pub mod login_anonymization_map;
 pub mod program;
 pub mod repository_contributor;
-
-// 重新导出所有实体模型
-pub use contributor_location::*;
-pub use github_user::*;
-pub use program::*;
-pub use repository_contributor::*;
+pub mod repository_sync_status;
+pub mod repository_topic;
+pub mod repository_traffic;
@@ -4,7 +4,8 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "programs")]
 pub struct Model {
-    #[sea_orm(primary_key)]
+    /// 仓库ID，由外部系统生成的字符串（TEXT），各表的repository_id都引用该列，统一按&str/String传递
+    #[sea_orm(primary_key, auto_increment = false)]
     pub id: String,
     pub name: String,
     pub github_url: Option<String>,
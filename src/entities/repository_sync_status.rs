@@ -0,0 +1,31 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// 仓库数据的同步状态，记录最近一次完成分析的时间和README中发现的位置线索
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "repository_sync_status")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub repository_id: String,
+    pub last_analyzed_at: DateTime,
+    /// README中提到的中国城市或公司名称，逗号分隔，没有时为NULL
+    pub readme_location_hints: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::program::Entity",
+        from = "Column::RepositoryId",
+        to = "super::program::Column::Id"
+    )]
+    Program,
+}
+
+impl Related<super::program::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Program.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,33 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// 仓库最近14天的访问与克隆统计，用于安排重新分析的优先级
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "repository_traffic")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub repository_id: String,
+    pub views_count: i64,
+    pub unique_visitors: i64,
+    pub clone_count: i64,
+    pub unique_cloners: i64,
+    pub fetched_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::program::Entity",
+        from = "Column::RepositoryId",
+        to = "super::program::Column::Id"
+    )]
+    Program,
+}
+
+impl Related<super::program::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Program.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,25 +1,71 @@
 use clap::{Parser, Subcommand};
 use dotenv::dotenv;
-use sea_orm::Database;
-use std::collections::HashMap;
+use sea_orm::ConnectOptions;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
+use std::ops::{Bound, RangeBounds};
 use std::path::Path;
-use std::process::Command;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::process::Command;
+use tokio::task::JoinSet;
 use tracing::{error, info, warn};
 
 // 导入模块
 mod config;
+mod context;
 mod contributor_analysis;
 mod entities;
 mod migrations;
 mod services;
 
-use crate::config::get_database_url;
-use crate::contributor_analysis::generate_contributors_report;
-use crate::migrations::setup_database;
-use crate::services::database::DbService;
-use crate::services::github_api::GitHubApiClient;
+use crate::config::{
+    average_request_delay, get_database_schema, get_database_url, get_read_database_url,
+    github_token_count, github_tokens, has_github_token, load_from_file, request_delay,
+    set_config_path, set_user_agent,
+};
+use crate::context::{connect_with_retries, SyncContext};
+use crate::contributor_analysis::{
+    generate_contributors_report, set_git_options, ChinaOverrides, GitOptions,
+};
+use crate::entities::program;
+use crate::services::database::{
+    ClassificationFilter, ContributorFilter, ContributorSort, ContributorSortField, DbService,
+    ProgramRow, UpsertSummary, DEFAULT_MAX_CONTRIBUTIONS,
+};
+use crate::services::github_api::{
+    parse_github_repo_url, parse_noreply_login, request_budget_exhausted, set_include_web_flow,
+    with_request_budget, GitHubApiClient, GitHubUser, RequestBudget,
+};
+
+// 默认的仓库克隆目录
+const DEFAULT_CLONE_DIR: &str = "/mnt/crates/github_source";
+
+// 获取用户资料遇到临时错误时的默认重试次数
+const DEFAULT_USER_DETAIL_RETRIES: u32 = 2;
+// --user-detail-retries允许的最大值
+const MAX_USER_DETAIL_RETRIES: u32 = 10;
+
+// 默认允许克隆的最大仓库大小（MB）
+const DEFAULT_MAX_CLONE_SIZE_MB: u64 = 500;
+
+// 通过API获取提交时区时，每个贡献者最多读取的提交数
+const API_TIMEZONE_MAX_COMMITS: u32 = 300;
+
+// 并发分析时输出工作任务进度的间隔
+const WORKER_STATUS_INTERVAL: Duration = Duration::from_secs(60);
+
+// GitHub API每小时的请求数上限：认证请求按令牌计算
+const AUTHENTICATED_HOURLY_LIMIT: u64 = 5000;
+const UNAUTHENTICATED_HOURLY_LIMIT: u64 = 60;
+
+// 单个仓库因请求数上限被推迟的最大次数，超过后不再限制该仓库的请求数
+const MAX_REQUEST_CAP_DEFERRALS: u32 = 20;
+
+// 中国贡献者比例超过告警阈值时的退出码，与其他错误（退出码1）区分
+const ALERT_EXIT_CODE: u8 = 2;
 
 // CLI 参数结构
 #[derive(Parser, Debug)]
@@ -35,10 +81,172 @@ struct Cli {
     #[arg(long)]
     sample_config: Option<String>,
 
+    /// 配置文件路径（.toml为TOML格式，其余为JSON格式），默认依次查找config.json和~/.config/crates-pro-sync/config.toml
+    #[arg(long, global = true)]
+    config: Option<String>,
+
     /// 分析贡献者地理位置
     #[arg(long)]
     analyze_contributors: Option<String>,
 
+    /// 贡献者分析报告的输出格式，markdown未指定输出文件时打印到标准输出
+    #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+    format: ReportFormat,
+
+    /// 分析结果目录，设置后把分析失败的作者及原因写入其中的analysis_errors.json
+    #[arg(long)]
+    output_dir: Option<String>,
+
+    /// 克隆仓库的根目录
+    #[arg(long, global = true, default_value = DEFAULT_CLONE_DIR)]
+    clone_dir: String,
+
+    /// 裸镜像根目录，存在 {DIR}/{owner}/{repo}.git 时直接分析镜像而不克隆
+    #[arg(long, global = true)]
+    mirror_base: Option<String>,
+
+    /// 中国贡献者判定覆盖列表文件（JSON），用于修正已知误判
+    #[arg(long, global = true)]
+    china_overrides: Option<String>,
+
+    /// 禁用提交签名校验，避免签名无效的提交被git log跳过
+    #[arg(long, global = true)]
+    no_verify_commits: bool,
+
+    /// 使用仓库配置的log.date日期格式（默认强制使用--date=iso-strict）
+    #[arg(long, global = true)]
+    respect_git_date_config: bool,
+
+    /// 只分析提交信息匹配该正则（POSIX扩展正则）的提交，例如 "^feat(\(.*\))?:"
+    #[arg(long, global = true, value_name = "REGEX")]
+    commit_message_filter: Option<String>,
+
+    /// 不确定区间半宽G，来自中国的概率落在[0.5-G, 0.5+G]内的贡献者标记为不确定
+    #[arg(long, global = true, value_parser = parse_f64_in(0.0..=0.5, "不确定区间半宽"))]
+    confidence_gap: Option<f64>,
+
+    /// 时区熵（比特）超过该值的贡献者标记为不确定，用于排除提交时区过于分散的数据
+    #[arg(long, global = true, value_parser = parse_f64_in(0.0.., "时区熵上限"))]
+    max_timezone_entropy: Option<f64>,
+
+    /// 时区最低提交占比（0-1），占比低于该值的时区在计算常用时区和概率前被丢弃，默认保留所有时区
+    #[arg(long, global = true, value_parser = parse_f64_in(0.0..=1.0, "概率"))]
+    min_timezone_share: Option<f64>,
+
+    /// 模拟判定：所有贡献者来自中国的概率设为该值，并按该概率随机判定，用于在没有真实数据时开发报表。
+    /// 会把模拟结果写入数据库，只能在--dry-run预览时使用，或设置CRATES_PRO_ALLOW_SIMULATE=1
+    #[arg(long, global = true, value_parser = parse_f64_in(0.0..=1.0, "概率"))]
+    simulate_china: Option<f64>,
+
+    /// 分析完成后中国贡献者人数比例（百分比，0-100）超过该值时输出告警并以退出码2退出
+    #[arg(long, global = true, value_parser = parse_f64_in(0.0..=100.0, "百分比"))]
+    alert_threshold_china_pct: Option<f64>,
+
+    /// 分析完成后中国贡献者提交数比例（百分比，0-100）超过该值时输出告警并以退出码2退出
+    #[arg(long, global = true, value_parser = parse_f64_in(0.0..=100.0, "百分比"))]
+    alert_threshold_china_commits_pct: Option<f64>,
+
+    /// 在debug级别输出执行的SQL语句（包括手写查询绑定的参数），用于排查慢查询和占位符问题
+    #[arg(long, global = true)]
+    dump_sql: bool,
+
+    /// 在info级别输出执行的每条SQL（手写查询在执行前输出），配合命令自身的--dry-run（或reset不加--yes）
+    /// 预览写入语句而不提交
+    #[arg(long, global = true)]
+    dry_run_show_sql: bool,
+
+    /// 启动时连接数据库（含设置表结构）失败后的重试次数，用于容器中数据库晚于本工具就绪的情况
+    #[arg(long, global = true, default_value_t = 0)]
+    db_connect_retries: u32,
+
+    /// 连接数据库重试的间隔（如 5s、1m）
+    #[arg(long, global = true, default_value = "2s", value_parser = parse_duration)]
+    db_connect_interval: Duration,
+
+    /// 只读模式：配置了只读副本（DATABASE_READ_URL）时只连接副本，不执行迁移，所有写入操作直接报错
+    #[arg(long, global = true)]
+    readonly: bool,
+
+    /// 按旧规则查找仓库：github_url包含owner/repo即可（可能匹配到其他主机上的同名仓库），
+    /// 找不到时再按仓库名称匹配；默认要求github.com/owner/repo精确匹配
+    #[arg(long, global = true)]
+    loose_url_match: bool,
+
+    /// 保留GitHub网页界面提交的web-flow身份（GitHub <noreply@github.com>），默认从贡献者中排除
+    #[arg(long, global = true)]
+    include_web_flow: bool,
+
+    /// 输出不带缩进的紧凑JSON（默认输出便于阅读的格式化JSON）
+    #[arg(long, global = true)]
+    compact: bool,
+
+    /// 请求GitHub API时使用的User-Agent，默认为crates-pro-sync/{版本}，也可通过GITHUB_USER_AGENT环境变量设置
+    #[arg(long, global = true)]
+    github_user_agent: Option<String>,
+
+    /// 报告中判断贡献者是否活跃的时间窗口，窗口内有提交的视为活跃（如 180d）
+    #[arg(long, global = true, default_value = "365d", value_parser = parse_duration)]
+    active_window: Duration,
+
+    /// 快速模式：不获取每个用户的资料，只用提交扫描得到的登录名、头像和贡献数存储贡献者
+    #[arg(long, global = true)]
+    no_user_details: bool,
+
+    /// 已知的中国组织（逗号分隔），设置后会查询每个贡献者公开所属的组织，成员判定为来自中国
+    #[arg(long, global = true, value_delimiter = ',')]
+    china_orgs: Vec<String>,
+
+    /// 无法获取本地仓库时，改用GitHub API获取贡献者的提交时区
+    #[arg(long, global = true)]
+    prefer_api_timezone: bool,
+
+    /// 克隆前检查仓库大小（MB），超过时跳过克隆，改用GitHub API获取提交时区
+    #[arg(long, global = true, default_value_t = DEFAULT_MAX_CLONE_SIZE_MB)]
+    max_clone_size_mb: u64,
+
+    /// 不检查仓库大小，总是克隆
+    #[arg(long, global = true)]
+    force_large_clone: bool,
+
+    /// 获取用户资料遇到超时等临时错误时的重试次数；用户不存在（404）时只用提交扫描得到的信息
+    #[arg(
+        long,
+        global = true,
+        default_value_t = DEFAULT_USER_DETAIL_RETRIES,
+        value_parser = clap::value_parser!(u32).range(0..=MAX_USER_DETAIL_RETRIES as i64)
+    )]
+    user_detail_retries: u32,
+
+    /// 扫描提交统计贡献者时，每处理N页就把部分统计写入数据库暂存表并释放内存，扫描结束后合并；
+    /// 用于内存受限时分析作者极多的仓库，默认全部在内存中统计
+    #[arg(long, global = true, value_name = "PAGES")]
+    stream_contributors_every: Option<u32>,
+
+    /// 单个贡献者在单个仓库中的贡献数上限，超过时视为API数据异常
+    #[arg(
+        long,
+        global = true,
+        default_value_t = DEFAULT_MAX_CONTRIBUTIONS,
+        value_parser = clap::value_parser!(i32).range(1..)
+    )]
+    max_contributions: i32,
+
+    /// 贡献数超过--max-contributions时报错而不是截断
+    #[arg(long, global = true)]
+    reject_excess_contributions: bool,
+
+    /// 输出中用contributor_<哈希>替换GitHub登录名，便于公开分享
+    #[arg(long, global = true)]
+    anonymize_logins: bool,
+
+    /// 启动时检查GitHub令牌必须具有的权限范围，逗号分隔（如 public_repo,read:org）
+    #[arg(long, global = true, value_delimiter = ',')]
+    require_scopes: Vec<String>,
+
+    /// PostgreSQL schema（默认读取DB_SCHEMA环境变量，未设置时为public）
+    #[arg(long, global = true)]
+    db_schema: Option<String>,
+
     /// 子命令
     #[command(subcommand)]
     command: Option<Commands>,
@@ -53,6 +261,192 @@ enum Commands {
 
         /// 仓库名称
         repo: String,
+
+        /// 完成分析后持续监视仓库的新提交
+        #[arg(long)]
+        watch: bool,
+
+        /// 监视模式下的轮询间隔（秒）
+        #[arg(long, default_value_t = 300)]
+        poll_interval_secs: u64,
+    },
+
+    /// 分析数据库中所有已注册的仓库
+    AnalyzeAll {
+        /// 跳过在该时间窗口内分析过的仓库（如 12h、7d）
+        #[arg(long, value_parser = parse_duration)]
+        skip_recent: Option<Duration>,
+
+        /// 按访问量安排分析顺序的权重，设置后优先分析过期时间长、访问量大的仓库
+        #[arg(long, value_parser = parse_f64_in(0.0.., "访问量权重"))]
+        traffic_weight: Option<f64>,
+
+        /// 跳过fork仓库，避免上游贡献者被计入
+        #[arg(long)]
+        exclude_forks: bool,
+
+        /// 并发分析仓库的工作任务数，各任务从共享队列中领取下一个仓库
+        #[arg(
+            long,
+            visible_alias = "parallel-repos",
+            default_value_t = 1,
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        spawn_workers: u32,
+
+        /// 只分析带有该主题标签的仓库，尚未记录主题的仓库会先通过API获取
+        #[arg(long)]
+        filter_topic: Option<String>,
+
+        /// 任一仓库分析失败时立即停止并以非零状态退出（未登记或在GitHub上不存在的仓库仍然跳过）
+        #[arg(long)]
+        fail_fast: bool,
+
+        /// 单个仓库每轮最多消耗的GitHub请求数，超出时推迟该仓库、先分析其他仓库，之后再继续
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        per_repo_request_cap: Option<u64>,
+
+        /// 开始前根据数据库中的统计估算API请求数和耗时，确认后再分析
+        #[arg(long)]
+        estimate: bool,
+    },
+
+    /// 从CSV文件（表头为name,github_url）导入仓库到programs表
+    ImportPrograms {
+        /// CSV文件路径
+        csv: String,
+    },
+
+    /// 合并同一个人的两条用户记录（如登录名变更），保留第一个用户
+    MergeUsers {
+        /// 保留的用户登录名
+        keep_login: String,
+
+        /// 合并后删除的用户登录名
+        drop_login: String,
+
+        /// 只显示将受影响的记录数，不实际修改
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// 修复数据库中的缺失数据
+    Backfill {
+        /// 按登录名重新获取GitHub资料，为github_id无效（不大于0）的用户写入真实ID，
+        /// 真实ID已属于其他用户时合并两条记录
+        #[arg(long)]
+        github_ids: bool,
+
+        /// 只显示将受影响的用户数，不实际修改
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// 清除仓库的分析数据（位置记录、贡献数和分析时间），以便从头重新分析
+    Reset {
+        /// 仓库所有者
+        owner: String,
+
+        /// 仓库名称
+        repo: String,
+
+        /// 确认执行，不加时只显示将受影响的记录数
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// 使用当前配置，基于已存储的时区分布重新判定所有贡献者，不重新拉取提交
+    ReclassifyAll {
+        /// 只统计判定会变化的数量，不写回数据库
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// 使用人工标注的CSV（github_login, true_is_china）校准来自中国的概率阈值
+    Calibrate {
+        /// 标注文件路径
+        csv: String,
+
+        /// 把F1最高的阈值写入配置文件
+        #[arg(long)]
+        save_threshold: bool,
+    },
+
+    /// 列出数据过期的仓库
+    Stale {
+        /// 超过该时长未分析的仓库视为过期（如 7d）
+        #[arg(long, value_parser = parse_duration)]
+        older_than: Duration,
+
+        /// 同时通过GitHub统计接口列出每个过期仓库最近4周的提交数，便于判断哪些仓库需要优先重新分析
+        #[arg(long)]
+        activity: bool,
+    },
+
+    /// 清理克隆目录中长时间未更新的仓库
+    CleanCache {
+        /// 删除最后修改时间早于该时长的克隆（如 30d）
+        #[arg(long, value_parser = parse_duration)]
+        older_than: Duration,
+    },
+
+    /// 列出中国贡献者比例最高的仓库
+    TopRepos {
+        /// 已分析贡献者少于该数量的仓库不参与排名
+        #[arg(long, default_value_t = 10)]
+        min_contributors: u32,
+
+        /// 最多显示的仓库数量
+        #[arg(long, default_value_t = 20)]
+        limit: u32,
+
+        /// 只统计带有该主题标签的仓库（使用分析时记录的主题）
+        #[arg(long)]
+        filter_topic: Option<String>,
+    },
+
+    /// 跨所有已分析仓库列出总贡献数最多的贡献者
+    Leaderboard {
+        /// 统计的贡献者范围
+        #[arg(long, value_enum, default_value_t = LeaderboardRegion::China)]
+        region: LeaderboardRegion,
+
+        /// 最多显示的贡献者数量
+        #[arg(long, default_value_t = 20)]
+        limit: u32,
+    },
+
+    /// 输出单个作者的时区分析结果（JSON），用于调试判定
+    Inspect {
+        /// 本地仓库路径
+        repo_path: String,
+
+        /// 作者邮箱
+        email: String,
+    },
+
+    /// 配置文件相关操作
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommands,
+    },
+
+    /// 查询单个用户的数据
+    User {
+        #[command(subcommand)]
+        action: UserCommands,
+    },
+
+    /// 显示贡献者在仓库中已存储的判定结果及各判定信号的得分
+    Show {
+        /// 仓库所有者
+        owner: String,
+
+        /// 仓库名称
+        repo: String,
+
+        /// GitHub登录名
+        login: String,
     },
 
     /// 查询仓库贡献者统计
@@ -62,49 +456,294 @@ enum Commands {
 
         /// 仓库名称
         repo: String,
+
+        /// 只显示来自中国概率不低于该值的贡献者（0.0-1.0）
+        #[arg(long, value_parser = parse_f64_in(0.0..=1.0, "概率"))]
+        min_china_probability: Option<f64>,
+
+        /// 只显示来自中国概率不高于该值的贡献者（0.0-1.0）
+        #[arg(long, value_parser = parse_f64_in(0.0..=1.0, "概率"))]
+        max_china_probability: Option<f64>,
+
+        /// 只显示判定来自中国的贡献者
+        #[arg(long, conflicts_with_all = ["only_non_china", "unanalyzed"])]
+        only_china: bool,
+
+        /// 只显示判定不来自中国的贡献者
+        #[arg(long, conflicts_with = "unanalyzed")]
+        only_non_china: bool,
+
+        /// 只显示尚未进行位置分析的贡献者
+        #[arg(long)]
+        unanalyzed: bool,
+
+        /// 只显示判定属于该国家/地区（ISO-3166代码，如 CN）的贡献者
+        #[arg(long, conflicts_with_all = ["only_china", "only_non_china", "unanalyzed"])]
+        region: Option<String>,
+
+        /// 排序字段（使用该字段的默认方向：登录名升序，其余降序）
+        #[arg(long, value_enum, conflicts_with = "contributors_sort")]
+        sort_by: Option<ContributorSortField>,
+
+        /// 排序字段和方向，格式为 字段[:asc|desc]（如 followers:desc、login:asc）
+        #[arg(long, value_name = "FIELD[:DIRECTION]")]
+        contributors_sort: Option<ContributorSort>,
+
+        /// 以JSON格式输出全部查询结果
+        #[arg(long)]
+        json: bool,
+
+        /// 对JSON输出应用JSONPath表达式，只输出匹配的值（如 "$.contributors[?(@.china_probability > 0.8)]"）
+        #[arg(long, requires = "json")]
+        json_path: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum UserCommands {
+    /// 列出用户参与的所有已登记仓库及其在各仓库中的判定结果
+    Repos {
+        /// GitHub登录名
+        login: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// 加载并校验配置文件，不连接数据库
+    Validate {
+        /// 配置文件路径
+        path: String,
     },
 }
 
+// 仓库分析选项
+#[derive(Debug, Clone, Default)]
+struct AnalyzeOptions {
+    /// 克隆仓库的根目录
+    clone_dir: String,
+    /// 裸镜像根目录
+    mirror_base: Option<String>,
+    /// 中国贡献者判定覆盖列表
+    china_overrides: ChinaOverrides,
+    /// 不确定区间半宽
+    confidence_gap: Option<f64>,
+    /// 时区熵上限
+    max_timezone_entropy: Option<f64>,
+    /// 时区最低提交占比
+    min_timezone_share: Option<f64>,
+    /// 已知的中国组织（小写）
+    china_orgs: Vec<String>,
+    /// 无本地仓库时使用API获取提交时区
+    prefer_api_timezone: bool,
+    /// 允许克隆的最大仓库大小（MB），None表示不检查
+    max_clone_size_mb: Option<u64>,
+    /// 每处理该数量的提交页面把贡献者统计写入暂存表
+    stream_contributors_every: Option<u32>,
+    /// 输出中匿名化登录名
+    anonymize_logins: bool,
+    /// 不获取用户资料
+    no_user_details: bool,
+    /// 获取用户资料遇到临时错误时的重试次数
+    user_detail_retries: u32,
+    /// 模拟判定使用的概率
+    simulate_china: Option<f64>,
+    /// 来自中国的概率阈值
+    china_threshold: Option<f64>,
+    /// 记录仓库的访问与克隆统计（只有按访问量排列分析顺序时需要）
+    fetch_traffic: bool,
+}
+
+// 贡献者分析报告的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ReportFormat {
+    /// JSON，字段与ContributorsReport一致
+    Json,
+    /// GitHub风格的Markdown，便于粘贴到issue或wiki
+    Markdown,
+}
+
+// 排行榜统计的贡献者范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum LeaderboardRegion {
+    /// 判定为来自中国的贡献者
+    China,
+}
+
+// JSON输出选项
+#[derive(Debug, Clone)]
+struct JsonOutput {
+    /// 应用到输出上的JSONPath表达式
+    path: Option<String>,
+    /// 输出紧凑JSON
+    compact: bool,
+}
+
+// 并发分析时工作任务的状态
+#[derive(Debug, Clone)]
+enum WorkerStatus {
+    /// 正在分析的仓库（owner/repo）
+    Analyzing(String),
+    /// 队列已空，任务结束
+    Finished,
+}
+
+impl std::fmt::Display for WorkerStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorkerStatus::Analyzing(repo) => write!(f, "正在分析 {}", repo),
+            WorkerStatus::Finished => write!(f, "已完成"),
+        }
+    }
+}
+
 // 定义错误类型
 type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
+// 生成解析有限浮点数参数的函数，取值必须在`range`内，`name`用于错误信息
+fn parse_f64_in<R>(
+    range: R,
+    name: &'static str,
+) -> impl Fn(&str) -> Result<f64, String> + Clone + Send + Sync + 'static
+where
+    R: RangeBounds<f64> + Clone + Send + Sync + 'static,
+{
+    move |value: &str| {
+        let parsed: f64 = value
+            .parse()
+            .map_err(|_| format!("无效的数值: {}", value))?;
+        if !parsed.is_finite() || !range.contains(&parsed) {
+            // 以区间记号描述取值范围，如[0, 1]、[0, +∞)
+            let start = match range.start_bound() {
+                Bound::Included(v) => format!("[{}", v),
+                Bound::Excluded(v) => format!("({}", v),
+                Bound::Unbounded => "(-∞".to_string(),
+            };
+            let end = match range.end_bound() {
+                Bound::Included(v) => format!("{}]", v),
+                Bound::Excluded(v) => format!("{})", v),
+                Bound::Unbounded => "+∞)".to_string(),
+            };
+            return Err(format!(
+                "{}必须在{}, {}范围内: {}",
+                name, start, end, parsed
+            ));
+        }
+        Ok(parsed)
+    }
+}
+
+// 解析时长参数，支持 s/m/h/d 后缀，不带后缀时按秒计算
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => value.split_at(pos),
+        None => (value, "s"),
+    };
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("无效的时长: {}", value))?;
+
+    let unit_seconds: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("无效的时长单位: {}（支持 s/m/h/d）", unit)),
+    };
+    let seconds = number
+        .checked_mul(unit_seconds)
+        .ok_or_else(|| format!("时长过长: {}", value))?;
+
+    Ok(Duration::from_secs(seconds))
+}
+
 // 初始化日志
-fn init_logger() {
+fn init_logger(dump_sql: bool, show_sql: bool) {
     use tracing_subscriber::fmt::format::FmtSpan;
     use tracing_subscriber::EnvFilter;
 
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let mut filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    // 输出sqlx执行的语句和手写查询的SQL及参数
+    if dump_sql {
+        for directive in [
+            "sqlx::query=debug",
+            "github_handler::services::database=debug",
+        ] {
+            filter = filter.add_directive(directive.parse().expect("日志过滤指令有效"));
+        }
+    }
+    if show_sql {
+        filter = filter.add_directive("sqlx::query=info".parse().expect("日志过滤指令有效"));
+    }
 
+    // 日志输出到标准错误，标准输出只留给JSON等结果数据
     tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
         .with_env_filter(filter)
         .with_span_events(FmtSpan::CLOSE)
         .init();
 }
 
-// 分析Git贡献者
+// 分析Git贡献者，返回数据写入的仓库ID，仓库未注册时返回None
 async fn analyze_git_contributors(
     db_service: &DbService,
     owner: &str,
     repo: &str,
-) -> Result<(), BoxError> {
+    options: &AnalyzeOptions,
+) -> Result<Option<String>, BoxError> {
     info!("分析仓库贡献者: {}/{}", owner, repo);
 
     // 获取仓库ID
     let repository_id = match db_service.get_repository_id(owner, repo).await? {
-        Some(id) => id,
+        Some(found) => {
+            if found.has_multiple_matches() {
+                warn!(
+                    "贡献者数据将写入仓库 ID={}，另有重复的仓库记录 {:?}，请清理programs表",
+                    found.id, found.other_candidates
+                );
+            }
+            found.id
+        }
         None => {
             warn!("仓库 {}/{} 未在数据库中注册", owner, repo);
-            return Ok(());
+            return Ok(None);
         }
     };
 
     // 创建GitHub API客户端
     let github_client = GitHubApiClient::new();
 
-    // 获取仓库贡献者
-    let contributors = github_client
-        .get_all_repository_contributors(owner, repo)
-        .await?;
+    // 获取仓库贡献者，流式模式下部分统计分批写入暂存表，扫描结束后合并
+    let contributors = match options.stream_contributors_every {
+        Some(pages) => {
+            let scan_id = uuid::Uuid::new_v4().to_string();
+            info!(
+                "流式统计贡献者: 每 {} 页写入一次暂存表 (扫描ID: {})",
+                pages, scan_id
+            );
+            let remaining = github_client
+                .scan_repository_contributors(owner, repo, None, Some(pages), |partial| {
+                    let scan_id = &scan_id;
+                    async move {
+                        db_service
+                            .stage_contributors(scan_id, partial)
+                            .await
+                            .map_err(BoxError::from)
+                    }
+                })
+                .await?;
+            db_service
+                .take_staged_contributors(&scan_id, remaining)
+                .await?
+        }
+        None => {
+            github_client
+                .get_all_repository_contributors(owner, repo)
+                .await?
+        }
+    };
 
     info!("获取到 {} 个贡献者，开始存储到数据库", contributors.len());
 
@@ -115,11 +754,20 @@ async fn analyze_git_contributors(
 
     // 存储贡献者信息
     for contributor in &contributors {
-        // 获取并存储用户详细信息
-        let mut user = match github_client.get_user_details(&contributor.login).await {
+        // 达到单仓库请求数上限时停止，已获取的用户资料有缓存，推迟到之后继续时不会重复请求
+        if !options.no_user_details && request_budget_exhausted() {
+            return Err(format!(
+                "获取仓库 {}/{} 的贡献者资料时达到单仓库请求数上限",
+                owner, repo
+            )
+            .into());
+        }
+
+        // 获取并存储用户详细信息，快速模式下只使用提交扫描得到的信息
+        let mut user = match fetch_user_details(&github_client, contributor, options).await {
             Ok(user) => user,
             Err(e) => {
-                warn!("获取用户 {} 详情失败: {}", contributor.login, e);
+                warn!("获取用户 {} 详情失败，本次跳过: {}", contributor.login, e);
                 continue;
             }
         };
@@ -159,12 +807,22 @@ async fn analyze_git_contributors(
         }
 
         // 等待一小段时间，避免触发GitHub API限制
-        tokio::time::sleep(Duration::from_millis(100)).await;
+        if !options.no_user_details {
+            tokio::time::sleep(request_delay()).await;
+        }
     }
 
-    // 查询并显示贡献者统计
-    match db_service.query_top_contributors(&repository_id).await {
-        Ok(top_contributors) => {
+    // 查询并显示贡献者统计，从主库读取刚写入的结果
+    match db_service
+        .without_read_replica()
+        .query_top_contributors(&repository_id, &ContributorFilter::default())
+        .await
+    {
+        Ok(mut top_contributors) => {
+            if options.anonymize_logins {
+                top_contributors.iter_mut().for_each(|c| c.anonymize());
+            }
+
             info!("仓库 {}/{} 的贡献者统计:", owner, repo);
             for (i, contributor) in top_contributors.iter().enumerate().take(10) {
                 info!(
@@ -180,6 +838,29 @@ async fn analyze_git_contributors(
         }
     }
 
+    // 从README中查找仓库主要团队所在位置的线索，获取失败时忽略
+    let readme_hints = match github_client.get_repository_readme(owner, repo).await {
+        Ok(readme) => {
+            let hints = readme
+                .as_deref()
+                .map(contributor_analysis::extract_location_keywords)
+                .unwrap_or_default();
+            if !hints.is_empty() {
+                info!(
+                    "仓库 {}/{} 的README中提到: {}",
+                    owner,
+                    repo,
+                    hints.join(", ")
+                );
+            }
+            hints
+        }
+        Err(e) => {
+            warn!("获取仓库 {}/{} 的README失败: {}", owner, repo, e);
+            Vec::new()
+        }
+    };
+
     // 分析贡献者国别 - 传递已获取的用户信息
     analyze_contributor_locations(
         db_service,
@@ -189,13 +870,203 @@ async fn analyze_git_contributors(
         &contributors,
         &github_users,
         &email_to_user_id,
+        &readme_hints,
+        options,
     )
     .await?;
 
-    Ok(())
+    // 记录仓库的访问与克隆统计，供analyze-all --traffic-weight安排重新分析的优先级；
+    // 该接口需要push权限，不需要时不请求，避免额外的请求和权限不足的告警
+    if options.fetch_traffic {
+        match github_client.get_repository_traffic(owner, repo).await {
+            Ok(traffic) => {
+                if let Err(e) = db_service
+                    .store_repository_traffic(&repository_id, &traffic)
+                    .await
+                {
+                    warn!("存储仓库 {}/{} 的访问统计失败: {}", owner, repo, e);
+                }
+            }
+            Err(e) => warn!("获取仓库 {}/{} 的访问统计失败: {}", owner, repo, e),
+        }
+    }
+
+    // 记录仓库的主题标签，供按主题筛选仓库
+    match github_client.get_repository_topics(owner, repo).await {
+        Ok(topics) => {
+            if let Err(e) = db_service
+                .store_repository_topics(&repository_id, &topics)
+                .await
+            {
+                warn!("存储仓库 {}/{} 的主题失败: {}", owner, repo, e);
+            }
+        }
+        Err(e) => warn!("获取仓库 {}/{} 的主题失败: {}", owner, repo, e),
+    }
+
+    // 记录仓库的分析时间
+    db_service
+        .mark_repository_analyzed(&repository_id, &readme_hints)
+        .await?;
+
+    Ok(Some(repository_id))
+}
+
+// 获取贡献者的用户资料：快速模式或用户已不存在（404）时只用提交扫描得到的信息；
+// 临时错误按--user-detail-retries重试，仍然失败时返回错误，避免用不完整的资料永久替代用户信息
+async fn fetch_user_details(
+    github_client: &GitHubApiClient,
+    contributor: &services::github_api::Contributor,
+    options: &AnalyzeOptions,
+) -> Result<GitHubUser, reqwest::Error> {
+    if options.no_user_details {
+        return Ok(GitHubUser::from_contributor(contributor));
+    }
+
+    match github_client
+        .get_user_details_with_retries(&contributor.login, options.user_detail_retries)
+        .await
+    {
+        Err(e) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+            warn!(
+                "用户 {} 不存在，只使用提交扫描得到的信息",
+                contributor.login
+            );
+            Ok(GitHubUser::from_contributor(contributor))
+        }
+        result => result,
+    }
+}
+
+// 获取用于时区分析的贡献者邮箱，依次使用用户资料、提交记录中的邮箱，最后用登录名代替
+fn contributor_email(
+    user: &services::github_api::GitHubUser,
+    contributors: &[services::github_api::Contributor],
+) -> String {
+    if let Some(email) = &user.email {
+        return email.clone();
+    }
+
+    // 查找对应的contributor是否有邮箱
+    let contributor_email = contributors
+        .iter()
+        .find(|c| c.login == user.login)
+        .and_then(|c| c.email.clone());
+
+    match contributor_email {
+        Some(email) => email,
+        None => {
+            warn!("用户 {} 没有邮箱信息，使用登录名作为替代", user.login);
+            format!("{}@github.com", user.login)
+        }
+    }
+}
+
+// 根据用户公开所属的组织补充判定，每个用户需要额外一次API请求，只在配置了中国组织时执行
+async fn apply_org_hints(
+    github_client: &GitHubApiClient,
+    analysis: &mut contributor_analysis::ContributorAnalysis,
+    login: &str,
+    options: &AnalyzeOptions,
+) {
+    if options.china_orgs.is_empty() {
+        return;
+    }
+
+    let orgs = match github_client.get_user_orgs(login).await {
+        Ok(orgs) => orgs,
+        Err(e) => {
+            warn!("获取用户 {} 的组织失败: {}", login, e);
+            return;
+        }
+    };
+
+    let china_orgs: Vec<String> = orgs
+        .into_iter()
+        .filter(|org| options.china_orgs.contains(&org.to_lowercase()))
+        .collect();
+    if !china_orgs.is_empty() {
+        info!(
+            "贡献者 {} 是中国组织的成员: {}",
+            login,
+            china_orgs.join(", ")
+        );
+    }
+    analysis.apply_org_membership(china_orgs);
+}
+
+// 对时区分析结果应用不确定区间、地区消歧和覆盖列表，location为用户资料中的位置，
+// readme_hints为仓库README中发现的位置线索
+fn apply_analysis_options(
+    analysis: &mut contributor_analysis::ContributorAnalysis,
+    login: Option<&str>,
+    location: Option<&str>,
+    readme_hints: &[String],
+    options: &AnalyzeOptions,
+) {
+    // 日志中的贡献者名称，没有登录名时（如inspect）使用邮箱
+    let name = login
+        .map(str::to_string)
+        .or_else(|| analysis.email.clone())
+        .unwrap_or_default();
+
+    // 丢弃零星出现的时区
+    if let Some(min_share) = options.min_timezone_share {
+        analysis.apply_min_timezone_share(min_share);
+    }
+
+    // 按较低权重混入README中的位置线索
+    analysis.apply_readme_location_hint(readme_hints);
+
+    // 使用校准过的概率阈值（未配置时使用默认阈值）重新判定
+    analysis.apply_china_threshold(
+        options
+            .china_threshold
+            .unwrap_or(contributor_analysis::DEFAULT_CHINA_THRESHOLD),
+    );
+
+    // 标记处于不确定区间的判定
+    if let Some(gap) = options.confidence_gap {
+        analysis.mark_uncertain(gap);
+        if analysis.uncertain {
+            info!(
+                "贡献者 {} 的判定处于不确定区间 (概率: {:.2})",
+                name, analysis.china_probability
+            );
+        }
+    }
+
+    // 提交时区过于分散的判定同样视为不确定
+    if let Some(max_entropy) = options.max_timezone_entropy {
+        analysis.mark_high_entropy(max_entropy);
+        if analysis.timezone_entropy > max_entropy {
+            info!(
+                "贡献者 {} 的提交时区过于分散 (时区熵: {:.2})",
+                name, analysis.timezone_entropy
+            );
+        }
+    }
+
+    // 常用时区对应多个国家/地区时，用资料位置或邮箱域名消歧
+    let email = analysis.email.clone();
+    analysis.disambiguate_region(location, email.as_deref());
+    if let Some(region) = &analysis.region {
+        info!("贡献者 {} 的地区消歧为 {}", name, region);
+    }
+
+    // 覆盖列表优先于启发式判定
+    if options.china_overrides.apply(login, analysis) {
+        info!("贡献者 {} 的判定来自覆盖列表", name);
+    }
+
+    // 模拟判定替换启发式结果，覆盖列表中的判定保持不变
+    if let Some(probability) = options.simulate_china {
+        analysis.simulate_china(probability);
+    }
 }
 
 // 分析贡献者国别位置
+#[allow(clippy::too_many_arguments)]
 async fn analyze_contributor_locations(
     db_service: &DbService,
     owner: &str,
@@ -204,111 +1075,121 @@ async fn analyze_contributor_locations(
     contributors: &[services::github_api::Contributor],
     github_users: &[services::github_api::GitHubUser],
     email_to_user_id: &HashMap<String, i32>,
+    readme_hints: &[String],
+    options: &AnalyzeOptions,
 ) -> Result<(), BoxError> {
     info!("分析仓库 {}/{} 的贡献者地理位置", owner, repo);
 
-    // 使用/mnt/crates/github_source作为基础目录
-    let base_dir = Path::new("/mnt/crates/github_source");
-    if !base_dir.exists() {
-        fs::create_dir_all(base_dir)?;
-        info!("创建根目录: {:?}", base_dir);
-    }
-
-    // 构建目标路径: /mnt/crates/github_source/{owner}/{repo}
-    let target_dir = base_dir.join(format!("{}/{}", owner, repo));
-    let target_path = target_dir.to_string_lossy();
-
-    // 检查目录是否已存在
-    if !target_dir.exists() {
-        // 确保父目录存在
-        if let Some(parent) = target_dir.parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent)?;
-            }
+    // 准备本地仓库（优先使用裸镜像，否则克隆或更新工作副本）
+    let target_path = match prepare_local_repository(owner, repo, options).await? {
+        LocalRepository::Ready(path) => Some(path),
+        LocalRepository::TooLarge => {
+            info!("仓库过大未克隆，改用GitHub API获取贡献者的提交时区");
+            None
+        }
+        LocalRepository::Unavailable if options.prefer_api_timezone => {
+            info!("本地仓库不可用，改用GitHub API获取贡献者的提交时区");
+            None
         }
+        LocalRepository::Unavailable => return Ok(()),
+    };
 
-        info!("克隆仓库到指定目录: {}", target_path);
-        let status = Command::new("git")
-            .args(&[
-                "clone",
-                &format!("https://github.com/{}/{}.git", owner, repo),
-                &target_path,
-            ])
-            .status();
+    info!("开始分析 {} 个贡献者的时区信息", github_users.len());
 
-        match status {
-            Ok(status) if !status.success() => {
-                warn!("克隆仓库失败: {}", status);
-                return Ok(());
-            }
-            Err(e) => {
-                warn!("执行git命令失败: {}", e);
-                return Ok(());
+    // 单次遍历提交历史，预先计算所有作者的时区分析结果（按小写邮箱索引）
+    let precomputed: HashMap<String, contributor_analysis::ContributorAnalysis> = match &target_path
+    {
+        Some(target_path) => {
+            match contributor_analysis::analyze_all_contributors_timezone(target_path).await {
+                Some(analyses) => analyses
+                    .into_iter()
+                    .filter_map(|a| a.email.clone().map(|e| (e.to_lowercase(), a)))
+                    .collect(),
+                None => {
+                    warn!("单次遍历提交历史失败，将逐个分析贡献者");
+                    HashMap::new()
+                }
             }
-            _ => {}
-        }
-    } else {
-        info!("更新已存在的仓库: {}", target_path);
-        let status = Command::new("git")
-            .current_dir(&target_dir)
-            .args(&["pull"])
-            .status();
-
-        if let Err(e) = status {
-            warn!("更新仓库失败: {}", e);
         }
-    }
+        None => HashMap::new(),
+    };
 
-    info!("开始分析 {} 个贡献者的时区信息", github_users.len());
+    // 使用GitHub隐私邮箱提交的作者按解析出的登录名（小写）索引，用于匹配没有公开邮箱的用户
+    let precomputed_by_login: HashMap<String, &contributor_analysis::ContributorAnalysis> =
+        precomputed
+            .iter()
+            .filter_map(|(email, analysis)| {
+                parse_noreply_login(email).map(|login| (login.to_lowercase(), analysis))
+            })
+            .collect();
 
+    let github_client = GitHubApiClient::new();
     let mut china_contributors = 0;
     let mut non_china_contributors = 0;
 
     // 对每个贡献者进行时区分析
-    for (i, user) in github_users.iter().enumerate() {
+    for user in github_users {
         // 使用贡献者的邮箱进行时区分析
-        let email = match &user.email {
-            Some(email) => email.clone(),
-            None => {
-                // 查找对应的contributor是否有邮箱
-                let contributor_email = contributors
-                    .iter()
-                    .find(|c| c.login == user.login)
-                    .and_then(|c| c.email.clone());
+        let email = contributor_email(user, contributors);
 
-                match contributor_email {
-                    Some(email) => email,
+        // 分析该贡献者的时区情况
+        // 优先使用预先计算的结果，找不到时回退到按作者执行git log；没有本地仓库时使用API
+        let precomputed_result = precomputed.get(&email.to_lowercase()).or_else(|| {
+            precomputed_by_login
+                .get(&user.login.to_lowercase())
+                .copied()
+        });
+        let mut analysis = match (&target_path, precomputed_result) {
+            (_, Some(result)) => result.clone(),
+            (Some(target_path), None) => {
+                match contributor_analysis::analyze_contributor_timezone(target_path, &email).await
+                {
+                    Some(result) => result,
                     None => {
-                        warn!("用户 {} 没有邮箱信息，使用登录名作为替代", user.login);
-                        format!("{}@github.com", user.login)
+                        warn!("无法分析用户 {} 的时区信息", user.login);
+                        continue;
                     }
                 }
             }
+            (None, None) => match github_client
+                .get_commit_author_timezone(owner, repo, &user.login, API_TIMEZONE_MAX_COMMITS)
+                .await
+            {
+                Ok(timezone_stats) => {
+                    contributor_analysis::build_analysis_from_timezone_stats(&email, timezone_stats)
+                }
+                Err(e) => {
+                    warn!("通过API获取用户 {} 的提交时区失败: {}", user.login, e);
+                    continue;
+                }
+            },
         };
 
-        // 分析该贡献者的时区情况
-        let analysis = match contributor_analysis::analyze_contributor_timezone(
-            &target_path.to_string(),
-            &email,
-        )
-        .await
-        {
-            Some(result) => result,
-            None => {
-                warn!("无法分析用户 {} 的时区信息", user.login);
-                continue;
-            }
-        };
+        apply_org_hints(&github_client, &mut analysis, &user.login, options).await;
+        apply_analysis_options(
+            &mut analysis,
+            Some(&user.login),
+            user.location.as_deref(),
+            readme_hints,
+            options,
+        );
 
         // 查找用户ID
+        // 优先使用GitHub数字ID查找（登录名可能会变更），最后才按登录名查找
         let user_id = match email_to_user_id.get(&email) {
             Some(id) => *id,
-            None => match db_service.get_user_id_by_name(&user.login).await {
-                Ok(Some(id)) => id,
-                _ => {
-                    warn!("未找到用户 {} 的ID", user.login);
-                    continue;
-                }
+            None => match db_service
+                .get_contributor_detail_by_github_id(user.id)
+                .await
+            {
+                Ok(Some(detail)) => detail.user_id,
+                _ => match db_service.get_user_id_by_name(&user.login).await {
+                    Ok(Some(id)) => id,
+                    _ => {
+                        warn!("未找到用户 {} 的ID", user.login);
+                        continue;
+                    }
+                },
             },
         };
 
@@ -352,8 +1233,9 @@ async fn analyze_contributor_locations(
         100.0 - china_percentage
     );
 
-    // 查询中国贡献者统计
+    // 查询中国贡献者统计，从主库读取刚写入的结果
     match db_service
+        .without_read_replica()
         .get_repository_china_contributor_stats(repository_id)
         .await
     {
@@ -392,142 +1274,2031 @@ async fn analyze_contributor_locations(
     Ok(())
 }
 
-// 查询仓库的顶级贡献者
-async fn query_top_contributors(
+// 监视仓库的新提交，持续更新新贡献者的信息，收到Ctrl-C时退出
+async fn watch_repository(
     db_service: &DbService,
     owner: &str,
     repo: &str,
+    repository_id: &str,
+    since: chrono::DateTime<chrono::Utc>,
+    poll_interval: Duration,
+    options: &AnalyzeOptions,
 ) -> Result<(), BoxError> {
-    info!("查询仓库 {}/{} 的顶级贡献者", owner, repo);
+    let github_client = GitHubApiClient::new();
+    // 从首次分析开始前计算，分析期间推送的提交也会在第一轮被统计
+    let mut since = since.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    // 使用上次完整分析时从README中发现的位置线索
+    let readme_hints = db_service
+        .get_readme_location_hints()
+        .await?
+        .remove(repository_id)
+        .unwrap_or_default();
 
-    // 获取仓库ID
-    let repository_id = match db_service.get_repository_id(owner, repo).await? {
-        Some(id) => id,
+    info!(
+        "进入监视模式: {}/{}，每 {} 秒检查一次新提交（Ctrl-C 退出）",
+        owner,
+        repo,
+        poll_interval.as_secs()
+    );
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                info!("收到中断信号，退出监视模式");
+                return Ok(());
+            }
+            _ = tokio::time::sleep(poll_interval) => {}
+        }
+
+        let poll_started = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        let contributors = match github_client
+            .get_repository_contributors_since(owner, repo, Some(&since))
+            .await
+        {
+            Ok(contributors) => contributors,
+            Err(e) => {
+                warn!("获取新提交失败: {}", e);
+                continue;
+            }
+        };
+
+        let new_commits: i32 = contributors.iter().map(|c| c.contributions).sum();
+        let mut updated = 0;
+
+        if !contributors.is_empty() {
+            // 本地仓库暂不可用时不推进since，下一轮重新获取这些提交
+            let LocalRepository::Ready(target_path) =
+                prepare_local_repository(owner, repo, options).await?
+            else {
+                warn!("本地仓库不可用，{} 个新提交将在下一轮重新处理", new_commits);
+                continue;
+            };
+
+            for contributor in &contributors {
+                match watch_update_contributor(
+                    db_service,
+                    &github_client,
+                    repository_id,
+                    &target_path,
+                    contributor,
+                    &contributors,
+                    &readme_hints,
+                    options,
+                )
+                .await
+                {
+                    Ok(true) => updated += 1,
+                    Ok(false) => {}
+                    Err(e) => error!("更新贡献者 {} 失败: {}", contributor.login, e),
+                }
+            }
+        }
+
+        // 本轮的提交处理完后才推进since
+        since = poll_started;
+        info!("{} 个新提交, {} 个贡献者已更新", new_commits, updated);
+    }
+}
+
+// 监视模式下处理一个有新提交的贡献者，返回是否更新了数据库
+#[allow(clippy::too_many_arguments)]
+async fn watch_update_contributor(
+    db_service: &DbService,
+    github_client: &GitHubApiClient,
+    repository_id: &str,
+    target_path: &str,
+    contributor: &services::github_api::Contributor,
+    contributors: &[services::github_api::Contributor],
+    readme_hints: &[String],
+    options: &AnalyzeOptions,
+) -> Result<bool, BoxError> {
+    let existing = db_service
+        .get_contributor_detail_by_github_id(contributor.id)
+        .await?;
+
+    // 数据库中没有的用户需要先获取并存储用户详情；其他仓库已存储的用户直接复用
+    let (user_id, email, location) = match existing {
+        Some(detail) => {
+            let email = contributor
+                .email
+                .clone()
+                .unwrap_or_else(|| format!("{}@github.com", detail.login));
+            (detail.user_id, email, detail.location)
+        }
         None => {
-            warn!("仓库 {}/{} 未在数据库中注册", owner, repo);
-            return Ok(());
+            let mut user = fetch_user_details(github_client, contributor, options).await?;
+            if user.email.is_none() {
+                user.email = contributor.email.clone();
+            }
+            let user_id = db_service.store_user(&user).await?;
+            (
+                user_id,
+                contributor_email(&user, contributors),
+                user.location,
+            )
         }
     };
 
-    // 查询贡献者统计
-    match db_service.query_top_contributors(&repository_id).await {
-        Ok(top_contributors) => {
-            info!("仓库 {}/{} 的贡献者统计:", owner, repo);
-            for (i, contributor) in top_contributors.iter().enumerate().take(10) {
-                let location_str = contributor
-                    .location
-                    .as_ref()
-                    .map(|loc| format!(" ({})", loc))
-                    .unwrap_or_default();
+    // 按仓库判断是否为新贡献者；贡献数为已存储的数量加上本轮的新提交数
+    let previous_contributions = db_service
+        .get_repository_contributions(repository_id, user_id)
+        .await?;
+    let is_new = previous_contributions.is_none();
+    db_service
+        .store_contributor(
+            repository_id,
+            user_id,
+            previous_contributions
+                .unwrap_or(0)
+                .saturating_add(contributor.contributions),
+        )
+        .await?;
 
-                let name_display = contributor.name.as_ref().unwrap_or(&contributor.login);
+    let Some(mut analysis) =
+        contributor_analysis::analyze_contributor_timezone(target_path, &email).await
+    else {
+        warn!("无法分析用户 {} 的时区信息", contributor.login);
+        return Ok(is_new);
+    };
+    apply_org_hints(github_client, &mut analysis, &contributor.login, options).await;
+    apply_analysis_options(
+        &mut analysis,
+        Some(&contributor.login),
+        location.as_deref(),
+        readme_hints,
+        options,
+    );
 
-                info!(
-                    "  {}. {}{} - {} 次提交",
-                    i + 1,
-                    name_display,
-                    location_str,
-                    contributor.contributions
-                );
-            }
+    // 已有位置记录且概率变化不超过5%时不更新
+    let previous = db_service
+        .get_contributor_location(repository_id, user_id)
+        .await?;
+    if let Some(previous_probability) = previous.and_then(|p| p.china_probability) {
+        if (analysis.china_probability - previous_probability).abs() <= 0.05 {
+            return Ok(is_new);
         }
-        Err(e) => {
-            error!("查询贡献者统计失败: {}", e);
+    }
+
+    db_service
+        .store_contributor_location(repository_id, user_id, &analysis)
+        .await?;
+    info!(
+        "贡献者 {} 的位置分析已更新 (概率: {:.2})",
+        contributor.login, analysis.china_probability
+    );
+
+    Ok(true)
+}
+
+// 准备本地仓库的结果
+enum LocalRepository {
+    /// 本地仓库路径
+    Ready(String),
+    /// 仓库超过允许克隆的大小，未克隆
+    TooLarge,
+    /// 无法获取仓库
+    Unavailable,
+}
+
+// 准备用于分析的本地仓库，返回仓库路径；需要新克隆时先检查仓库大小
+async fn prepare_local_repository(
+    owner: &str,
+    repo: &str,
+    options: &AnalyzeOptions,
+) -> Result<LocalRepository, BoxError> {
+    // 如果配置了镜像目录且存在对应的裸镜像，直接使用镜像，避免检出工作区
+    if let Some(mirror_base) = &options.mirror_base {
+        let mirror_dir = Path::new(mirror_base).join(format!("{}/{}.git", owner, repo));
+        if mirror_dir.exists() {
+            let mirror_path = mirror_dir.to_string_lossy().to_string();
+            info!("使用已有的裸镜像: {}", mirror_path);
+
+            let status = Command::new("git")
+                .arg(format!("--git-dir={}", mirror_path))
+                .args(["fetch", "--prune"])
+                .status()
+                .await;
+
+            match status {
+                Ok(status) if !status.success() => warn!("更新裸镜像失败: {}", status),
+                Err(e) => warn!("更新裸镜像失败: {}", e),
+                _ => {}
+            }
+
+            return Ok(LocalRepository::Ready(mirror_path));
         }
+
+        info!("未找到裸镜像 {:?}，回退到克隆仓库", mirror_dir);
     }
 
-    // 查询中国贡献者统计
-    match db_service
-        .get_repository_china_contributor_stats(&repository_id)
-        .await
-    {
-        Ok(stats) => {
-            info!(
-                "仓库 {}/{} 的中国贡献者统计: {}人中有{}人来自中国 ({:.1}%)",
-                owner,
-                repo,
-                stats.total_contributors,
-                stats.china_contributors,
-                stats.china_percentage
-            );
+    // 使用配置的克隆目录作为基础目录
+    let base_dir = Path::new(&options.clone_dir);
+    if !base_dir.exists() {
+        fs::create_dir_all(base_dir)?;
+        info!("创建根目录: {:?}", base_dir);
+    }
+
+    // 构建目标路径: {clone_dir}/{owner}/{repo}
+    let target_dir = base_dir.join(format!("{}/{}", owner, repo));
+    let target_path = target_dir.to_string_lossy();
+
+    // 检查目录是否已存在
+    if !target_dir.exists() {
+        // 克隆前检查仓库大小（API返回的size单位为KB），获取失败时仍然克隆
+        if let Some(max_size_mb) = options.max_clone_size_mb {
+            match GitHubApiClient::new().get_repo_metadata(owner, repo).await {
+                Ok(metadata) if metadata.size / 1024 > max_size_mb => {
+                    warn!(
+                        "仓库 {}/{} 大小约 {} MB，超过 --max-clone-size-mb {} MB，跳过克隆（加上 --force-large-clone 强制克隆）",
+                        owner,
+                        repo,
+                        metadata.size / 1024,
+                        max_size_mb
+                    );
+                    return Ok(LocalRepository::TooLarge);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("获取仓库 {}/{} 的大小失败，继续克隆: {}", owner, repo, e),
+            }
         }
-        Err(e) => {
-            error!("获取中国贡献者统计失败: {}", e);
+
+        // 确保父目录存在
+        if let Some(parent) = target_dir.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        info!("克隆仓库到指定目录: {}", target_path);
+        let status = Command::new("git")
+            .args([
+                "clone",
+                &format!("https://github.com/{}/{}.git", owner, repo),
+                &target_path,
+            ])
+            .status()
+            .await;
+
+        match status {
+            Ok(status) if !status.success() => {
+                warn!("克隆仓库失败: {}", status);
+                return Ok(LocalRepository::Unavailable);
+            }
+            Err(e) => {
+                warn!("执行git命令失败: {}", e);
+                return Ok(LocalRepository::Unavailable);
+            }
+            _ => {}
+        }
+    } else {
+        info!("更新已存在的仓库: {}", target_path);
+        let status = Command::new("git")
+            .current_dir(&target_dir)
+            .args(["pull"])
+            .status()
+            .await;
+
+        if let Err(e) = status {
+            warn!("更新仓库失败: {}", e);
         }
     }
 
-    Ok(())
+    Ok(LocalRepository::Ready(target_path.to_string()))
 }
 
-#[tokio::main]
-async fn main() -> Result<(), BoxError> {
-    // 加载.env文件
-    dotenv().ok();
-
-    // 初始化日志
-    init_logger();
+// 校准阈值扫描的范围（0.1到0.9，步长0.1）
+const CALIBRATION_THRESHOLDS: std::ops::RangeInclusive<u32> = 1..=9;
 
-    // 解析命令行参数
-    let cli = Cli::parse();
+// 标注文件中的一行
+#[derive(Debug, serde::Deserialize)]
+struct LabeledContributor {
+    github_login: String,
+    true_is_china: bool,
+}
 
-    // 处理贡献者分析请求
-    if let Some(repo_path) = cli.analyze_contributors {
-        let report = generate_contributors_report(&repo_path).await;
-        report.print_summary();
+// 某个阈值下的判定效果
+#[derive(Debug, Clone, Copy)]
+struct CalibrationPoint {
+    threshold: f64,
+    precision: f64,
+    recall: f64,
+    f1: f64,
+}
 
-        // 如果提供了第二个位置参数，将结果保存为JSON
-        if let Some(output_path) = cli.repo {
-            let json = report.to_json()?;
-            std::fs::write(&output_path, json)?;
-            info!("分析结果已保存到: {}", output_path);
+/// 用人工标注的结果评估不同概率阈值下的精确率、召回率和F1，输出F1最高的阈值
+async fn calibrate(
+    db_service: &DbService,
+    csv_path: &str,
+    save_threshold: bool,
+) -> Result<(), BoxError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(csv_path)?;
+    let mut labels = HashMap::new();
+    for (i, record) in reader.deserialize::<LabeledContributor>().enumerate() {
+        match record {
+            Ok(row) => {
+                labels.insert(row.github_login.to_lowercase(), row.true_is_china);
+            }
+            // 第1行为表头
+            Err(e) => warn!("跳过第 {} 行: {}", i + 2, e),
         }
+    }
+    if labels.is_empty() {
+        return Err(format!("标注文件 {} 中没有有效记录", csv_path).into());
+    }
 
-        return Ok(());
+    // 同一贡献者出现在多个仓库时，按各仓库的提交数加权平均来自中国的概率
+    let mut weighted: HashMap<String, (f64, f64)> = HashMap::new();
+    for (location, user) in db_service.all_contributor_locations().await? {
+        let (Some(user), Some(probability)) = (user, location.china_probability) else {
+            continue;
+        };
+        let login = user.login.to_lowercase();
+        if !labels.contains_key(&login) {
+            continue;
+        }
+        let commits = location
+            .timezone_stats
+            .and_then(|value| serde_json::from_value::<HashMap<String, usize>>(value).ok())
+            .map(|stats| stats.values().sum::<usize>())
+            .filter(|&total| total > 0)
+            .unwrap_or(1) as f64;
+        let entry = weighted.entry(login).or_default();
+        entry.0 += probability * commits;
+        entry.1 += commits;
     }
 
-    // 连接数据库
-    info!("连接数据库...");
-    let db_url = get_database_url();
-    let conn = Database::connect(&db_url).await?;
+    let mut samples = Vec::new();
+    let mut missing = Vec::new();
+    for (login, &is_china) in &labels {
+        match weighted.get(login) {
+            Some(&(sum, weight)) => samples.push((sum / weight, is_china)),
+            None => missing.push(login.as_str()),
+        }
+    }
+    if !missing.is_empty() {
+        missing.sort_unstable();
+        warn!(
+            "{} 个标注的贡献者没有分析结果，已忽略: {}",
+            missing.len(),
+            missing.join(", ")
+        );
+    }
+    if samples.is_empty() {
+        return Err("没有可用于校准的贡献者".into());
+    }
 
-    // 设置数据库表结构
-    match setup_database(&conn).await {
-        Ok(_) => info!("数据库表结构设置完成"),
-        Err(e) => {
-            // 如果是约束已存在的错误，则可以继续执行
-            if e.to_string().contains("already exists") {
-                warn!("数据库表结构已存在，跳过创建: {}", e);
+    info!("使用 {} 个标注的贡献者校准阈值:", samples.len());
+    let mut best: Option<CalibrationPoint> = None;
+    for step in CALIBRATION_THRESHOLDS {
+        let threshold = step as f64 / 10.0;
+        let (mut tp, mut fp, mut fn_) = (0usize, 0usize, 0usize);
+        for &(probability, is_china) in &samples {
+            match (probability >= threshold, is_china) {
+                (true, true) => tp += 1,
+                (true, false) => fp += 1,
+                (false, true) => fn_ += 1,
+                (false, false) => {}
+            }
+        }
+        let ratio = |num: usize, den: usize| {
+            if den == 0 {
+                0.0
             } else {
-                // 对于其他错误，记录并返回
-                error!("设置数据库表结构失败: {}", e);
-                return Err(format!("数据库设置失败: {}", e).into());
+                num as f64 / den as f64
             }
+        };
+        let precision = ratio(tp, tp + fp);
+        let recall = ratio(tp, tp + fn_);
+        let f1 = if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        };
+        info!(
+            "  阈值 {:.1}: 精确率 {:.3}, 召回率 {:.3}, F1 {:.3}",
+            threshold, precision, recall, f1
+        );
+
+        // F1相同时保留较低的阈值
+        if best.is_none_or(|b| f1 > b.f1) {
+            best = Some(CalibrationPoint {
+                threshold,
+                precision,
+                recall,
+                f1,
+            });
         }
     }
 
-    // 创建数据库服务
-    let db_service = DbService::new(conn);
+    let best = best.expect("阈值范围不为空");
+    info!(
+        "F1最高的阈值为 {:.1} (精确率 {:.3}, 召回率 {:.3}, F1 {:.3})",
+        best.threshold, best.precision, best.recall, best.f1
+    );
 
-    // 处理子命令
-    match cli.command {
-        Some(Commands::Analyze { owner, repo }) => {
-            analyze_git_contributors(&db_service, &owner, &repo).await?;
+    if save_threshold {
+        let path = crate::config::save_china_threshold(best.threshold)?;
+        info!("已将阈值写入配置文件 {}", path);
+    }
+
+    Ok(())
+}
+
+// 基于已存储的时区分布，用当前的配置重新判定所有仓库的贡献者，统计判定发生翻转的数量
+async fn reclassify_all(
+    db_service: &DbService,
+    dry_run: bool,
+    options: &AnalyzeOptions,
+) -> Result<(), BoxError> {
+    let github_client = GitHubApiClient::new();
+    let locations = db_service.all_contributor_locations().await?;
+    let readme_hints = db_service.get_readme_location_hints().await?;
+
+    let mut repositories = HashSet::new();
+    let mut reclassified = 0;
+    let mut skipped = 0;
+    let mut flipped = 0;
+    let mut unchanged_ids = Vec::new();
+    for (location, user) in locations {
+        repositories.insert(location.repository_id.clone());
+
+        // 旧版本写入的记录没有时区分布，无法重新判定
+        let Some(timezone_stats) = location
+            .timezone_stats
+            .clone()
+            .and_then(|value| serde_json::from_value::<HashMap<String, usize>>(value).ok())
+            .filter(|stats| !stats.is_empty())
+        else {
+            skipped += 1;
+            continue;
+        };
+        let (login, email, profile_location) = match user {
+            Some(user) => (user.login, user.email, user.location),
+            None => (location.user_id.to_string(), None, None),
+        };
+
+        let mut analysis = contributor_analysis::build_analysis_from_timezone_stats(
+            email.as_deref().unwrap_or(&login),
+            timezone_stats,
+        );
+        if let Some(hours) = location
+            .commit_hours
+            .clone()
+            .and_then(contributor_analysis::commit_hours_from_json)
+        {
+            analysis.commit_hours = hours;
+        }
+        apply_org_hints(&github_client, &mut analysis, &login, options).await;
+        apply_analysis_options(
+            &mut analysis,
+            Some(&login),
+            profile_location.as_deref(),
+            readme_hints
+                .get(&location.repository_id)
+                .map(Vec::as_slice)
+                .unwrap_or_default(),
+            options,
+        );
+        reclassified += 1;
+
+        if analysis.from_china != location.is_from_china {
+            flipped += 1;
+            info!(
+                "仓库 {} 的贡献者 {} 判定变化: {} -> {}",
+                location.repository_id,
+                login,
+                if location.is_from_china {
+                    "中国"
+                } else {
+                    "非中国"
+                },
+                if analysis.from_china {
+                    "中国"
+                } else {
+                    "非中国"
+                }
+            );
         }
 
-        Some(Commands::Query { owner, repo }) => {
-            query_top_contributors(&db_service, &owner, &repo).await?;
+        let changed = analysis.from_china != location.is_from_china
+            || analysis.uncertain != location.uncertain
+            || location.china_probability != Some(analysis.china_probability)
+            || location.common_timezone.as_deref() != Some(analysis.common_timezone.as_str())
+            || location.region_code.as_deref() != analysis.region_code()
+            || location.signals != serde_json::to_value(analysis.classification_signals()).ok();
+        if changed && !dry_run {
+            db_service
+                .update_location_classification(location.id, &analysis)
+                .await?;
+        } else if !changed {
+            unchanged_ids.push(location.id);
         }
+    }
 
-        None => {
-            // 如果没有提供子命令，但提供了owner和repo参数
-            if let (Some(owner), Some(repo)) = (cli.owner, cli.repo) {
-                analyze_git_contributors(&db_service, &owner, &repo).await?;
-            } else {
-                // 没有足够的参数，显示帮助信息
-                println!("请提供仓库所有者和名称，或使用子命令。运行 --help 获取更多信息。");
+    // 判定结果没有变化的记录也更新判定时间，分析时间保持不变
+    if !dry_run {
+        db_service
+            .touch_location_classified_at(&unchanged_ids)
+            .await?;
+    }
+
+    info!(
+        "{}重新判定完成: 仓库 {} 个, 重新判定 {} 条, 缺少时区分布跳过 {} 条, 判定翻转 {} 条",
+        if dry_run { "[预览] " } else { "" },
+        repositories.len(),
+        reclassified,
+        skipped,
+        flipped
+    );
+    if skipped > 0 {
+        info!("跳过的记录需要重新分析对应仓库后才能参与重新判定");
+    }
+
+    Ok(())
+}
+
+// 分析数据库中所有已注册的仓库，返回是否有仓库超过告警阈值
+#[allow(clippy::too_many_arguments)]
+async fn analyze_all_repositories(
+    db_service: &DbService,
+    skip_recent: Option<Duration>,
+    traffic_weight: Option<f64>,
+    exclude_forks: bool,
+    workers: usize,
+    filter_topic: Option<&str>,
+    fail_fast: bool,
+    per_repo_request_cap: Option<u64>,
+    estimate: bool,
+    alert_thresholds: AlertThresholds,
+    options: &AnalyzeOptions,
+) -> Result<bool, BoxError> {
+    let mut programs = db_service.list_programs_with_github_url().await?;
+    let last_analyzed = if skip_recent.is_some() || traffic_weight.is_some() {
+        db_service.get_last_analyzed_times().await?
+    } else {
+        HashMap::new()
+    };
+
+    let now = chrono::Utc::now().naive_utc();
+
+    // 按优先级排序：过期小时数 × (1 + 权重 × ln(1 + 独立访客数 + 独立克隆数))，从未分析过的仓库最先
+    if let Some(weight) = traffic_weight {
+        let traffic = db_service.get_repository_traffic().await?;
+        let priority = |program: &program::Model| -> f64 {
+            let Some(analyzed_at) = last_analyzed.get(&program.id) else {
+                return f64::INFINITY;
+            };
+            let stale_hours = (now - *analyzed_at).num_minutes().max(0) as f64 / 60.0;
+            let reach = traffic
+                .get(&program.id)
+                .map(|t| (t.unique_visitors + t.unique_cloners) as f64)
+                .unwrap_or_default();
+            stale_hours * (1.0 + weight * reach.ln_1p())
+        };
+        programs.sort_by(|a, b| priority(b).total_cmp(&priority(a)));
+        info!("已按访问量权重 {} 排列仓库分析顺序", weight);
+    }
+
+    let mut skipped = 0;
+    let mut skipped_forks = Vec::new();
+    let mut queue = VecDeque::new();
+    let github_client = GitHubApiClient::new();
+    let mut topics = match filter_topic {
+        Some(_) => db_service.get_repository_topics().await?,
+        None => HashMap::new(),
+    };
+
+    for program in &programs {
+        let github_url = program.github_url.as_deref().unwrap_or_default();
+        let Some((owner, repo)) = parse_github_repo_url(github_url) else {
+            warn!("无法解析仓库 {} 的GitHub地址: {}", program.name, github_url);
+            skipped += 1;
+            continue;
+        };
+
+        // 跳过最近分析过的仓库
+        if let (Some(window), Some(analyzed_at)) = (skip_recent, last_analyzed.get(&program.id)) {
+            if now - *analyzed_at < chrono::Duration::from_std(window)? {
+                info!("仓库 {}/{} 于 {} 分析过，跳过", owner, repo, analyzed_at);
+                skipped += 1;
+                continue;
+            }
+        }
+
+        // 跳过fork仓库，获取仓库信息失败时仍然分析
+        if exclude_forks {
+            match github_client.get_repo_metadata(&owner, &repo).await {
+                Ok(metadata) if metadata.fork => {
+                    info!(
+                        "仓库 {}/{} 是 {} 的fork，跳过",
+                        owner,
+                        repo,
+                        metadata
+                            .parent
+                            .as_ref()
+                            .map(|p| p.full_name.as_str())
+                            .unwrap_or("未知仓库")
+                    );
+                    skipped += 1;
+                    skipped_forks.push(format!("{}/{}", owner, repo));
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) => warn!(
+                    "获取仓库 {}/{} 的信息失败，无法判断是否为fork: {}",
+                    owner, repo, e
+                ),
+            }
+        }
+
+        // 跳过不带指定主题的仓库，尚未记录主题时先通过API获取，获取失败时仍然分析
+        if let Some(topic) = filter_topic {
+            if !topics.contains_key(&program.id) {
+                match github_client.get_repository_topics(&owner, &repo).await {
+                    Ok(fetched) => {
+                        if let Err(e) = db_service
+                            .store_repository_topics(&program.id, &fetched)
+                            .await
+                        {
+                            warn!("存储仓库 {}/{} 的主题失败: {}", owner, repo, e);
+                        }
+                        let fetched = fetched.iter().map(|t| t.to_lowercase()).collect();
+                        topics.insert(program.id.clone(), fetched);
+                    }
+                    Err(e) => warn!(
+                        "获取仓库 {}/{} 的主题失败，无法按主题筛选: {}",
+                        owner, repo, e
+                    ),
+                }
+            }
+            if let Some(repo_topics) = topics.get(&program.id) {
+                if !repo_topics.iter().any(|t| t.eq_ignore_ascii_case(topic)) {
+                    info!("仓库 {}/{} 没有主题 {}，跳过", owner, repo, topic);
+                    skipped += 1;
+                    continue;
+                }
             }
         }
+
+        queue.push_back((owner, repo, 0));
     }
 
-    Ok(())
+    // 估算成本并确认，未确认时不开始分析
+    if estimate {
+        let repos: Vec<(String, String)> = queue
+            .iter()
+            .map(|(owner, repo, _)| (owner.clone(), repo.clone()))
+            .collect();
+        if !confirm_batch_estimate(db_service, &repos, skipped).await? {
+            info!("已取消批量分析");
+            return Ok(false);
+        }
+    }
+
+    let total = queue.len();
+    let workers = workers.max(1);
+    if workers > 1 {
+        info!("使用 {} 个工作任务并发分析 {} 个仓库", workers, total);
+    }
+
+    let queue = Arc::new(Mutex::new(queue));
+    let statuses = Arc::new(Mutex::new(HashMap::new()));
+    let started = Arc::new(AtomicUsize::new(0));
+    let completed = Arc::new(AtomicU64::new(0));
+    let aborted = Arc::new(AtomicBool::new(false));
+
+    let mut handles = JoinSet::new();
+    for worker_id in 0..workers {
+        let queue = Arc::clone(&queue);
+        let statuses = Arc::clone(&statuses);
+        let started = Arc::clone(&started);
+        let completed = Arc::clone(&completed);
+        let aborted = Arc::clone(&aborted);
+        let db_service = db_service.clone();
+        let options = options.clone();
+
+        handles.spawn(async move {
+            let mut succeeded = 0;
+            let mut failed = Vec::new();
+            let mut alerted = Vec::new();
+
+            loop {
+                // 快速失败模式下有仓库失败后不再开始新的分析
+                if aborted.load(Ordering::SeqCst) {
+                    break;
+                }
+                let next = queue.lock().unwrap().pop_front();
+                let Some((owner, repo, deferrals)) = next else {
+                    break;
+                };
+
+                statuses.lock().unwrap().insert(
+                    worker_id,
+                    WorkerStatus::Analyzing(format!("{}/{}", owner, repo)),
+                );
+                if deferrals == 0 {
+                    let index = started.fetch_add(1, Ordering::SeqCst) + 1;
+                    info!(
+                        "[{}/{}] 工作任务 {} 分析仓库 {}/{}",
+                        index, total, worker_id, owner, repo
+                    );
+                } else {
+                    info!(
+                        "工作任务 {} 继续分析被推迟的仓库 {}/{}（第 {} 轮）",
+                        worker_id,
+                        owner,
+                        repo,
+                        deferrals + 1
+                    );
+                }
+
+                // 设置了单仓库请求数上限时，每轮分析使用新的预算；推迟次数过多时不再限制，保证最终完成
+                let budget = per_repo_request_cap
+                    .filter(|_| deferrals < MAX_REQUEST_CAP_DEFERRALS)
+                    .map(|cap| Arc::new(RequestBudget::new(cap)));
+                let analysis = analyze_git_contributors(&db_service, &owner, &repo, &options);
+                let result = match &budget {
+                    Some(budget) => with_request_budget(Arc::clone(budget), analysis).await,
+                    None => analysis.await,
+                };
+
+                // 因达到请求数上限而中止的仓库放回队列末尾，先分析其他仓库
+                if let (Err(e), Some(budget)) = (&result, &budget) {
+                    if budget.exhausted() {
+                        info!(
+                            "仓库 {}/{} 本轮已使用 {} 个请求，推迟到之后继续: {}",
+                            owner,
+                            repo,
+                            budget.used(),
+                            e
+                        );
+                        queue
+                            .lock()
+                            .unwrap()
+                            .push_back((owner, repo, deferrals + 1));
+                        continue;
+                    }
+                }
+
+                let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                info!("已完成 {}/{} 个仓库", done, total);
+                match result {
+                    Ok(repository_id) => {
+                        succeeded += 1;
+                        // 按该仓库的统计检查告警阈值，检查失败不影响其他仓库的分析
+                        let Some(repository_id) = repository_id else {
+                            continue;
+                        };
+                        match check_repository_alerts(&db_service, &repository_id, alert_thresholds)
+                            .await
+                        {
+                            Ok(true) => alerted.push(format!("{}/{}", owner, repo)),
+                            Ok(false) => {}
+                            Err(e) => warn!("检查仓库 {}/{} 的告警阈值失败: {}", owner, repo, e),
+                        }
+                    }
+                    Err(e) => {
+                        error!("分析仓库 {}/{} 失败: {}", owner, repo, e);
+                        failed.push(format!("{}/{}", owner, repo));
+                        if fail_fast {
+                            aborted.store(true, Ordering::SeqCst);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            statuses
+                .lock()
+                .unwrap()
+                .insert(worker_id, WorkerStatus::Finished);
+            (succeeded, failed, alerted)
+        });
+    }
+
+    // 多个工作任务时定期输出各任务的进度
+    let reporter = (workers > 1).then(|| {
+        let statuses = Arc::clone(&statuses);
+        let started = Arc::clone(&started);
+        let completed = Arc::clone(&completed);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(WORKER_STATUS_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let mut statuses: Vec<_> = statuses
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|(id, status)| (*id, status.clone()))
+                    .collect();
+                statuses.sort_by_key(|(id, _)| *id);
+                info!(
+                    "进度: 已开始 {}/{} 个仓库, 已完成 {} 个",
+                    started.load(Ordering::SeqCst),
+                    total,
+                    completed.load(Ordering::SeqCst)
+                );
+                for (id, status) in statuses {
+                    info!("  工作任务 {}: {}", id, status);
+                }
+            }
+        })
+    });
+
+    let mut succeeded = 0;
+    let mut failed = Vec::new();
+    let mut alerted = Vec::new();
+    while let Some(result) = handles.join_next().await {
+        let (worker_succeeded, worker_failed, worker_alerted) = result?;
+        succeeded += worker_succeeded;
+        failed.extend(worker_failed);
+        alerted.extend(worker_alerted);
+    }
+    if let Some(reporter) = reporter {
+        reporter.abort();
+    }
+
+    info!(
+        "全部仓库分析完成: 共 {} 个, 成功 {} 个, 跳过 {} 个, 失败 {} 个",
+        programs.len(),
+        succeeded,
+        skipped,
+        failed.len()
+    );
+    for repo in &skipped_forks {
+        info!("  跳过fork: {}", repo);
+    }
+    for repo in &failed {
+        info!("  失败: {}", repo);
+    }
+    for repo in &alerted {
+        info!("  超过告警阈值: {}", repo);
+    }
+
+    if aborted.load(Ordering::SeqCst) {
+        let remaining = queue.lock().unwrap().len();
+        return Err(format!(
+            "快速失败: 仓库 {} 分析失败，已停止，剩余 {} 个仓库未分析",
+            failed.join(", "),
+            remaining
+        )
+        .into());
+    }
+
+    Ok(!alerted.is_empty())
+}
+
+// 输出批量分析的成本估算并询问是否继续，用户输入y或yes时返回true
+async fn confirm_batch_estimate(
+    db_service: &DbService,
+    repos: &[(String, String)],
+    skipped: usize,
+) -> Result<bool, BoxError> {
+    let estimate = db_service
+        .estimate_batch_cost(repos, &GitHubApiClient::new())
+        .await?;
+
+    // 认证请求每个令牌每小时5000次，未配置令牌时每小时60次
+    let tokens = github_token_count();
+    let hourly_limit = if tokens == 0 {
+        UNAUTHENTICATED_HOURLY_LIMIT
+    } else {
+        AUTHENTICATED_HOURLY_LIMIT * tokens as u64
+    };
+    let by_delay = average_request_delay().as_secs_f64() * estimate.estimated_api_calls as f64;
+    let by_rate_limit = estimate.estimated_api_calls as f64 / hourly_limit as f64 * 3600.0;
+    let minutes = by_delay.max(by_rate_limit) / 60.0;
+
+    info!("批量分析估算:");
+    info!(
+        "  待分析仓库: {} 个（分析过 {} 个, 从未分析 {} 个, 未登记 {} 个）, 按筛选条件跳过 {} 个",
+        estimate.repositories,
+        estimate.previously_analyzed,
+        estimate.never_analyzed,
+        estimate.unregistered,
+        skipped
+    );
+    info!(
+        "  预计新贡献者: {} 人（从未分析的仓库，按GitHub星标数估算）",
+        estimate.estimated_new_contributors
+    );
+    info!(
+        "  预计API请求: {} 次（{} 个令牌, 每小时上限 {} 次）",
+        estimate.estimated_api_calls, tokens, hourly_limit
+    );
+    info!("  预计耗时: 约 {:.0} 分钟", minutes.ceil());
+
+    print!("是否继续? [y/N] ");
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// 中国贡献者比例告警阈值（百分比），未设置的阈值不检查
+#[derive(Debug, Clone, Copy, Default)]
+struct AlertThresholds {
+    china_pct: Option<f64>,
+    china_commits_pct: Option<f64>,
+}
+
+impl AlertThresholds {
+    fn is_empty(&self) -> bool {
+        self.china_pct.is_none() && self.china_commits_pct.is_none()
+    }
+
+    // 检查人数比例和提交数比例，任一超过阈值时输出告警并返回true
+    fn check(&self, china_pct: f64, china_commits_pct: f64) -> bool {
+        let checks = [
+            ("contributor", china_pct, self.china_pct),
+            ("commit", china_commits_pct, self.china_commits_pct),
+        ];
+        let mut exceeded = false;
+        for (kind, value, threshold) in checks {
+            let Some(threshold) = threshold else {
+                continue;
+            };
+            if value > threshold {
+                exceeded = true;
+                error!("中国贡献者比例超过告警阈值");
+                eprintln!(
+                    "ALERT: China {} ratio ({:.1}%) exceeds threshold ({:.1}%)",
+                    kind, value, threshold
+                );
+            }
+        }
+        exceeded
+    }
+
+    // 是否超过告警阈值对应的进程退出码
+    fn exit_code(alerted: bool) -> ExitCode {
+        if alerted {
+            ExitCode::from(ALERT_EXIT_CODE)
+        } else {
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+// 分析完成后按数据库中的统计检查告警阈值，超过阈值时返回true；
+// 从主库读取，避免副本尚未同步刚写入的分析结果
+async fn check_repository_alerts(
+    db_service: &DbService,
+    repository_id: &str,
+    thresholds: AlertThresholds,
+) -> Result<bool, BoxError> {
+    if thresholds.is_empty() {
+        return Ok(false);
+    }
+    let stats = db_service
+        .without_read_replica()
+        .get_repository_china_contributor_stats(repository_id)
+        .await?;
+    Ok(thresholds.check(stats.china_percentage, stats.china_commits_percentage))
+}
+
+// 通过连接参数把该连接上事务的默认模式设为只读，防止手写SQL绕过写入检查或误写副本
+fn read_only_transactions(url: &str) -> String {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!(
+        "{}{}options[default_transaction_read_only]=on",
+        url, separator
+    )
+}
+
+// 输出JSON，指定了JSONPath时只输出匹配的值（没有匹配时输出[]）
+fn print_json(value: &serde_json::Value, json_output: &JsonOutput) -> Result<(), BoxError> {
+    let value = match &json_output.path {
+        Some(path) => {
+            let selected = jsonpath_lib::select(value, path)
+                .map_err(|e| format!("无效的JSONPath表达式 {:?}: {:?}", path, e))?;
+            serde_json::Value::Array(selected.into_iter().cloned().collect())
+        }
+        None => value.clone(),
+    };
+
+    let json = if json_output.compact {
+        serde_json::to_string(&value)?
+    } else {
+        serde_json::to_string_pretty(&value)?
+    };
+    println!("{}", json);
+    Ok(())
+}
+
+// 加载并校验配置文件，输出其中的关键设置
+fn validate_config_file(path: &str) -> Result<(), BoxError> {
+    let config = load_from_file(path)?;
+
+    info!("配置文件 {} 校验通过", path);
+    info!("  GitHub令牌: {} 个", config.github.tokens.len());
+    match &config.database {
+        Some(db) => info!(
+            "  数据库schema: {}",
+            db.schema.as_deref().unwrap_or("未设置")
+        ),
+        None => info!("  未配置数据库，将使用DATABASE_URL环境变量"),
+    }
+
+    Ok(())
+}
+
+// 逐行读取CSV并分批导入仓库，跳过并报告无效的行
+async fn import_programs(db_service: &DbService, csv_path: &str) -> Result<(), BoxError> {
+    const BATCH_SIZE: usize = 500;
+
+    info!("从 {} 导入仓库", csv_path);
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(csv_path)?;
+
+    // 已有仓库只加载一次，各批次共用并随导入更新
+    let mut existing = db_service.load_program_index().await?;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut summary = UpsertSummary::default();
+    let mut skipped = 0;
+
+    for (i, record) in reader.deserialize::<ProgramRow>().enumerate() {
+        // 第1行为表头
+        let line = i + 2;
+        let row = match record {
+            Ok(row) => row,
+            Err(e) => {
+                warn!("跳过第 {} 行: {}", line, e);
+                skipped += 1;
+                continue;
+            }
+        };
+        if row.name.is_empty() || parse_github_repo_url(&row.github_url).is_none() {
+            warn!("跳过第 {} 行: 无效的GitHub地址 {:?}", line, row.github_url);
+            skipped += 1;
+            continue;
+        }
+
+        batch.push(row);
+        if batch.len() >= BATCH_SIZE {
+            let result = db_service.upsert_programs(&batch, &mut existing).await?;
+            summary.inserted += result.inserted;
+            summary.updated += result.updated;
+            summary.unchanged += result.unchanged;
+            batch.clear();
+        }
+    }
+
+    if !batch.is_empty() {
+        let result = db_service.upsert_programs(&batch, &mut existing).await?;
+        summary.inserted += result.inserted;
+        summary.updated += result.updated;
+        summary.unchanged += result.unchanged;
+    }
+
+    info!(
+        "仓库导入完成: 新增 {} 个, 更新 {} 个, 未变化 {} 个, 跳过 {} 行",
+        summary.inserted, summary.updated, summary.unchanged, skipped
+    );
+    Ok(())
+}
+
+// 清理克隆目录中最后修改时间早于阈值的仓库，并报告释放的磁盘空间
+fn clean_clone_cache(base_dir: &Path, older_than: Duration) -> Result<(), BoxError> {
+    if !base_dir.exists() {
+        info!("克隆目录 {:?} 不存在，无需清理", base_dir);
+        return Ok(());
+    }
+
+    let now = std::time::SystemTime::now();
+    let mut removed = 0;
+    let mut reclaimed_bytes = 0;
+
+    // 克隆目录结构为 {base_dir}/{owner}/{repo}
+    for owner_entry in fs::read_dir(base_dir)? {
+        let owner_dir = owner_entry?.path();
+        if !owner_dir.is_dir() {
+            continue;
+        }
+
+        for repo_entry in fs::read_dir(&owner_dir)? {
+            let repo_dir = repo_entry?.path();
+            if !repo_dir.is_dir() {
+                continue;
+            }
+
+            let age = now
+                .duration_since(last_modified(&repo_dir))
+                .unwrap_or_default();
+            if age < older_than {
+                continue;
+            }
+
+            let size = dir_size(&repo_dir);
+            match fs::remove_dir_all(&repo_dir) {
+                Ok(_) => {
+                    info!(
+                        "已删除 {:?}（{} 天未更新，{:.1} MB）",
+                        repo_dir,
+                        age.as_secs() / 86400,
+                        size as f64 / 1024.0 / 1024.0
+                    );
+                    removed += 1;
+                    reclaimed_bytes += size;
+                }
+                Err(e) => warn!("删除 {:?} 失败: {}", repo_dir, e),
+            }
+        }
+
+        // 删除已经清空的所有者目录
+        if fs::read_dir(&owner_dir)?.next().is_none() {
+            fs::remove_dir(&owner_dir)?;
+        }
+    }
+
+    info!(
+        "清理完成: 删除了 {} 个仓库克隆，释放 {:.1} MB 磁盘空间",
+        removed,
+        reclaimed_bytes as f64 / 1024.0 / 1024.0
+    );
+
+    Ok(())
+}
+
+// 获取仓库克隆的最后修改时间，git pull只会更新.git目录中的文件，因此同时检查.git和FETCH_HEAD
+fn last_modified(repo_dir: &Path) -> std::time::SystemTime {
+    [
+        repo_dir.to_path_buf(),
+        repo_dir.join(".git"),
+        repo_dir.join(".git/FETCH_HEAD"),
+    ]
+    .iter()
+    .filter_map(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+    .max()
+    .unwrap_or(std::time::UNIX_EPOCH)
+}
+
+// 递归计算目录占用的字节数
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(t) if t.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+// 查询仓库的顶级贡献者
+async fn query_top_contributors(
+    db_service: &DbService,
+    owner: &str,
+    repo: &str,
+    filter: &ContributorFilter,
+    anonymize_logins: bool,
+    json_output: Option<&JsonOutput>,
+) -> Result<(), BoxError> {
+    info!("查询仓库 {}/{} 的顶级贡献者", owner, repo);
+
+    // 获取仓库ID
+    let repository_id = match db_service.get_repository_id(owner, repo).await? {
+        Some(found) => found.id,
+        None => {
+            warn!("仓库 {}/{} 未在数据库中注册", owner, repo);
+            return Ok(());
+        }
+    };
+
+    // 查询贡献者统计
+    let contributors = db_service
+        .query_top_contributors(&repository_id, filter)
+        .await;
+
+    // JSON输出包含全部贡献者和统计信息，查询失败时直接返回错误
+    if let Some(json_output) = json_output {
+        let mut contributors = contributors?;
+        let mut china_stats = db_service
+            .get_repository_china_contributor_stats(&repository_id)
+            .await?;
+        if anonymize_logins {
+            contributors.iter_mut().for_each(|c| c.anonymize());
+            china_stats
+                .china_contributors_details
+                .iter_mut()
+                .for_each(|c| c.anonymize());
+        }
+        let value = serde_json::json!({
+            "owner": owner,
+            "repo": repo,
+            "repository_id": repository_id,
+            "china_stats": china_stats,
+            "languages": db_service.get_language_china_breakdown(&repository_id).await?,
+            "contributors": contributors,
+        });
+        return print_json(&value, json_output);
+    }
+
+    match contributors {
+        Ok(mut top_contributors) => {
+            if anonymize_logins {
+                top_contributors.iter_mut().for_each(|c| c.anonymize());
+            }
+
+            info!("仓库 {}/{} 的贡献者统计:", owner, repo);
+            for (i, contributor) in top_contributors.iter().enumerate().take(10) {
+                let location_str = contributor
+                    .location
+                    .as_ref()
+                    .map(|loc| format!(" ({})", loc))
+                    .unwrap_or_default();
+
+                let probability_str = contributor
+                    .china_probability
+                    .map(|p| format!(" [中国概率 {:.2}]", p))
+                    .unwrap_or_default();
+
+                let name_display = contributor.name.as_ref().unwrap_or(&contributor.login);
+                let id_str = contributor
+                    .id
+                    .map(|id| format!(" #{}", id))
+                    .unwrap_or_default();
+
+                info!(
+                    "  {}. {}{}{}{} - {} 次提交",
+                    i + 1,
+                    name_display,
+                    id_str,
+                    location_str,
+                    probability_str,
+                    contributor.contributions
+                );
+            }
+        }
+        Err(e) => {
+            error!("查询贡献者统计失败: {}", e);
+        }
+    }
+
+    // 查询中国贡献者统计
+    match db_service
+        .get_repository_china_contributor_stats(&repository_id)
+        .await
+    {
+        Ok(stats) => {
+            info!(
+                "仓库 {}/{} 的中国贡献者统计: {}人中有{}人来自中国 ({:.1}%)",
+                owner,
+                repo,
+                stats.total_contributors,
+                stats.china_contributors,
+                stats.china_percentage
+            );
+        }
+        Err(e) => {
+            error!("获取中国贡献者统计失败: {}", e);
+        }
+    }
+
+    // 按语言统计中国贡献者比例
+    match db_service
+        .get_language_china_breakdown(&repository_id)
+        .await
+    {
+        Ok(stats) if !stats.is_empty() => {
+            info!("按语言的中国贡献者统计:");
+            for stat in stats {
+                info!(
+                    "  {}: {}人中有{}人来自中国 ({:.1}%)",
+                    stat.language,
+                    stat.total_contributors,
+                    stat.china_contributors,
+                    stat.china_percentage
+                );
+            }
+        }
+        Ok(_) => {}
+        Err(e) => {
+            error!("获取按语言的中国贡献者统计失败: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<ExitCode, BoxError> {
+    // 加载.env文件
+    dotenv().ok();
+
+    // 解析命令行参数
+    let cli = Cli::parse();
+
+    // 初始化日志
+    init_logger(cli.dump_sql, cli.dry_run_show_sql);
+
+    // sqlx输出已执行语句的日志级别，--dry-run-show-sql时提升到info
+    let sqlx_log_level = if cli.dry_run_show_sql {
+        log::LevelFilter::Info
+    } else {
+        log::LevelFilter::Debug
+    };
+
+    // 设置配置文件路径，需在首次读取配置前完成
+    set_config_path(cli.config.clone());
+    set_user_agent(cli.github_user_agent.clone());
+    set_include_web_flow(cli.include_web_flow);
+
+    // 模拟判定会写入假数据，只允许在预览模式下使用或显式允许，避免误用污染生产数据库
+    let dry_run = matches!(cli.command, Some(Commands::ReclassifyAll { dry_run: true }));
+    if cli.simulate_china.is_some()
+        && !dry_run
+        && std::env::var("CRATES_PRO_ALLOW_SIMULATE").as_deref() != Ok("1")
+    {
+        return Err(
+            "--simulate-china 会写入模拟数据，只能在 --dry-run 预览时使用，或设置环境变量 CRATES_PRO_ALLOW_SIMULATE=1"
+                .into(),
+        );
+    }
+
+    // 设置git命令选项
+    set_git_options(GitOptions {
+        no_verify_commits: cli.no_verify_commits,
+        respect_git_date_config: cli.respect_git_date_config,
+        commit_message_filter: cli.commit_message_filter.clone(),
+    });
+
+    // 加载判定覆盖列表
+    let china_overrides = match &cli.china_overrides {
+        Some(path) => ChinaOverrides::load(path)?,
+        None => ChinaOverrides::default(),
+    };
+
+    let alert_thresholds = AlertThresholds {
+        china_pct: cli.alert_threshold_china_pct,
+        china_commits_pct: cli.alert_threshold_china_commits_pct,
+    };
+
+    // 处理贡献者分析请求
+    if let Some(repo_path) = cli.analyze_contributors {
+        let report = generate_contributors_report(
+            &repo_path,
+            &china_overrides,
+            cli.confidence_gap,
+            cli.max_timezone_entropy,
+            cli.min_timezone_share,
+            chrono::Duration::from_std(cli.active_window)?,
+        )
+        .await;
+        report.print_summary();
+
+        // 如果提供了第二个位置参数，将结果按指定格式保存
+        let output = match cli.format {
+            ReportFormat::Json if cli.repo.is_none() => None,
+            ReportFormat::Json if cli.compact => Some(report.to_json_compact()?),
+            ReportFormat::Json => Some(report.to_json()?),
+            ReportFormat::Markdown => Some(report.to_markdown()),
+        };
+        match (cli.repo, output) {
+            (Some(output_path), Some(output)) => {
+                std::fs::write(&output_path, output)?;
+                info!("分析结果已保存到: {}", output_path);
+            }
+            (None, Some(output)) => print!("{}", output),
+            _ => {}
+        }
+
+        if let Some(output_dir) = cli.output_dir {
+            fs::create_dir_all(&output_dir)?;
+            let errors_path = Path::new(&output_dir).join("analysis_errors.json");
+            fs::write(
+                &errors_path,
+                serde_json::to_string_pretty(&report.error_log)?,
+            )?;
+            info!(
+                "{} 个分析失败的作者已保存到: {}",
+                report.failed_analyses,
+                errors_path.display()
+            );
+        }
+
+        let alerted =
+            alert_thresholds.check(report.china_percentage, report.china_commits_percentage());
+        return Ok(AlertThresholds::exit_code(alerted));
+    }
+
+    // 告警阈值只在分析仓库后检查，其他命令不会产生可比较的统计
+    if !alert_thresholds.is_empty()
+        && !matches!(
+            cli.command,
+            None | Some(Commands::Analyze { .. }) | Some(Commands::AnalyzeAll { .. })
+        )
+    {
+        return Err(
+            "--alert-threshold-china-pct和--alert-threshold-china-commits-pct只能用于analyze、analyze-all或--analyze-contributors"
+                .into(),
+        );
+    }
+
+    // 清理克隆缓存不需要连接数据库
+    if let Some(Commands::CleanCache { older_than }) = cli.command {
+        clean_clone_cache(Path::new(&cli.clone_dir), older_than)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // 检查单个作者的分析结果不需要连接数据库
+    if let Some(Commands::Inspect { repo_path, email }) = &cli.command {
+        let Some(mut analysis) =
+            contributor_analysis::analyze_contributor_timezone(repo_path, email).await
+        else {
+            return Err(format!("无法读取仓库 {} 中作者 {} 的提交", repo_path, email).into());
+        };
+
+        // 与分析流程使用相同的选项（时区占比、README线索、概率阈值、覆盖列表等）判定
+        let options = AnalyzeOptions {
+            china_overrides: china_overrides.clone(),
+            confidence_gap: cli.confidence_gap,
+            max_timezone_entropy: cli.max_timezone_entropy,
+            min_timezone_share: cli.min_timezone_share,
+            simulate_china: cli.simulate_china,
+            china_threshold: crate::config::get_china_threshold(),
+            ..AnalyzeOptions::default()
+        };
+        let readme_hints = contributor_analysis::local_readme_location_hints(repo_path);
+        apply_analysis_options(&mut analysis, None, None, &readme_hints, &options);
+
+        // 输出中附上各判定信号的得分，解释判定结果
+        let mut output = serde_json::to_value(&analysis)?;
+        output["signals"] = serde_json::to_value(analysis.classification_signals())?;
+        let analysis = output;
+
+        if cli.compact {
+            println!("{}", serde_json::to_string(&analysis)?);
+        } else {
+            println!("{}", serde_json::to_string_pretty(&analysis)?);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // 校验配置文件不需要连接数据库
+    if let Some(Commands::Config {
+        action: ConfigCommands::Validate { path },
+    }) = &cli.command
+    {
+        validate_config_file(path)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // 需要访问GitHub API的命令在连接数据库前先校验令牌，避免在分析中途才因令牌问题失败
+    let uses_github_api = matches!(
+        cli.command,
+        Some(Commands::Analyze { .. })
+            | Some(Commands::AnalyzeAll { .. })
+            | Some(Commands::Backfill { .. })
+            | Some(Commands::Stale { activity: true, .. })
+    ) || (cli.command.is_none() && cli.owner.is_some() && cli.repo.is_some());
+    if uses_github_api && has_github_token() {
+        // 轮换时每个令牌都可能被使用，因此逐个校验
+        let required_scopes: Vec<&str> = cli.require_scopes.iter().map(String::as_str).collect();
+        let tokens = github_tokens();
+        for (index, token) in tokens.into_iter().enumerate() {
+            let permissions = GitHubApiClient::new()
+                .with_token(token)
+                .verify_token_permissions(&required_scopes)
+                .await
+                .map_err(|e| format!("校验第 {} 个GitHub令牌失败: {}", index + 1, e))?;
+            info!(
+                "第 {} 个GitHub令牌校验通过: 用户 {}, 权限范围 [{}], 剩余请求数 {}",
+                index + 1,
+                permissions.login,
+                permissions.scopes.join(", "),
+                permissions
+                    .rate_limit_remaining
+                    .map(|r| r.to_string())
+                    .unwrap_or_else(|| "未知".to_string())
+            );
+        }
+    }
+
+    // 连接数据库
+    info!("连接数据库...");
+    // 只读模式下优先连接只读副本，不再单独连接主库
+    let read_url = get_read_database_url();
+    let db_url = if cli.readonly {
+        info!("以只读模式连接数据库");
+        read_only_transactions(&read_url.clone().unwrap_or_else(get_database_url))
+    } else {
+        get_database_url()
+    };
+    let db_schema = cli.db_schema.unwrap_or_else(get_database_schema);
+    info!("使用数据库schema: {}", db_schema);
+
+    // 只读模式下不执行迁移
+    let SyncContext { db, github } = SyncContext::builder(db_url)
+        .schema(db_schema.clone())
+        .migrate(!cli.readonly)
+        .connect_retries(cli.db_connect_retries, cli.db_connect_interval)
+        .sqlx_logging_level(sqlx_log_level)
+        .build()
+        .await?;
+
+    // 创建数据库服务
+    let mut db_service = db
+        .with_contribution_limit(cli.max_contributions, cli.reject_excess_contributions)
+        .with_readonly(cli.readonly)
+        .with_loose_url_match(cli.loose_url_match)
+        .with_show_sql(cli.dry_run_show_sql);
+
+    // 配置了只读副本时，查询类方法改用副本，减少与分析写入的争用
+    if let Some(read_url) = read_url.filter(|_| !cli.readonly) {
+        info!("连接只读副本...");
+        let mut read_options = ConnectOptions::new(read_only_transactions(&read_url));
+        read_options
+            .set_schema_search_path(db_schema)
+            .sqlx_logging_level(sqlx_log_level);
+        let read_conn = connect_with_retries(
+            read_options,
+            None,
+            cli.db_connect_retries,
+            cli.db_connect_interval,
+        )
+        .await
+        .map_err(|e| format!("连接只读副本失败: {}", e))?;
+        db_service = db_service.with_read_replica(read_conn);
+    }
+
+    let analyze_options = AnalyzeOptions {
+        clone_dir: cli.clone_dir,
+        mirror_base: cli.mirror_base,
+        china_overrides,
+        confidence_gap: cli.confidence_gap,
+        max_timezone_entropy: cli.max_timezone_entropy,
+        min_timezone_share: cli.min_timezone_share,
+        no_user_details: cli.no_user_details,
+        user_detail_retries: cli.user_detail_retries,
+        china_orgs: cli.china_orgs.iter().map(|o| o.to_lowercase()).collect(),
+        prefer_api_timezone: cli.prefer_api_timezone,
+        max_clone_size_mb: (!cli.force_large_clone).then_some(cli.max_clone_size_mb),
+        stream_contributors_every: cli.stream_contributors_every,
+        anonymize_logins: cli.anonymize_logins,
+        simulate_china: cli.simulate_china,
+        china_threshold: crate::config::get_china_threshold(),
+        // 按访问量排列分析顺序时同时刷新各仓库的访问统计，供下次批量分析使用
+        fetch_traffic: matches!(
+            cli.command,
+            Some(Commands::AnalyzeAll {
+                traffic_weight: Some(_),
+                ..
+            })
+        ),
+    };
+
+    // 分析的仓库中是否有超过告警阈值的
+    let mut alerted = false;
+
+    // 处理子命令
+    match cli.command {
+        Some(Commands::Analyze {
+            owner,
+            repo,
+            watch,
+            poll_interval_secs,
+        }) => {
+            let analysis_started = chrono::Utc::now();
+            let repository_id =
+                analyze_git_contributors(&db_service, &owner, &repo, &analyze_options).await?;
+            if let Some(repository_id) = &repository_id {
+                alerted =
+                    check_repository_alerts(&db_service, repository_id, alert_thresholds).await?;
+            }
+
+            // 超过告警阈值时直接以告警退出码结束，不再继续监视
+            if alerted {
+                return Ok(AlertThresholds::exit_code(alerted));
+            }
+
+            // 沿用分析时得到的仓库ID，未注册的仓库无法监视
+            if let (true, Some(repository_id)) = (watch, repository_id) {
+                watch_repository(
+                    &db_service,
+                    &owner,
+                    &repo,
+                    &repository_id,
+                    analysis_started,
+                    Duration::from_secs(poll_interval_secs),
+                    &analyze_options,
+                )
+                .await?;
+            }
+        }
+
+        Some(Commands::AnalyzeAll {
+            skip_recent,
+            traffic_weight,
+            exclude_forks,
+            spawn_workers,
+            filter_topic,
+            fail_fast,
+            per_repo_request_cap,
+            estimate,
+        }) => {
+            alerted = analyze_all_repositories(
+                &db_service,
+                skip_recent,
+                traffic_weight,
+                exclude_forks,
+                spawn_workers as usize,
+                filter_topic.as_deref(),
+                fail_fast,
+                per_repo_request_cap,
+                estimate,
+                alert_thresholds,
+                &analyze_options,
+            )
+            .await?;
+        }
+
+        Some(Commands::ImportPrograms { csv }) => {
+            import_programs(&db_service, &csv).await?;
+        }
+
+        Some(Commands::Backfill {
+            github_ids,
+            dry_run,
+        }) => {
+            if !github_ids {
+                return Err("请指定要回填的数据，如 --github-ids".into());
+            }
+
+            let result = db_service
+                .backfill_missing_github_ids(&github, dry_run)
+                .await?;
+            info!(
+                "{}回填github_id完成: 共 {} 个用户, 回填 {} 个, 合并 {} 个, 已不存在 {} 个, 失败 {} 个",
+                if dry_run { "[预览] " } else { "" },
+                result.checked,
+                result.updated,
+                result.merged,
+                result.not_found,
+                result.failed
+            );
+        }
+
+        Some(Commands::MergeUsers {
+            keep_login,
+            drop_login,
+            dry_run,
+        }) => {
+            let Some(keep_id) = db_service.get_user_id_by_name(&keep_login).await? else {
+                return Err(format!("未找到用户 {}", keep_login).into());
+            };
+            let Some(drop_id) = db_service.get_user_id_by_name(&drop_login).await? else {
+                return Err(format!("未找到用户 {}", drop_login).into());
+            };
+
+            let result = db_service.merge_users(keep_id, drop_id, dry_run).await?;
+            info!(
+                "{}合并用户 {} <- {}: 贡献者关系 转移 {} 条/合并 {} 条, 位置记录 转移 {} 条/删除 {} 条, 提交文件 转移 {} 条, 删除用户 {} 个",
+                if dry_run { "[预览] " } else { "" },
+                keep_login,
+                drop_login,
+                result.contributors_updated,
+                result.contributors_merged,
+                result.locations_updated,
+                result.locations_deleted,
+                result.commit_files_updated,
+                result.users_deleted
+            );
+        }
+
+        Some(Commands::Reset { owner, repo, yes }) => {
+            let Some(found) = db_service.get_repository_id(&owner, &repo).await? else {
+                return Err(format!("仓库 {}/{} 未在数据库中注册", owner, repo).into());
+            };
+
+            let result = db_service
+                .reset_analysis_for_repository(&found.id, !yes)
+                .await?;
+            info!(
+                "{}重置仓库 {}/{}: 删除位置记录 {} 条, 清零贡献者关系 {} 条, 作废分析记录 {} 条",
+                if yes { "" } else { "[预览] " },
+                owner,
+                repo,
+                result.locations_deleted,
+                result.contributors_reset,
+                result.runs_invalidated
+            );
+            if !yes {
+                info!("确认无误后加上 --yes 执行重置");
+            }
+        }
+
+        Some(Commands::ReclassifyAll { dry_run }) => {
+            reclassify_all(&db_service, dry_run, &analyze_options).await?;
+        }
+
+        Some(Commands::Calibrate {
+            csv,
+            save_threshold,
+        }) => {
+            calibrate(&db_service, &csv, save_threshold).await?;
+        }
+
+        Some(Commands::Stale {
+            older_than,
+            activity,
+        }) => {
+            let stale = db_service.repos_older_than(older_than).await?;
+            info!("共有 {} 个仓库的数据已过期:", stale.len());
+            for (owner, repo) in stale {
+                if !activity {
+                    info!("  {}/{}", owner, repo);
+                    continue;
+                }
+                // 获取活跃度失败时仍然列出该仓库
+                match github.get_repository_participation(&owner, &repo).await {
+                    Ok(weeks) => {
+                        let recent: i64 = weeks.iter().rev().take(4).sum();
+                        info!("  {}/{} (最近4周 {} 次提交)", owner, repo, recent);
+                    }
+                    Err(e) => {
+                        warn!("获取仓库 {}/{} 的提交活跃度失败: {}", owner, repo, e);
+                        info!("  {}/{}", owner, repo);
+                    }
+                }
+            }
+        }
+
+        Some(Commands::TopRepos {
+            min_contributors,
+            limit,
+            filter_topic,
+        }) => {
+            let rankings = db_service
+                .get_top_repositories_by_china_ratio(
+                    min_contributors,
+                    limit,
+                    filter_topic.as_deref(),
+                )
+                .await?;
+            info!("中国贡献者比例最高的仓库:");
+            for (i, ranking) in rankings.iter().enumerate() {
+                info!(
+                    "  {}. {} ({}) - {}人中有{}人来自中国 ({:.1}%)",
+                    i + 1,
+                    ranking.name,
+                    ranking.github_url.as_deref().unwrap_or(&ranking.program_id),
+                    ranking.total_contributors,
+                    ranking.china_contributors,
+                    ranking.china_percentage
+                );
+            }
+        }
+
+        Some(Commands::Leaderboard { region, limit }) => {
+            let mut entries = match region {
+                LeaderboardRegion::China => db_service.global_china_leaderboard(limit).await?,
+            };
+            if cli.anonymize_logins {
+                entries.iter_mut().for_each(|e| e.anonymize());
+            }
+            if entries.is_empty() {
+                warn!("没有已分析的中国贡献者");
+            }
+            info!("所有仓库中贡献数最多的中国贡献者:");
+            for (i, entry) in entries.iter().enumerate() {
+                let name_display = entry
+                    .name
+                    .as_ref()
+                    .map(|name| format!(" ({})", name))
+                    .unwrap_or_default();
+                info!(
+                    "  {}. {}{} - {} 次贡献, 参与 {} 个仓库",
+                    i + 1,
+                    entry.login,
+                    name_display,
+                    entry.total_contributions,
+                    entry.repo_count
+                );
+            }
+        }
+
+        Some(Commands::User {
+            action: UserCommands::Repos { login },
+        }) => {
+            let entries = db_service.get_contributor_repositories(&login).await?;
+            if entries.is_empty() {
+                warn!("用户 {} 没有参与任何已登记的仓库", login);
+            }
+            for entry in &entries {
+                let classification = match entry.is_from_china {
+                    Some(true) => "来自中国",
+                    Some(false) => "非中国",
+                    None => "未分析",
+                };
+                info!(
+                    "  {} ({}) - 贡献数 {}, {}{}",
+                    entry.name,
+                    entry.github_url.as_deref().unwrap_or(&entry.program_id),
+                    entry.contributions,
+                    classification,
+                    entry
+                        .china_probability
+                        .map(|p| format!(" (概率: {:.2})", p))
+                        .unwrap_or_default()
+                );
+            }
+        }
+
+        Some(Commands::Show { owner, repo, login }) => {
+            let Some(found) = db_service.get_repository_id(&owner, &repo).await? else {
+                return Err(format!("仓库 {}/{} 未在数据库中注册", owner, repo).into());
+            };
+            let Some(user_id) = db_service.get_user_id_by_name(&login).await? else {
+                return Err(format!("用户 {} 不存在", login).into());
+            };
+            let Some(location) = db_service
+                .get_contributor_location(&found.id, user_id)
+                .await?
+            else {
+                warn!(
+                    "用户 {} 在仓库 {}/{} 中尚未进行位置分析",
+                    login, owner, repo
+                );
+                return Ok(ExitCode::SUCCESS);
+            };
+
+            info!(
+                "{} 在 {}/{} 中的判定: {} (地区: {}, 常用时区: {}, 概率: {}{})",
+                login,
+                owner,
+                repo,
+                if location.is_from_china {
+                    "来自中国"
+                } else {
+                    "非中国"
+                },
+                location.region_code.as_deref().unwrap_or("未知"),
+                location.common_timezone.as_deref().unwrap_or("未知"),
+                location
+                    .china_probability
+                    .map(|p| format!("{:.2}", p))
+                    .unwrap_or_else(|| "未知".to_string()),
+                if location.uncertain {
+                    ", 不确定"
+                } else {
+                    ""
+                }
+            );
+            info!("分析时间: {}", location.analyzed_at);
+            if let Some(classified_at) = location.classified_at {
+                info!("判定时间: {}", classified_at);
+            }
+
+            let signals = db_service
+                .get_classification_signals(&found.id, user_id)
+                .await?;
+            if signals.is_empty() {
+                info!("该记录没有存储判定信号，重新分析或执行reclassify-all后可查看");
+            }
+            for (name, score) in signals {
+                info!("  {}: {:.2}", name, score);
+            }
+        }
+
+        Some(Commands::CleanCache { .. }) => unreachable!("clean-cache在连接数据库前处理"),
+
+        Some(Commands::Config { .. }) => unreachable!("config在连接数据库前处理"),
+
+        Some(Commands::Inspect { .. }) => unreachable!("inspect在连接数据库前处理"),
+
+        Some(Commands::Query {
+            owner,
+            repo,
+            min_china_probability,
+            max_china_probability,
+            only_china,
+            only_non_china,
+            unanalyzed,
+            region,
+            sort_by,
+            contributors_sort,
+            json,
+            json_path,
+        }) => {
+            let classification = if only_china {
+                ClassificationFilter::China
+            } else if only_non_china {
+                ClassificationFilter::NonChina
+            } else if unanalyzed {
+                ClassificationFilter::Unanalyzed
+            } else {
+                ClassificationFilter::All
+            };
+            let filter = ContributorFilter {
+                min_china_probability,
+                max_china_probability,
+                classification,
+                region_code: region,
+                sort: contributors_sort
+                    .or(sort_by.map(ContributorSort::from))
+                    .unwrap_or_default(),
+            };
+            let json_output = json.then_some(JsonOutput {
+                path: json_path,
+                compact: cli.compact,
+            });
+            query_top_contributors(
+                &db_service,
+                &owner,
+                &repo,
+                &filter,
+                cli.anonymize_logins,
+                json_output.as_ref(),
+            )
+            .await?;
+        }
+
+        None => {
+            // 如果没有提供子命令，但提供了owner和repo参数
+            if let (Some(owner), Some(repo)) = (cli.owner, cli.repo) {
+                let repository_id =
+                    analyze_git_contributors(&db_service, &owner, &repo, &analyze_options).await?;
+                if let Some(repository_id) = repository_id {
+                    alerted =
+                        check_repository_alerts(&db_service, &repository_id, alert_thresholds)
+                            .await?;
+                }
+            } else {
+                // 没有足够的参数，显示帮助信息
+                println!("请提供仓库所有者和名称，或使用子命令。运行 --help 获取更多信息。");
+            }
+        }
+    }
+
+    Ok(AlertThresholds::exit_code(alerted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(
+            parse_duration(" 365d "),
+            Ok(Duration::from_secs(365 * 86400))
+        );
+        assert!(parse_duration("5w").is_err());
+        assert!(parse_duration("d").is_err());
+    }
+
+    #[test]
+    fn alert_thresholds_report_exceeded_ratios() {
+        let thresholds = AlertThresholds {
+            china_pct: Some(50.0),
+            china_commits_pct: None,
+        };
+        assert!(thresholds.check(60.0, 0.0));
+        assert!(!thresholds.check(50.0, 100.0));
+        assert!(!AlertThresholds::default().check(100.0, 100.0));
+        assert_eq!(
+            AlertThresholds::exit_code(true),
+            ExitCode::from(ALERT_EXIT_CODE)
+        );
+    }
+
+    #[test]
+    fn parse_f64_in_checks_range() {
+        let probability = parse_f64_in(0.0..=1.0, "概率");
+        assert_eq!(probability("0.5"), Ok(0.5));
+        assert_eq!(probability("1"), Ok(1.0));
+        assert_eq!(
+            probability("1.5"),
+            Err("概率必须在[0, 1]范围内: 1.5".to_string())
+        );
+        assert!(probability("NaN").is_err());
+        assert!(probability("abc").is_err());
+
+        let weight = parse_f64_in(0.0.., "访问量权重");
+        assert_eq!(weight("12.5"), Ok(12.5));
+        assert!(weight("-1").is_err());
+        assert!(weight("inf").is_err());
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflow() {
+        assert!(parse_duration(&format!("{}d", u64::MAX / 86400 + 1)).is_err());
+        assert!(parse_duration("99999999999999999999s").is_err());
+        assert_eq!(
+            parse_duration(&format!("{}d", u64::MAX / 86400)),
+            Ok(Duration::from_secs(u64::MAX / 86400 * 86400))
+        );
+    }
 }
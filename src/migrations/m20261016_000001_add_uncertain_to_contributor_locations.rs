@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+// 为contributor_locations添加uncertain列，标记处于不确定区间的判定
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 表由外部系统创建，不存在时跳过
+        if !manager.has_table("contributor_locations").await? {
+            return Ok(());
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContributorLocations::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ContributorLocations::Uncertain)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContributorLocations::Table)
+                    .drop_column(ContributorLocations::Uncertain)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ContributorLocations {
+    Table,
+    Uncertain,
+}
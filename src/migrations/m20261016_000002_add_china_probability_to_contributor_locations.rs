@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+// 为contributor_locations添加china_probability列，保存来自中国的概率
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 表由外部系统创建，不存在时跳过
+        if !manager.has_table("contributor_locations").await? {
+            return Ok(());
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContributorLocations::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ContributorLocations::ChinaProbability)
+                            .double()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContributorLocations::Table)
+                    .drop_column(ContributorLocations::ChinaProbability)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ContributorLocations {
+    Table,
+    ChinaProbability,
+}
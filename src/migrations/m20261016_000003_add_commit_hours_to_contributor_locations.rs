@@ -0,0 +1,52 @@
+use sea_orm_migration::prelude::*;
+
+// 为contributor_locations添加提交小时分布（原始计数和归一化比例）
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 表由外部系统创建，不存在时跳过
+        if !manager.has_table("contributor_locations").await? {
+            return Ok(());
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContributorLocations::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ContributorLocations::CommitHours)
+                            .json()
+                            .null(),
+                    )
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ContributorLocations::CommitHoursNormalized)
+                            .json()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContributorLocations::Table)
+                    .drop_column(ContributorLocations::CommitHours)
+                    .drop_column(ContributorLocations::CommitHoursNormalized)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ContributorLocations {
+    Table,
+    CommitHours,
+    CommitHoursNormalized,
+}
@@ -0,0 +1,66 @@
+use sea_orm_migration::prelude::*;
+
+// 创建commit_files表，记录每个提交修改的文件及其语言
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CommitFiles::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CommitFiles::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(CommitFiles::RepositoryId).text().not_null())
+                    .col(ColumnDef::new(CommitFiles::UserId).integer().not_null())
+                    .col(ColumnDef::new(CommitFiles::CommitSha).text().not_null())
+                    .col(ColumnDef::new(CommitFiles::FilePath).text().not_null())
+                    .col(ColumnDef::new(CommitFiles::Language).text().null())
+                    .col(
+                        ColumnDef::new(CommitFiles::CommittedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_commit_files_repository_user")
+                    .table(CommitFiles::Table)
+                    .col(CommitFiles::RepositoryId)
+                    .col(CommitFiles::UserId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CommitFiles::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CommitFiles {
+    Table,
+    Id,
+    RepositoryId,
+    UserId,
+    CommitSha,
+    FilePath,
+    Language,
+    CommittedAt,
+}
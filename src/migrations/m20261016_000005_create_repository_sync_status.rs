@@ -0,0 +1,43 @@
+use sea_orm_migration::prelude::*;
+
+// 创建repository_sync_status表，记录每个仓库最近一次分析的时间
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RepositorySyncStatus::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RepositorySyncStatus::RepositoryId)
+                            .text()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RepositorySyncStatus::LastAnalyzedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RepositorySyncStatus::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RepositorySyncStatus {
+    Table,
+    RepositoryId,
+    LastAnalyzedAt,
+}
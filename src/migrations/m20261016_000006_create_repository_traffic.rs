@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+// 创建repository_traffic表，记录仓库的访问与克隆统计
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RepositoryTraffic::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RepositoryTraffic::RepositoryId)
+                            .text()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RepositoryTraffic::ViewsCount)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(RepositoryTraffic::UniqueVisitors)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(RepositoryTraffic::CloneCount)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(RepositoryTraffic::UniqueCloners)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(RepositoryTraffic::FetchedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RepositoryTraffic::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RepositoryTraffic {
+    Table,
+    RepositoryId,
+    ViewsCount,
+    UniqueVisitors,
+    CloneCount,
+    UniqueCloners,
+    FetchedAt,
+}
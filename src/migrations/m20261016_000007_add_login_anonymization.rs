@@ -0,0 +1,81 @@
+use sea_orm_migration::prelude::*;
+
+// 为github_users添加anonymized_login计算列，并创建login_anonymization_map映射表
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// 与services::database::anonymized_login保持一致：contributor_加登录名SHA256的前8位。
+// 计算列要求表达式不可变，convert_to不满足；GitHub登录名只含字母、数字和连字符，直接转换为bytea结果相同
+const ANONYMIZED_LOGIN_EXPR: &str =
+    "GENERATED ALWAYS AS ('contributor_' || left(encode(sha256(login::bytea), 'hex'), 8)) STORED";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginAnonymizationMap::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LoginAnonymizationMap::OriginalHash)
+                            .text()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(LoginAnonymizationMap::AnonymizedLogin)
+                            .text()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 表由外部系统创建，不存在时跳过
+        if !manager.has_table("github_users").await? {
+            return Ok(());
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GithubUsers::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(GithubUsers::AnonymizedLogin)
+                            .text()
+                            .extra(ANONYMIZED_LOGIN_EXPR),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GithubUsers::Table)
+                    .drop_column(GithubUsers::AnonymizedLogin)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(LoginAnonymizationMap::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum GithubUsers {
+    Table,
+    AnonymizedLogin,
+}
+
+#[derive(DeriveIden)]
+enum LoginAnonymizationMap {
+    Table,
+    OriginalHash,
+    AnonymizedLogin,
+}
@@ -0,0 +1,55 @@
+use sea_orm_migration::prelude::*;
+
+// 为contributor_locations添加region_code列（ISO-3166国家/地区代码），为多地区判定做准备
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 表由外部系统创建，不存在时跳过
+        if !manager.has_table("contributor_locations").await? {
+            return Ok(());
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContributorLocations::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ContributorLocations::RegionCode)
+                            .string_len(2)
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 已有的中国贡献者记录补齐地区代码
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE contributor_locations SET region_code = 'CN' WHERE is_from_china AND region_code IS NULL",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContributorLocations::Table)
+                    .drop_column(ContributorLocations::RegionCode)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ContributorLocations {
+    Table,
+    RegionCode,
+}
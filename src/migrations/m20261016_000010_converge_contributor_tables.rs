@@ -0,0 +1,99 @@
+use sea_orm_migration::prelude::*;
+
+// 收敛贡献者相关表的结构：无论外部系统执行过哪些旧迁移，都保证repository_id为TEXT、
+// (repository_id, user_id)上有唯一索引，并且存在指向programs和github_users的外键
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// 需要收敛的表
+const TABLES: [&str; 2] = ["repository_contributors", "contributor_locations"];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in TABLES {
+            // 表由外部系统创建，不存在时跳过
+            if !manager.has_table(table).await? {
+                continue;
+            }
+
+            manager
+                .get_connection()
+                .execute_unprepared(&converge_sql(table))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // 只补齐缺失的结构，回滚时保持不变
+        Ok(())
+    }
+}
+
+// 生成收敛单个表结构的SQL，每一步都先检查当前结构，已满足时不做修改
+fn converge_sql(table: &str) -> String {
+    format!(
+        r#"
+DO $$
+DECLARE
+    fk RECORD;
+BEGIN
+    -- repository_id不是TEXT时，先删除引用该列的外键再修改类型
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_schema = current_schema() AND table_name = '{table}'
+          AND column_name = 'repository_id' AND data_type <> 'text'
+    ) THEN
+        FOR fk IN
+            SELECT con.conname FROM pg_constraint con
+            JOIN pg_attribute att ON att.attrelid = con.conrelid AND att.attnum = ANY (con.conkey)
+            WHERE con.conrelid = '{table}'::regclass AND con.contype = 'f'
+              AND att.attname = 'repository_id'
+        LOOP
+            EXECUTE format('ALTER TABLE {table} DROP CONSTRAINT %I', fk.conname);
+        END LOOP;
+        ALTER TABLE {table} ALTER COLUMN repository_id TYPE TEXT USING repository_id::text;
+    END IF;
+
+    -- 没有(repository_id, user_id)唯一索引时，先删除重复记录（保留最新的一条）再创建
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_index idx
+        WHERE idx.indrelid = '{table}'::regclass AND idx.indisunique
+          AND idx.indnkeyatts = 2
+          AND (SELECT array_agg(att.attname::text ORDER BY att.attname)
+               FROM pg_attribute att
+               WHERE att.attrelid = idx.indrelid AND att.attnum = ANY (idx.indkey))
+              = ARRAY['repository_id', 'user_id']
+    ) THEN
+        DELETE FROM {table} a USING {table} b
+        WHERE a.repository_id = b.repository_id AND a.user_id = b.user_id AND a.id < b.id;
+        CREATE UNIQUE INDEX {table}_repository_id_user_id_idx ON {table} (repository_id, user_id);
+    END IF;
+
+    -- 补齐外键，已有数据中可能存在孤立记录，使用NOT VALID只约束新写入的数据
+    IF to_regclass('programs') IS NOT NULL AND NOT EXISTS (
+        SELECT 1 FROM pg_constraint con
+        JOIN pg_attribute att ON att.attrelid = con.conrelid AND att.attnum = ANY (con.conkey)
+        WHERE con.conrelid = '{table}'::regclass AND con.contype = 'f'
+          AND att.attname = 'repository_id'
+    ) THEN
+        ALTER TABLE {table} ADD CONSTRAINT {table}_repository_id_fkey
+            FOREIGN KEY (repository_id) REFERENCES programs (id) NOT VALID;
+    END IF;
+
+    IF to_regclass('github_users') IS NOT NULL AND NOT EXISTS (
+        SELECT 1 FROM pg_constraint con
+        JOIN pg_attribute att ON att.attrelid = con.conrelid AND att.attnum = ANY (con.conkey)
+        WHERE con.conrelid = '{table}'::regclass AND con.contype = 'f'
+          AND att.attname = 'user_id'
+    ) THEN
+        ALTER TABLE {table} ADD CONSTRAINT {table}_user_id_fkey
+            FOREIGN KEY (user_id) REFERENCES github_users (id) NOT VALID;
+    END IF;
+END $$;
+"#,
+        table = table
+    )
+}
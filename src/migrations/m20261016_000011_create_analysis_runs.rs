@@ -0,0 +1,79 @@
+use sea_orm_migration::prelude::*;
+
+// 创建analysis_runs表，记录每次分析的报告和运行状态
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AnalysisRuns::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AnalysisRuns::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AnalysisRuns::RepositoryId).text().not_null())
+                    .col(ColumnDef::new(AnalysisRuns::RunAt).timestamp().not_null())
+                    .col(ColumnDef::new(AnalysisRuns::ReportJson).json().not_null())
+                    .col(
+                        ColumnDef::new(AnalysisRuns::TotalContributors)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(AnalysisRuns::ChinaPercentage)
+                            .float()
+                            .not_null()
+                            .default(0.0),
+                    )
+                    .col(
+                        ColumnDef::new(AnalysisRuns::Status)
+                            .text()
+                            .not_null()
+                            .default("running"),
+                    )
+                    .col(ColumnDef::new(AnalysisRuns::ErrorMessage).text().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_analysis_runs_repository_id_run_at")
+                    .table(AnalysisRuns::Table)
+                    .col(AnalysisRuns::RepositoryId)
+                    .col(AnalysisRuns::RunAt)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AnalysisRuns::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum AnalysisRuns {
+    Table,
+    Id,
+    RepositoryId,
+    RunAt,
+    ReportJson,
+    TotalContributors,
+    ChinaPercentage,
+    Status,
+    ErrorMessage,
+}
@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+// 创建repository_topics表，记录仓库在GitHub上的主题标签
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RepositoryTopics::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RepositoryTopics::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RepositoryTopics::RepositoryId)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RepositoryTopics::Topic).text().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_repository_topics_repository_id_topic")
+                    .table(RepositoryTopics::Table)
+                    .col(RepositoryTopics::RepositoryId)
+                    .col(RepositoryTopics::Topic)
+                    .unique()
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        // 按主题筛选仓库时使用
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_repository_topics_topic")
+                    .table(RepositoryTopics::Table)
+                    .col(RepositoryTopics::Topic)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RepositoryTopics::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RepositoryTopics {
+    Table,
+    Id,
+    RepositoryId,
+    Topic,
+}
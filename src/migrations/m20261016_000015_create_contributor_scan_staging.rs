@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+// 创建contributor_scan_staging暂存表，分批写入提交扫描的部分统计结果，扫描结束后合并并清除。
+// 数据只在单次扫描期间有用，使用UNLOGGED表减少WAL写入
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+CREATE UNLOGGED TABLE IF NOT EXISTS contributor_scan_staging (
+    scan_id TEXT NOT NULL,
+    author_id BIGINT NOT NULL,
+    login TEXT NOT NULL,
+    avatar_url TEXT NOT NULL,
+    contributions INTEGER NOT NULL,
+    email TEXT,
+    PRIMARY KEY (scan_id, author_id)
+)
+"#,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(ContributorScanStaging::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ContributorScanStaging {
+    Table,
+}
@@ -0,0 +1,40 @@
+use sea_orm_migration::prelude::*;
+
+// 为repository_sync_status添加README中发现的位置线索（逗号分隔的城市/公司名称）
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RepositorySyncStatus::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(RepositorySyncStatus::ReadmeLocationHints)
+                            .text()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RepositorySyncStatus::Table)
+                    .drop_column(RepositorySyncStatus::ReadmeLocationHints)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RepositorySyncStatus {
+    Table,
+    ReadmeLocationHints,
+}
@@ -0,0 +1,56 @@
+use sea_orm_migration::prelude::*;
+
+// 为contributor_locations添加最近一次判定的时间：analyzed_at记录提交统计的计算时间，
+// 基于已存储数据重新判定时只更新classified_at，便于区分过期的提交数据和最近的重新判定
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 表由外部系统创建，不存在时跳过
+        if !manager.has_table("contributor_locations").await? {
+            return Ok(());
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContributorLocations::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(ContributorLocations::ClassifiedAt)
+                            .timestamp()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 已有记录的判定与分析同时完成
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE contributor_locations SET classified_at = analyzed_at WHERE classified_at IS NULL",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ContributorLocations::Table)
+                    .drop_column(ContributorLocations::ClassifiedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ContributorLocations {
+    Table,
+    ClassifiedAt,
+}
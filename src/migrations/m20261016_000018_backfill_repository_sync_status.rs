@@ -0,0 +1,33 @@
+use sea_orm_migration::prelude::*;
+
+// 用contributor_locations中每个仓库最近的分析时间补齐repository_sync_status，
+// 升级前分析过的仓库不会因为缺少同步记录而被当作从未分析
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 表由外部系统创建，不存在时跳过
+        if !manager.has_table("contributor_locations").await? {
+            return Ok(());
+        }
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "INSERT INTO repository_sync_status (repository_id, last_analyzed_at)
+                SELECT repository_id, MAX(analyzed_at) FROM contributor_locations
+                GROUP BY repository_id
+                ON CONFLICT DO NOTHING",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // 补齐的记录与正常分析写入的记录无法区分，回滚时保持不变
+        Ok(())
+    }
+}
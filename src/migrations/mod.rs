@@ -1,17 +1,65 @@
 use sea_orm_migration::prelude::*;
 use sea_orm_migration::sea_orm::DbConn;
 
+mod m20261016_000001_add_uncertain_to_contributor_locations;
+mod m20261016_000002_add_china_probability_to_contributor_locations;
+mod m20261016_000003_add_commit_hours_to_contributor_locations;
+mod m20261016_000004_create_commit_files;
+mod m20261016_000005_create_repository_sync_status;
+mod m20261016_000006_create_repository_traffic;
+mod m20261016_000007_add_login_anonymization;
+mod m20261016_000008_add_region_code_to_contributor_locations;
+mod m20261016_000009_add_data_source_to_contributor_locations;
+mod m20261016_000010_converge_contributor_tables;
+mod m20261016_000011_create_analysis_runs;
+mod m20261016_000012_add_timezone_stats_to_contributor_locations;
+mod m20261016_000013_create_repository_topics;
+mod m20261016_000014_add_signals_to_contributor_locations;
+mod m20261016_000015_create_contributor_scan_staging;
+mod m20261016_000016_add_readme_location_hints_to_repository_sync_status;
+mod m20261016_000017_add_classified_at_to_contributor_locations;
+mod m20261016_000018_backfill_repository_sync_status;
+
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![]
+        vec![
+            Box::new(m20261016_000001_add_uncertain_to_contributor_locations::Migration),
+            Box::new(m20261016_000002_add_china_probability_to_contributor_locations::Migration),
+            Box::new(m20261016_000003_add_commit_hours_to_contributor_locations::Migration),
+            Box::new(m20261016_000004_create_commit_files::Migration),
+            Box::new(m20261016_000005_create_repository_sync_status::Migration),
+            Box::new(m20261016_000006_create_repository_traffic::Migration),
+            Box::new(m20261016_000007_add_login_anonymization::Migration),
+            Box::new(m20261016_000008_add_region_code_to_contributor_locations::Migration),
+            Box::new(m20261016_000009_add_data_source_to_contributor_locations::Migration),
+            Box::new(m20261016_000010_converge_contributor_tables::Migration),
+            Box::new(m20261016_000011_create_analysis_runs::Migration),
+            Box::new(m20261016_000012_add_timezone_stats_to_contributor_locations::Migration),
+            Box::new(m20261016_000013_create_repository_topics::Migration),
+            Box::new(m20261016_000014_add_signals_to_contributor_locations::Migration),
+            Box::new(m20261016_000015_create_contributor_scan_staging::Migration),
+            Box::new(
+                m20261016_000016_add_readme_location_hints_to_repository_sync_status::Migration,
+            ),
+            Box::new(m20261016_000017_add_classified_at_to_contributor_locations::Migration),
+            Box::new(m20261016_000018_backfill_repository_sync_status::Migration),
+        ]
     }
 }
 
-pub async fn setup_database(db: &DbConn) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn setup_database(db: &DbConn, schema: &str) -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("正在设置数据库表结构...");
+
+    // 连接的search_path指向该schema，迁移创建的表和迁移记录表都会放在其中，需要先确保schema存在
+    db.execute_unprepared(&format!(
+        "CREATE SCHEMA IF NOT EXISTS \"{}\"",
+        schema.replace('"', "\"\"")
+    ))
+    .await?;
+
     Migrator::up(db, None).await?;
     tracing::info!("数据库表设置完成");
     Ok(())
@@ -0,0 +1,257 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+// 缓存文件相对于HOME目录的路径
+const ETAG_CACHE_PATH: &str = ".cache/crates-pro-sync/etags.json";
+
+// 缓存的响应体总大小上限（字节），超过时淘汰最久未使用的条目
+const ETAG_CACHE_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+// 全局ETag缓存实例，首次使用时从磁盘加载
+pub static ETAG_CACHE: Lazy<Mutex<ETagCache>> = Lazy::new(|| {
+    let path = env::var("HOME")
+        .ok()
+        .map(|home| Path::new(&home).join(ETAG_CACHE_PATH));
+    Mutex::new(ETagCache::load(path))
+});
+
+// 磁盘上的缓存条目，响应体均为UTF-8的JSON，按字符串保存
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedEntry {
+    etag: String,
+    body: String,
+    // 最近一次使用的序号，用于重新加载后继续按最久未使用淘汰；旧版本的缓存文件没有该字段
+    #[serde(default)]
+    last_used: u64,
+}
+
+// 内存中的缓存条目
+#[derive(Debug)]
+struct CacheEntry {
+    etag: String,
+    body: Vec<u8>,
+    last_used: u64,
+}
+
+/// GitHub API响应的ETag缓存（URL -> (ETag, 响应体)）
+///
+/// 再次请求同一URL时带上`If-None-Match`，数据未变化时GitHub返回304且不消耗速率限制额度，
+/// 此时直接使用缓存的响应体。响应体总大小超过`ETAG_CACHE_MAX_BYTES`时淘汰最久未使用的条目。
+#[derive(Debug, Default)]
+pub struct ETagCache {
+    entries: HashMap<String, CacheEntry>,
+    path: Option<PathBuf>,
+    dirty: bool,
+    total_bytes: usize,
+    // 单调递增的使用序号
+    clock: u64,
+}
+
+impl ETagCache {
+    /// 从磁盘加载缓存，文件不存在或无法解析时使用空缓存；`path`为None时不持久化
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_deref()
+            .filter(|path| path.exists())
+            .and_then(|path| {
+                let contents = fs::read_to_string(path)
+                    .map_err(|e| warn!("读取ETag缓存 {} 失败: {}", path.display(), e))
+                    .ok()?;
+                serde_json::from_str::<HashMap<String, PersistedEntry>>(&contents)
+                    .map_err(|e| warn!("解析ETag缓存 {} 失败，将重新建立: {}", path.display(), e))
+                    .ok()
+            })
+            .unwrap_or_default();
+
+        let mut cache = ETagCache {
+            path,
+            ..ETagCache::default()
+        };
+        for (url, entry) in entries {
+            cache.clock = cache.clock.max(entry.last_used);
+            cache.total_bytes += entry.body.len();
+            cache.entries.insert(
+                url,
+                CacheEntry {
+                    etag: entry.etag,
+                    body: entry.body.into_bytes(),
+                    last_used: entry.last_used,
+                },
+            );
+        }
+        cache.evict();
+
+        debug!("已加载 {} 条ETag缓存", cache.entries.len());
+        cache
+    }
+
+    /// 获取URL对应的ETag和缓存的响应体
+    pub fn get(&mut self, url: &str) -> Option<(String, Vec<u8>)> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(url)?;
+        entry.last_used = self.clock;
+        Some((entry.etag.clone(), entry.body.clone()))
+    }
+
+    /// 更新URL对应的ETag和响应体
+    pub fn insert(&mut self, url: &str, etag: String, body: Vec<u8>) {
+        self.clock += 1;
+        self.total_bytes += body.len();
+        let entry = CacheEntry {
+            etag,
+            body,
+            last_used: self.clock,
+        };
+        if let Some(old) = self.entries.insert(url.to_string(), entry) {
+            self.total_bytes -= old.body.len();
+        }
+        self.dirty = true;
+        self.evict();
+    }
+
+    // 淘汰最久未使用的条目，直到响应体总大小不超过上限
+    fn evict(&mut self) {
+        if self.total_bytes <= ETAG_CACHE_MAX_BYTES {
+            return;
+        }
+
+        let mut by_age: Vec<(u64, String)> = self
+            .entries
+            .iter()
+            .map(|(url, entry)| (entry.last_used, url.clone()))
+            .collect();
+        by_age.sort_unstable();
+
+        let mut evicted = 0;
+        for (_, url) in by_age {
+            if self.total_bytes <= ETAG_CACHE_MAX_BYTES {
+                break;
+            }
+            if let Some(entry) = self.entries.remove(&url) {
+                self.total_bytes -= entry.body.len();
+                evicted += 1;
+            }
+        }
+        self.dirty = true;
+        debug!("ETag缓存超过大小上限，淘汰 {} 条最久未使用的条目", evicted);
+    }
+
+    // 有更新时取出待写入磁盘的路径和条目，并清除更新标记
+    fn take_snapshot(&mut self) -> Option<(PathBuf, HashMap<String, PersistedEntry>)> {
+        let path = self.path.clone()?;
+        if !self.dirty {
+            return None;
+        }
+
+        let persisted = self
+            .entries
+            .iter()
+            .map(|(url, entry)| {
+                (
+                    url.clone(),
+                    PersistedEntry {
+                        etag: entry.etag.clone(),
+                        body: String::from_utf8_lossy(&entry.body).into_owned(),
+                        last_used: entry.last_used,
+                    },
+                )
+            })
+            .collect();
+        self.dirty = false;
+        Some((path, persisted))
+    }
+}
+
+/// 有更新时把全局ETag缓存写回磁盘
+///
+/// 只在持锁期间复制条目，序列化和写文件在阻塞线程池中进行，不阻塞其他任务读写缓存。
+pub async fn save_etag_cache() {
+    let Some((path, persisted)) = ETAG_CACHE.lock().unwrap().take_snapshot() else {
+        return;
+    };
+
+    let count = persisted.len();
+    let display_path = path.display().to_string();
+    let result = tokio::task::spawn_blocking(move || write_atomically(&path, &persisted))
+        .await
+        .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+    match result {
+        Ok(()) => debug!("已保存 {} 条ETag缓存到 {}", count, display_path),
+        Err(e) => {
+            warn!("保存ETag缓存到 {} 失败: {}", display_path, e);
+            ETAG_CACHE.lock().unwrap().dirty = true;
+        }
+    }
+}
+
+// 先写入同目录下的临时文件再重命名，进程中途退出时不会留下写了一半的缓存文件
+fn write_atomically(
+    path: &Path,
+    persisted: &HashMap<String, PersistedEntry>,
+) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string(persisted)?;
+    let tmp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    fs::write(&tmp_path, json)?;
+    fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used_entries_over_size_limit() {
+        let mut cache = ETagCache::default();
+        let half = vec![b'x'; ETAG_CACHE_MAX_BYTES / 2];
+        cache.insert("a", "etag-a".to_string(), half.clone());
+        cache.insert("b", "etag-b".to_string(), half.clone());
+        // 使用a后b成为最久未使用的条目
+        assert!(cache.get("a").is_some());
+        cache.insert("c", "etag-c".to_string(), half);
+
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+        assert!(cache.total_bytes <= ETAG_CACHE_MAX_BYTES);
+    }
+
+    #[test]
+    fn replacing_an_entry_updates_total_size() {
+        let mut cache = ETagCache::default();
+        cache.insert("a", "1".to_string(), vec![0; 10]);
+        cache.insert("a", "2".to_string(), vec![0; 4]);
+
+        assert_eq!(cache.total_bytes, 4);
+        assert_eq!(cache.get("a").unwrap().0, "2");
+    }
+
+    #[test]
+    fn snapshot_round_trips_through_disk() {
+        let dir = env::temp_dir().join(format!("etag-cache-test-{}", std::process::id()));
+        let path = dir.join("etags.json");
+
+        let mut cache = ETagCache::load(Some(path.clone()));
+        cache.insert("url", "etag".to_string(), b"{\"k\":1}".to_vec());
+        let (snapshot_path, persisted) = cache.take_snapshot().unwrap();
+        write_atomically(&snapshot_path, &persisted).unwrap();
+        assert!(cache.take_snapshot().is_none());
+
+        let mut reloaded = ETagCache::load(Some(path));
+        assert_eq!(
+            reloaded.get("url"),
+            Some(("etag".to_string(), b"{\"k\":1}".to_vec()))
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
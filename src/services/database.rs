@@ -1,47 +1,472 @@
+use sea_orm::sea_query::{Expr, OnConflict};
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
-    QueryFilter, Set, Statement,
+    QueryFilter, QueryOrder, QueryResult, Set, Statement, TransactionTrait,
 };
-use tracing::{info, warn};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tracing::{debug, info, warn};
 
-use crate::entities::{contributor_location, github_user, program, repository_contributor};
-use crate::services::github_api::GitHubUser;
+use crate::config::request_delay;
+use crate::entities::{
+    contributor_location, github_user, login_anonymization_map, program, repository_contributor,
+    repository_sync_status, repository_topic, repository_traffic,
+};
+use crate::services::github_api::{
+    parse_github_repo_url, parse_github_repo_url_strict, Contributor, GitHubApiClient, GitHubUser,
+    TrafficStats,
+};
 
 // 贡献者详情返回结果
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct ContributorDetail {
-    pub id: i64,
+    /// GitHub数字ID，匿名化后为空
+    pub id: Option<i64>,
+    pub user_id: i32,
     pub login: String,
     pub name: Option<String>,
     pub contributions: i32,
     pub location: Option<String>,
+    pub china_probability: Option<f64>,
+}
+
+impl ContributorDetail {
+    // 用匿名登录名替换登录名，并清除显示名、GitHub数字ID和位置等可识别身份的字段，用于公开分享的输出
+    pub fn anonymize(&mut self) {
+        self.login = anonymized_login(&self.login);
+        self.name = None;
+        self.id = None;
+        self.location = None;
+    }
+}
+
+// 登录名的SHA256十六进制摘要
+fn login_hash(login: &str) -> String {
+    Sha256::digest(login.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// 匿名登录名：contributor_加登录名SHA256的前8位，与github_users.anonymized_login一致
+pub fn anonymized_login(login: &str) -> String {
+    format!("contributor_{}", &login_hash(login)[..8])
+}
+
+// 贡献者查询的排序字段
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ContributorSortField {
+    /// 贡献数
+    #[default]
+    Contributions,
+    /// 来自中国的概率
+    ChinaProbability,
+    /// 判定置信度（来自中国的概率与0.5的距离）
+    ConfidenceScore,
+    /// 登录名（不区分大小写）
+    Login,
+    /// 关注者数
+    Followers,
+}
+
+impl ContributorSortField {
+    // 未指定排序方向时使用的方向：登录名升序，其余降序
+    fn default_direction(self) -> SortDirection {
+        match self {
+            ContributorSortField::Login => SortDirection::Asc,
+            _ => SortDirection::Desc,
+        }
+    }
+}
+
+// 排序方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    fn as_sql(self) -> &'static str {
+        match self {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        }
+    }
+}
+
+// 贡献者查询的排序方式，命令行格式为`字段[:方向]`（如 login:asc），字段和方向只能取枚举中的值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContributorSort {
+    pub field: ContributorSortField,
+    pub direction: SortDirection,
+}
+
+impl Default for ContributorSort {
+    fn default() -> Self {
+        ContributorSortField::default().into()
+    }
+}
+
+impl From<ContributorSortField> for ContributorSort {
+    fn from(field: ContributorSortField) -> Self {
+        ContributorSort {
+            field,
+            direction: field.default_direction(),
+        }
+    }
+}
+
+impl std::str::FromStr for ContributorSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use clap::ValueEnum;
+
+        let (field, direction) = match s.split_once(':') {
+            Some((field, direction)) => (field, Some(direction)),
+            None => (s, None),
+        };
+        // 同时接受china_probability和china-probability两种写法
+        let field = ContributorSortField::from_str(&field.trim().replace('_', "-"), true).map_err(|_| {
+            format!(
+                "无效的排序字段 {:?}，可选: contributions, china_probability, confidence_score, login, followers",
+                field
+            )
+        })?;
+        let direction = match direction {
+            Some(direction) => SortDirection::from_str(direction.trim(), true)
+                .map_err(|_| format!("无效的排序方向 {:?}，可选: asc, desc", direction))?,
+            None => field.default_direction(),
+        };
+
+        Ok(ContributorSort { field, direction })
+    }
+}
+
+// 按判定结果筛选贡献者
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClassificationFilter {
+    /// 显示全部贡献者
+    #[default]
+    All,
+    /// 只显示判定来自中国的贡献者
+    China,
+    /// 只显示判定不来自中国的贡献者
+    NonChina,
+    /// 只显示尚未进行位置分析的贡献者
+    Unanalyzed,
+}
+
+// 贡献者查询的过滤条件
+#[derive(Debug, Clone, Default)]
+pub struct ContributorFilter {
+    pub min_china_probability: Option<f64>,
+    pub max_china_probability: Option<f64>,
+    pub classification: ClassificationFilter,
+    /// 只显示判定属于该国家/地区（ISO-3166代码）的贡献者
+    pub region_code: Option<String>,
+    pub sort: ContributorSort,
 }
 
 // 中国贡献者统计结果
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct ChinaContributorStats {
     pub total_contributors: i64,
     pub china_contributors: i64,
     pub china_percentage: f64,
+    /// 中国贡献者的提交数占全部提交数的比例（百分比）
+    pub china_commits_percentage: f64,
     pub china_contributors_details: Vec<ContributorDetail>,
 }
 
+// 仓库查找结果；other_candidates非空表示有多条记录同时匹配（MultipleMatches）
+#[derive(Debug, Clone)]
+pub struct RepositoryMatch {
+    pub id: String,
+    pub other_candidates: Vec<String>,
+}
+
+impl RepositoryMatch {
+    pub fn has_multiple_matches(&self) -> bool {
+        !self.other_candidates.is_empty()
+    }
+}
+
+// 按GitHub仓库（小写的owner/repo）索引的已有仓库，导入时只加载一次并随每批导入更新
+pub type ProgramIndex = HashMap<(String, String), program::Model>;
+
+// 待导入的仓库记录
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ProgramRow {
+    pub name: String,
+    pub github_url: String,
+}
+
+// 仓库导入结果
+#[derive(Debug, Clone, Default)]
+pub struct UpsertSummary {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+// 合并用户的结果统计
+#[derive(Debug, Clone, Default)]
+pub struct MergeResult {
+    /// 改为指向保留用户的贡献者关系数
+    pub contributors_updated: u64,
+    /// 两个用户在同一仓库都有贡献时，合并贡献数后删除的关系数
+    pub contributors_merged: u64,
+    /// 改为指向保留用户的位置记录数
+    pub locations_updated: u64,
+    /// 保留用户已有位置记录时删除的重复记录数
+    pub locations_deleted: u64,
+    /// 改为指向保留用户的提交文件记录数
+    pub commit_files_updated: u64,
+    /// 删除的用户数
+    pub users_deleted: u64,
+}
+
+// 回填github_id的结果统计
+#[derive(Debug, Clone, Default)]
+pub struct BackfillResult {
+    /// github_id无效的用户数
+    pub checked: usize,
+    /// 写入了真实github_id的用户数
+    pub updated: usize,
+    /// 真实github_id已属于另一条记录，合并到该记录的用户数
+    pub merged: usize,
+    /// GitHub上已不存在的用户数
+    pub not_found: usize,
+    /// 获取资料失败、留待下次回填的用户数
+    pub failed: usize,
+}
+
+// 批量分析的成本估算
+#[derive(Debug, Clone, Default)]
+pub struct BatchCostEstimate {
+    /// 参与估算的仓库数
+    pub repositories: usize,
+    /// 分析过的仓库数，按已存储的贡献者数和提交数估算请求数
+    pub previously_analyzed: usize,
+    /// 从未分析过的仓库数，按GitHub星标数估算新贡献者数
+    pub never_analyzed: usize,
+    /// 未在数据库中登记的仓库数，分析时会被跳过
+    pub unregistered: usize,
+    /// 从未分析过的仓库中估算的新贡献者数
+    pub estimated_new_contributors: u64,
+    /// 估算的GitHub API请求数
+    pub estimated_api_calls: u64,
+}
+
+// 每个仓库除提交扫描和用户资料外的固定请求数：仓库大小、访问量（浏览和克隆）、主题、README
+const FIXED_CALLS_PER_REPOSITORY: u64 = 5;
+
+// 按星标数估算从未分析过的仓库的规模：大约每10个星标对应一名贡献者，每名贡献者20次提交
+const STARS_PER_CONTRIBUTOR: u64 = 10;
+const COMMITS_PER_CONTRIBUTOR: u64 = 20;
+const MAX_ESTIMATED_CONTRIBUTORS: u64 = 10_000;
+
+// 无法获取星标数时，假设每个仓库的贡献者数
+const DEFAULT_ESTIMATED_CONTRIBUTORS: u64 = 30;
+
+// Commits API每页的提交数和最多扫描的页数
+const COMMITS_PER_PAGE: u64 = 100;
+const MAX_COMMIT_PAGES: u64 = 100;
+
+// 按星标数估算仓库的贡献者数，至少为1
+fn estimated_contributors_from_stars(stars: u64) -> u64 {
+    (stars / STARS_PER_CONTRIBUTOR).clamp(1, MAX_ESTIMATED_CONTRIBUTORS)
+}
+
+// 重置仓库分析数据的结果统计
+#[derive(Debug, Clone, Default)]
+pub struct ResetResult {
+    /// 删除的位置记录数
+    pub locations_deleted: u64,
+    /// 贡献数被清零的贡献者关系数
+    pub contributors_reset: u64,
+    /// 标记为invalidated的分析记录数
+    pub runs_invalidated: u64,
+}
+
+// 按中国贡献者比例排列的仓库
+#[derive(Debug, Clone)]
+pub struct RepoRanking {
+    pub program_id: String,
+    pub name: String,
+    pub github_url: Option<String>,
+    pub total_contributors: i64,
+    pub china_contributors: i64,
+    pub china_percentage: f64,
+}
+
+// 跨仓库贡献者排行榜中的一项
+#[derive(Debug, Clone)]
+pub struct LeaderboardEntry {
+    pub login: String,
+    pub name: Option<String>,
+    pub total_contributions: i64,
+    pub repo_count: i64,
+}
+
+impl LeaderboardEntry {
+    // 用匿名登录名替换登录名和显示名，用于公开分享的输出
+    pub fn anonymize(&mut self) {
+        self.login = anonymized_login(&self.login);
+        self.name = None;
+    }
+}
+
+// 贡献者参与的仓库及其在该仓库中的判定结果
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ContributorRepoEntry {
+    pub program_id: String,
+    pub name: String,
+    pub github_url: Option<String>,
+    pub contributions: i32,
+    pub is_from_china: Option<bool>,
+    pub china_probability: Option<f64>,
+}
+
+// 按语言统计的中国贡献者比例
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LanguageStat {
+    pub language: String,
+    pub total_contributors: i64,
+    pub china_contributors: i64,
+    pub china_percentage: f64,
+}
+
+// 从查询结果行解析贡献者详情
+fn contributor_detail_from_row(row: &QueryResult) -> Result<ContributorDetail, DbErr> {
+    Ok(ContributorDetail {
+        id: Some(row.try_get("", "github_id")?),
+        user_id: row.try_get("", "user_id")?,
+        login: row.try_get("", "login")?,
+        name: row.try_get("", "name")?,
+        contributions: row.try_get("", "contributions")?,
+        location: row.try_get("", "location")?,
+        china_probability: row.try_get("", "china_probability")?,
+    })
+}
+
 // 数据库服务
 #[derive(Clone)]
 pub struct DbService {
     conn: DatabaseConnection,
+    // 只读副本连接，查询类方法优先使用，未配置时使用主库
+    read_conn: Option<DatabaseConnection>,
+    schema: String,
+    max_contributions: i32,
+    // 贡献数超过上限时返回错误而不是截断
+    reject_excess_contributions: bool,
+    readonly: bool,
+    // 按旧规则查找仓库：github_url包含owner/repo即可，找不到时再按名称匹配
+    loose_url_match: bool,
+    // 在info级别输出手写SQL
+    show_sql: bool,
 }
 
+// 默认的单个贡献者贡献数上限，超过时视为API数据异常
+pub const DEFAULT_MAX_CONTRIBUTIONS: i32 = 100_000;
+
 impl DbService {
     // 创建数据库服务实例
-    pub fn new(conn: DatabaseConnection) -> Self {
-        Self { conn }
+    pub fn new(conn: DatabaseConnection, schema: String) -> Self {
+        Self {
+            conn,
+            read_conn: None,
+            schema,
+            max_contributions: DEFAULT_MAX_CONTRIBUTIONS,
+            reject_excess_contributions: false,
+            readonly: false,
+            loose_url_match: false,
+            show_sql: false,
+        }
+    }
+
+    // 设置只读模式，只读模式下所有写入方法直接返回错误，不执行SQL
+    pub fn with_readonly(mut self, readonly: bool) -> Self {
+        self.readonly = readonly;
+        self
+    }
+
+    // 设置是否按旧规则查找仓库，用于github_url不规范的历史数据
+    pub fn with_loose_url_match(mut self, loose_url_match: bool) -> Self {
+        self.loose_url_match = loose_url_match;
+        self
+    }
+
+    // 设置是否在info级别输出手写SQL（--dry-run-show-sql）
+    pub fn with_show_sql(mut self, show_sql: bool) -> Self {
+        self.show_sql = show_sql;
+        self
+    }
+
+    // 设置只读副本连接，查询统计、排名等只读方法会使用该连接，写入仍然使用主库
+    pub fn with_read_replica(mut self, read_conn: DatabaseConnection) -> Self {
+        self.read_conn = Some(read_conn);
+        self
+    }
+
+    // 返回不使用只读副本的DbService，用于读取刚写入、副本可能尚未同步的数据
+    pub fn without_read_replica(&self) -> Self {
+        Self {
+            read_conn: None,
+            ..self.clone()
+        }
+    }
+
+    // 查询使用的连接：配置了只读副本时使用副本，否则使用主库
+    fn reader(&self) -> &DatabaseConnection {
+        self.read_conn.as_ref().unwrap_or(&self.conn)
+    }
+
+    // 构建手写SQL语句，并在debug级别输出SQL和绑定的参数（--dump-sql时可见），便于排查占位符和类型问题；
+    // --dry-run-show-sql时改为info级别，在执行前输出
+    fn statement<I>(&self, sql: &str, values: I) -> Statement
+    where
+        I: IntoIterator<Item = sea_orm::Value>,
+    {
+        let values: Vec<sea_orm::Value> = values.into_iter().collect();
+        let sql_line = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+        if self.show_sql {
+            info!("SQL: {} 参数: {:?}", sql_line, values);
+        } else {
+            debug!("SQL: {} 参数: {:?}", sql_line, values);
+        }
+        Statement::from_sql_and_values(self.conn.get_database_backend(), sql, values)
+    }
+
+    // 只读模式下拒绝写入
+    fn ensure_writable(&self) -> Result<(), DbErr> {
+        if self.readonly {
+            return Err(DbErr::Custom("只读模式下不允许写入数据库".to_string()));
+        }
+        Ok(())
+    }
+
+    // 设置贡献数上限；reject为true时超出上限返回错误，否则截断到上限
+    pub fn with_contribution_limit(mut self, max_contributions: i32, reject: bool) -> Self {
+        self.max_contributions = max_contributions;
+        self.reject_excess_contributions = reject;
+        self
+    }
+
+    // 返回带schema前缀的表名，用于手写SQL
+    fn qualified(&self, table: &str) -> String {
+        format!("\"{}\".{}", self.schema.replace('"', "\"\""), table)
     }
 
     // 存储GitHub用户
     pub async fn store_user(&self, user: &GitHubUser) -> Result<i32, DbErr> {
+        self.ensure_writable()?;
         info!("存储GitHub用户: {}", user.login);
 
+        self.store_login_anonymization(&user.login).await?;
+
         // 查询用户是否已存在
         let existing_user = github_user::Entity::find()
             .filter(github_user::Column::GithubId.eq(user.id))
@@ -59,57 +484,842 @@ impl DbService {
         let user_model = github_user::ActiveModel::from(user.clone());
         let res = user_model.insert(&self.conn).await?;
 
-        Ok(res.id)
+        Ok(res.id)
+    }
+
+    // 记录登录名哈希到匿名登录名的映射，便于匿名化输出后的内部核对
+    async fn store_login_anonymization(&self, login: &str) -> Result<(), DbErr> {
+        let mapping = login_anonymization_map::ActiveModel {
+            original_hash: Set(login_hash(login)),
+            anonymized_login: Set(anonymized_login(login)),
+        };
+
+        login_anonymization_map::Entity::insert(mapping)
+            .on_conflict(
+                OnConflict::column(login_anonymization_map::Column::OriginalHash)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .do_nothing()
+            .exec(&self.conn)
+            .await?;
+
+        Ok(())
+    }
+
+    // 根据用户名查找用户ID
+    pub async fn get_user_id_by_name(&self, login: &str) -> Result<Option<i32>, DbErr> {
+        info!("通过登录名查找用户ID: {}", login);
+
+        let user = github_user::Entity::find()
+            .filter(github_user::Column::Login.eq(login))
+            .one(&self.conn)
+            .await?;
+
+        Ok(user.map(|u| u.id))
+    }
+
+    // 根据GitHub数字ID查找贡献者详情，贡献数为该用户在所有仓库中的总和
+    pub async fn get_contributor_detail_by_github_id(
+        &self,
+        github_id: i64,
+    ) -> Result<Option<ContributorDetail>, DbErr> {
+        info!("通过GitHub ID查找贡献者: {}", github_id);
+
+        let Some(user) = github_user::Entity::find()
+            .filter(github_user::Column::GithubId.eq(github_id))
+            .one(&self.conn)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let contributions = repository_contributor::Entity::find()
+            .filter(repository_contributor::Column::UserId.eq(user.id))
+            .all(&self.conn)
+            .await?
+            .iter()
+            .map(|rc| rc.contributions)
+            .sum();
+
+        Ok(Some(ContributorDetail {
+            id: Some(user.github_id),
+            user_id: user.id,
+            login: user.login,
+            name: user.name,
+            contributions,
+            location: user.location,
+            china_probability: None,
+        }))
+    }
+
+    // 合并同一个人的两条用户记录（如登录名变更后重复创建），把drop_id的数据转移到keep_id并删除drop_id。
+    // 在事务中执行，dry_run时只统计影响的行数并回滚
+    pub async fn merge_users(
+        &self,
+        keep_id: i32,
+        drop_id: i32,
+        dry_run: bool,
+    ) -> Result<MergeResult, DbErr> {
+        self.ensure_writable()?;
+        if keep_id == drop_id {
+            return Err(DbErr::Custom("不能将用户与自身合并".to_string()));
+        }
+
+        info!("合并用户: 保留 ID={}, 删除 ID={}", keep_id, drop_id);
+
+        let txn = self.conn.begin().await?;
+        let contributors = self.qualified("repository_contributors");
+        let locations = self.qualified("contributor_locations");
+
+        let exec = |sql: String| self.statement(&sql, [keep_id.into(), drop_id.into()]);
+
+        // 两个用户在同一仓库都有贡献时，把贡献数累加到保留用户上并删除重复关系
+        txn.execute(exec(format!(
+            "UPDATE {rc} keep SET contributions = keep.contributions + d.contributions
+             FROM {rc} d
+             WHERE keep.user_id = $1 AND d.user_id = $2 AND keep.repository_id = d.repository_id",
+            rc = contributors
+        )))
+        .await?;
+        let contributors_merged = txn
+            .execute(exec(format!(
+                "DELETE FROM {rc} WHERE user_id = $2
+                 AND repository_id IN (SELECT repository_id FROM {rc} WHERE user_id = $1)",
+                rc = contributors
+            )))
+            .await?
+            .rows_affected();
+        let contributors_updated = txn
+            .execute(exec(format!(
+                "UPDATE {} SET user_id = $1 WHERE user_id = $2",
+                contributors
+            )))
+            .await?
+            .rows_affected();
+
+        // 保留用户已有的位置记录优先
+        let locations_deleted = txn
+            .execute(exec(format!(
+                "DELETE FROM {cl} WHERE user_id = $2
+                 AND repository_id IN (SELECT repository_id FROM {cl} WHERE user_id = $1)",
+                cl = locations
+            )))
+            .await?
+            .rows_affected();
+        let locations_updated = txn
+            .execute(exec(format!(
+                "UPDATE {} SET user_id = $1 WHERE user_id = $2",
+                locations
+            )))
+            .await?
+            .rows_affected();
+
+        let commit_files_updated = txn
+            .execute(exec(format!(
+                "UPDATE {} SET user_id = $1 WHERE user_id = $2",
+                self.qualified("commit_files")
+            )))
+            .await?
+            .rows_affected();
+
+        let users_deleted = txn
+            .execute(self.statement(
+                &format!(
+                    "DELETE FROM {} WHERE id = $1",
+                    self.qualified("github_users")
+                ),
+                [drop_id.into()],
+            ))
+            .await?
+            .rows_affected();
+
+        if dry_run {
+            txn.rollback().await?;
+        } else {
+            txn.commit().await?;
+        }
+
+        Ok(MergeResult {
+            contributors_updated,
+            contributors_merged,
+            locations_updated,
+            locations_deleted,
+            commit_files_updated,
+            users_deleted,
+        })
+    }
+
+    // 估算批量分析的GitHub API请求数：分析过的仓库按已存储的贡献者数和提交数估算，
+    // 从未分析过的仓库通过GitHub获取星标数，按星标数估算新贡献者数和提交数
+    pub async fn estimate_batch_cost(
+        &self,
+        repos: &[(String, String)],
+        github_client: &GitHubApiClient,
+    ) -> Result<BatchCostEstimate, DbErr> {
+        let sql = format!(
+            "SELECT repository_id, COUNT(*) AS contributors, COALESCE(SUM(contributions), 0) AS commits
+             FROM {} GROUP BY repository_id",
+            self.qualified("repository_contributors")
+        );
+        let mut stored: HashMap<String, (u64, u64)> = HashMap::new();
+        for row in self.reader().query_all(self.statement(&sql, [])).await? {
+            let repository_id: String = row.try_get("", "repository_id")?;
+            let contributors: i64 = row.try_get("", "contributors")?;
+            let commits: i64 = row.try_get("", "commits")?;
+            stored.insert(repository_id, (contributors as u64, commits as u64));
+        }
+        let analyzed = self.get_last_analyzed_times().await?;
+
+        let mut estimate = BatchCostEstimate {
+            repositories: repos.len(),
+            ..Default::default()
+        };
+        for (owner, repo) in repos {
+            let Some(found) = self.get_repository_id(owner, repo).await? else {
+                estimate.unregistered += 1;
+                continue;
+            };
+
+            let (contributors, commits) = match stored.get(&found.id) {
+                Some(size) if analyzed.contains_key(&found.id) => {
+                    estimate.previously_analyzed += 1;
+                    *size
+                }
+                _ => {
+                    estimate.never_analyzed += 1;
+                    let contributors = match github_client.get_repo_metadata(owner, repo).await {
+                        Ok(metadata) => {
+                            estimated_contributors_from_stars(metadata.stargazers_count)
+                        }
+                        Err(e) => {
+                            warn!(
+                                "获取仓库 {}/{} 的星标数失败，按 {} 名贡献者估算: {}",
+                                owner, repo, DEFAULT_ESTIMATED_CONTRIBUTORS, e
+                            );
+                            DEFAULT_ESTIMATED_CONTRIBUTORS
+                        }
+                    };
+                    estimate.estimated_new_contributors += contributors;
+                    (contributors, contributors * COMMITS_PER_CONTRIBUTOR)
+                }
+            };
+            let pages = commits
+                .div_ceil(COMMITS_PER_PAGE)
+                .clamp(1, MAX_COMMIT_PAGES);
+            estimate.estimated_api_calls += contributors + pages + FIXED_CALLS_PER_REPOSITORY;
+        }
+
+        Ok(estimate)
+    }
+
+    // 回填github_id无效（不大于0，来自旧的导入或占位记录）的用户：按登录名重新获取GitHub资料并写入真实ID。
+    // 真实ID已属于另一条用户记录时违反唯一约束，此时把该用户合并到已有记录；dry_run时只统计不修改
+    pub async fn backfill_missing_github_ids(
+        &self,
+        github_client: &GitHubApiClient,
+        dry_run: bool,
+    ) -> Result<BackfillResult, DbErr> {
+        self.ensure_writable()?;
+        let users = github_user::Entity::find()
+            .filter(github_user::Column::GithubId.lte(0))
+            .order_by_asc(github_user::Column::Id)
+            .all(&self.conn)
+            .await?;
+        info!("找到 {} 个github_id无效的用户", users.len());
+
+        let mut result = BackfillResult {
+            checked: users.len(),
+            ..Default::default()
+        };
+        for user in users {
+            let details = match github_client.get_user_details(&user.login).await {
+                Ok(details) => details,
+                Err(e) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                    warn!("用户 {} 在GitHub上已不存在，无法回填", user.login);
+                    result.not_found += 1;
+                    continue;
+                }
+                Err(e) => {
+                    warn!("获取用户 {} 的资料失败，留待下次回填: {}", user.login, e);
+                    result.failed += 1;
+                    continue;
+                }
+            };
+
+            let owner = github_user::Entity::find()
+                .filter(github_user::Column::GithubId.eq(details.id))
+                .one(&self.conn)
+                .await?;
+            match owner {
+                Some(existing) => {
+                    info!(
+                        "用户 {} 的github_id {} 已属于用户 {} (ID={})，合并到该记录",
+                        user.login, details.id, existing.login, existing.id
+                    );
+                    self.merge_users(existing.id, user.id, dry_run).await?;
+                    result.merged += 1;
+                }
+                None => {
+                    info!("用户 {} 的github_id回填为 {}", user.login, details.id);
+                    if !dry_run {
+                        let mut model: github_user::ActiveModel = user.into();
+                        model.github_id = Set(details.id);
+                        model.updated_at_local = Set(chrono::Utc::now().naive_utc());
+                        model.update(&self.conn).await?;
+                    }
+                    result.updated += 1;
+                }
+            }
+
+            tokio::time::sleep(request_delay()).await;
+        }
+
+        Ok(result)
+    }
+
+    // 清除仓库的分析数据，以便在判定算法更新后从头重新分析。
+    // 在事务中删除位置记录、清零贡献数、清除分析时间并把分析记录标记为invalidated，
+    // dry_run时只统计影响的行数并回滚
+    pub async fn reset_analysis_for_repository(
+        &self,
+        repository_id: &str,
+        dry_run: bool,
+    ) -> Result<ResetResult, DbErr> {
+        self.ensure_writable()?;
+        info!("重置仓库 ID={} 的分析数据", repository_id);
+
+        let txn = self.conn.begin().await?;
+        let exec = |sql: String| self.statement(&sql, [repository_id.into()]);
+
+        let locations_deleted = txn
+            .execute(exec(format!(
+                "DELETE FROM {} WHERE repository_id = $1",
+                self.qualified("contributor_locations")
+            )))
+            .await?
+            .rows_affected();
+        let contributors_reset = txn
+            .execute(exec(format!(
+                "UPDATE {} SET contributions = 0, updated_at = NOW() WHERE repository_id = $1",
+                self.qualified("repository_contributors")
+            )))
+            .await?
+            .rows_affected();
+        // 清除分析时间后，analyze-all --skip-recent和stale会把仓库视为从未分析
+        txn.execute(exec(format!(
+            "DELETE FROM {} WHERE repository_id = $1",
+            self.qualified("repository_sync_status")
+        )))
+        .await?;
+        let runs_invalidated = txn
+            .execute(exec(format!(
+                "UPDATE {} SET status = 'invalidated' WHERE repository_id = $1 AND status <> 'invalidated'",
+                self.qualified("analysis_runs")
+            )))
+            .await?
+            .rows_affected();
+
+        if dry_run {
+            txn.rollback().await?;
+        } else {
+            txn.commit().await?;
+        }
+
+        Ok(ResetResult {
+            locations_deleted,
+            contributors_reset,
+            runs_invalidated,
+        })
+    }
+
+    // 根据仓库所有者和名称获取仓库ID
+    // 默认要求github_url的主机为github.com且路径恰好是owner/repo（不区分大小写）；
+    // 宽松模式下只要github_url包含owner/repo即可，找不到时再按名称匹配，
+    // 多个仓库同时匹配时，优先选择github_url精确匹配的仓库，其次选择ID最小的仓库
+    pub async fn get_repository_id(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> Result<Option<RepositoryMatch>, DbErr> {
+        info!("获取仓库ID: {}/{}", owner, repo);
+
+        // 直接查询github_url字段
+        let mut programs = program::Entity::find()
+            .filter(
+                program::Column::GithubUrl
+                    .contains(format!("{}/{}", owner, repo))
+                    .or(program::Column::GithubUrl.contains(format!("{}/{}.git", owner, repo))),
+            )
+            .all(&self.conn)
+            .await?;
+
+        // 严格模式下排除其他主机（如gitlab.com）或只是包含该子串的地址
+        if !self.loose_url_match {
+            let before = programs.len();
+            programs.retain(|p| {
+                p.github_url
+                    .as_deref()
+                    .and_then(parse_github_repo_url_strict)
+                    .is_some_and(|(o, r)| {
+                        o.eq_ignore_ascii_case(owner) && r.eq_ignore_ascii_case(repo)
+                    })
+            });
+            if programs.len() < before {
+                debug!(
+                    "严格匹配排除了 {} 条github_url不符的记录，可使用--loose-url-match按旧规则查找",
+                    before - programs.len()
+                );
+            }
+        }
+
+        // 如果没有找到，宽松模式下尝试直接通过名称匹配
+        if programs.is_empty() && self.loose_url_match {
+            programs = program::Entity::find()
+                .filter(program::Column::Name.eq(repo))
+                .all(&self.conn)
+                .await?;
+            if !programs.is_empty() {
+                info!("通过名称匹配仓库 {}", repo);
+            }
+        }
+
+        if programs.is_empty() {
+            warn!("未找到仓库 {}/{}", owner, repo);
+            return Ok(None);
+        }
+
+        let is_exact = |p: &program::Model| {
+            p.github_url
+                .as_deref()
+                .and_then(parse_github_repo_url)
+                .is_some_and(|(o, r)| o.eq_ignore_ascii_case(owner) && r.eq_ignore_ascii_case(repo))
+        };
+        programs.sort_by(|a, b| is_exact(b).cmp(&is_exact(a)).then_with(|| a.id.cmp(&b.id)));
+
+        let chosen = programs.remove(0);
+        if !programs.is_empty() {
+            warn!(
+                "仓库 {}/{} 匹配到 {} 条记录，选择 ID={} ({})",
+                owner,
+                repo,
+                programs.len() + 1,
+                chosen.id,
+                chosen.github_url.as_deref().unwrap_or_default()
+            );
+            for other in &programs {
+                warn!(
+                    "  未选择的候选: ID={} ({})",
+                    other.id,
+                    other.github_url.as_deref().unwrap_or_default()
+                );
+            }
+        }
+
+        info!("找到仓库 {}/{}, ID: {}", owner, repo, chosen.id);
+        Ok(Some(RepositoryMatch {
+            id: chosen.id,
+            other_candidates: programs.into_iter().map(|p| p.id).collect(),
+        }))
+    }
+
+    // 加载已有仓库的索引，供分批导入时共用
+    pub async fn load_program_index(&self) -> Result<ProgramIndex, DbErr> {
+        let mut existing = ProgramIndex::new();
+        for program in self.list_programs_with_github_url().await? {
+            if let Some((owner, repo)) = program
+                .github_url
+                .as_deref()
+                .and_then(parse_github_repo_url)
+            {
+                existing
+                    .entry((owner.to_lowercase(), repo.to_lowercase()))
+                    .or_insert(program);
+            }
+        }
+        Ok(existing)
+    }
+
+    // 批量导入仓库，按GitHub仓库（owner/repo，不区分大小写）去重：已存在时更新名称，否则新建记录。
+    // 每批在一个事务中写入，成功后把新建和更新的记录合并到`existing`中
+    pub async fn upsert_programs(
+        &self,
+        rows: &[ProgramRow],
+        existing: &mut ProgramIndex,
+    ) -> Result<UpsertSummary, DbErr> {
+        self.ensure_writable()?;
+        let mut summary = UpsertSummary::default();
+        let txn = self.conn.begin().await?;
+        // 本批次中更新了名称的已有仓库
+        let mut updated_programs: ProgramIndex = HashMap::new();
+
+        // 本批次中新建的仓库，同一仓库出现多次时以最后一行的名称为准
+        let mut new_programs: Vec<program::Model> = Vec::new();
+        let mut pending: HashMap<(String, String), usize> = HashMap::new();
+        for row in rows {
+            let Some((owner, repo)) = parse_github_repo_url(&row.github_url) else {
+                continue;
+            };
+            let key = (owner.to_lowercase(), repo.to_lowercase());
+
+            if let Some(&index) = pending.get(&key) {
+                new_programs[index].name = row.name.clone();
+                continue;
+            }
+
+            let current = updated_programs.get(&key).or_else(|| existing.get(&key));
+            match current {
+                Some(program) if program.name == row.name => summary.unchanged += 1,
+                Some(program) => {
+                    let mut model: program::ActiveModel = program.clone().into();
+                    model.name = Set(row.name.clone());
+                    let updated = model.update(&txn).await?;
+                    updated_programs.insert(key, updated);
+                    summary.updated += 1;
+                }
+                None => {
+                    pending.insert(key, new_programs.len());
+                    new_programs.push(program::Model {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        name: row.name.clone(),
+                        github_url: Some(format!("https://github.com/{}/{}", owner, repo)),
+                    });
+                }
+            }
+        }
+
+        summary.inserted = new_programs.len();
+        if !new_programs.is_empty() {
+            program::Entity::insert_many(
+                new_programs.iter().cloned().map(program::ActiveModel::from),
+            )
+            .exec(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+
+        // 事务提交后再更新索引，失败的批次不会留下未写入的记录
+        existing.extend(updated_programs);
+        for (key, index) in pending {
+            existing.insert(key, new_programs[index].clone());
+        }
+
+        Ok(summary)
+    }
+
+    // 获取所有配置了GitHub地址的仓库
+    pub async fn list_programs_with_github_url(&self) -> Result<Vec<program::Model>, DbErr> {
+        info!("查询所有配置了GitHub地址的仓库");
+
+        let programs = program::Entity::find()
+            .filter(program::Column::GithubUrl.is_not_null())
+            .filter(program::Column::GithubUrl.ne(""))
+            .all(&self.conn)
+            .await?;
+
+        info!("找到 {} 个仓库", programs.len());
+        Ok(programs)
+    }
+
+    // 获取每个仓库最近一次完成分析的时间
+    pub async fn get_last_analyzed_times(
+        &self,
+    ) -> Result<HashMap<String, chrono::NaiveDateTime>, DbErr> {
+        let statuses = repository_sync_status::Entity::find()
+            .all(&self.conn)
+            .await?;
+
+        Ok(statuses
+            .into_iter()
+            .map(|s| (s.repository_id, s.last_analyzed_at))
+            .collect())
+    }
+
+    // 获取各仓库上次分析时从README中发现的位置线索（仓库ID -> 城市/公司名称），没有线索的仓库不包含在内
+    pub async fn get_readme_location_hints(&self) -> Result<HashMap<String, Vec<String>>, DbErr> {
+        let statuses = repository_sync_status::Entity::find()
+            .filter(repository_sync_status::Column::ReadmeLocationHints.is_not_null())
+            .all(self.reader())
+            .await?;
+
+        Ok(statuses
+            .into_iter()
+            .filter_map(|s| {
+                let hints: Vec<String> = s
+                    .readme_location_hints?
+                    .split(',')
+                    .filter(|hint| !hint.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                (!hints.is_empty()).then_some((s.repository_id, hints))
+            })
+            .collect())
+    }
+
+    // 记录仓库完成分析的时间，以及本次分析从README中发现的位置线索
+    pub async fn mark_repository_analyzed(
+        &self,
+        repository_id: &str,
+        readme_location_hints: &[String],
+    ) -> Result<(), DbErr> {
+        self.ensure_writable()?;
+        let status = repository_sync_status::ActiveModel {
+            repository_id: Set(repository_id.to_string()),
+            last_analyzed_at: Set(chrono::Utc::now().naive_utc()),
+            readme_location_hints: Set(
+                (!readme_location_hints.is_empty()).then(|| readme_location_hints.join(","))
+            ),
+        };
+
+        repository_sync_status::Entity::insert(status)
+            .on_conflict(
+                OnConflict::column(repository_sync_status::Column::RepositoryId)
+                    .update_columns([
+                        repository_sync_status::Column::LastAnalyzedAt,
+                        repository_sync_status::Column::ReadmeLocationHints,
+                    ])
+                    .to_owned(),
+            )
+            .exec(&self.conn)
+            .await?;
+
+        info!("已更新仓库 ID={} 的分析时间", repository_id);
+        Ok(())
+    }
+
+    // 存储仓库的访问与克隆统计
+    pub async fn store_repository_traffic(
+        &self,
+        repository_id: &str,
+        traffic: &TrafficStats,
+    ) -> Result<(), DbErr> {
+        self.ensure_writable()?;
+        let model = repository_traffic::ActiveModel {
+            repository_id: Set(repository_id.to_string()),
+            views_count: Set(traffic.views_count),
+            unique_visitors: Set(traffic.unique_visitors),
+            clone_count: Set(traffic.clone_count),
+            unique_cloners: Set(traffic.unique_cloners),
+            fetched_at: Set(chrono::Utc::now().naive_utc()),
+        };
+
+        repository_traffic::Entity::insert(model)
+            .on_conflict(
+                OnConflict::column(repository_traffic::Column::RepositoryId)
+                    .update_columns([
+                        repository_traffic::Column::ViewsCount,
+                        repository_traffic::Column::UniqueVisitors,
+                        repository_traffic::Column::CloneCount,
+                        repository_traffic::Column::UniqueCloners,
+                        repository_traffic::Column::FetchedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec(&self.conn)
+            .await?;
+
+        Ok(())
+    }
+
+    // 获取所有仓库最近一次记录的访问与克隆统计
+    pub async fn get_repository_traffic(&self) -> Result<HashMap<String, TrafficStats>, DbErr> {
+        let rows = repository_traffic::Entity::find().all(&self.conn).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                (
+                    r.repository_id,
+                    TrafficStats {
+                        views_count: r.views_count,
+                        unique_visitors: r.unique_visitors,
+                        clone_count: r.clone_count,
+                        unique_cloners: r.unique_cloners,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    // 用最新获取的主题替换仓库已存储的主题
+    pub async fn store_repository_topics(
+        &self,
+        repository_id: &str,
+        topics: &[String],
+    ) -> Result<(), DbErr> {
+        self.ensure_writable()?;
+
+        let txn = self.conn.begin().await?;
+        repository_topic::Entity::delete_many()
+            .filter(repository_topic::Column::RepositoryId.eq(repository_id))
+            .exec(&txn)
+            .await?;
+
+        if !topics.is_empty() {
+            let models = topics.iter().map(|topic| repository_topic::ActiveModel {
+                repository_id: Set(repository_id.to_string()),
+                topic: Set(topic.to_lowercase()),
+                ..Default::default()
+            });
+            repository_topic::Entity::insert_many(models)
+                .on_conflict(
+                    OnConflict::columns([
+                        repository_topic::Column::RepositoryId,
+                        repository_topic::Column::Topic,
+                    ])
+                    .do_nothing()
+                    .to_owned(),
+                )
+                .exec_without_returning(&txn)
+                .await?;
+        }
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    // 获取所有仓库已存储的主题，返回仓库ID -> 主题列表
+    pub async fn get_repository_topics(&self) -> Result<HashMap<String, Vec<String>>, DbErr> {
+        let rows = repository_topic::Entity::find()
+            .order_by_asc(repository_topic::Column::Topic)
+            .all(self.reader())
+            .await?;
+
+        let mut topics: HashMap<String, Vec<String>> = HashMap::new();
+        for row in rows {
+            topics.entry(row.repository_id).or_default().push(row.topic);
+        }
+        Ok(topics)
+    }
+
+    // 获取数据已过期（超过指定时长未分析或从未分析）的仓库，返回(owner, repo)
+    pub async fn repos_older_than(
+        &self,
+        older_than: std::time::Duration,
+    ) -> Result<Vec<(String, String)>, DbErr> {
+        let cutoff = chrono::Duration::from_std(older_than)
+            .ok()
+            .and_then(|older_than| {
+                chrono::Utc::now()
+                    .naive_utc()
+                    .checked_sub_signed(older_than)
+            })
+            .ok_or_else(|| DbErr::Custom(format!("时长过大: {:?}", older_than)))?;
+
+        let last_analyzed = self.get_last_analyzed_times().await?;
+        let programs = self.list_programs_with_github_url().await?;
+
+        let mut stale = Vec::new();
+        for program in programs {
+            let is_stale = last_analyzed
+                .get(&program.id)
+                .is_none_or(|analyzed_at| *analyzed_at < cutoff);
+            if !is_stale {
+                continue;
+            }
+
+            match program
+                .github_url
+                .as_deref()
+                .and_then(parse_github_repo_url)
+            {
+                Some(owner_repo) => stale.push(owner_repo),
+                None => warn!("无法解析仓库 {} 的GitHub地址", program.name),
+            }
+        }
+
+        info!("找到 {} 个数据过期的仓库", stale.len());
+        Ok(stale)
     }
 
-    // 根据用户名查找用户ID
-    pub async fn get_user_id_by_name(&self, login: &str) -> Result<Option<i32>, DbErr> {
-        info!("通过登录名查找用户ID: {}", login);
+    // 把提交扫描的部分统计写入暂存表，同一作者已存在时累加提交数，邮箱保留先出现的非空值
+    pub async fn stage_contributors(
+        &self,
+        scan_id: &str,
+        contributors: Vec<Contributor>,
+    ) -> Result<(), DbErr> {
+        self.ensure_writable()?;
+        // 每条记录6个参数，分批写入避免超过参数数量上限
+        const STAGE_BATCH_SIZE: usize = 1000;
 
-        let user = github_user::Entity::find()
-            .filter(github_user::Column::Login.eq(login))
-            .one(&self.conn)
-            .await?;
+        let table = self.qualified("contributor_scan_staging");
+        for chunk in contributors.chunks(STAGE_BATCH_SIZE) {
+            let placeholders = (0..chunk.len())
+                .map(|i| {
+                    let base = i * 6;
+                    format!(
+                        "(${}, ${}, ${}, ${}, ${}, ${})",
+                        base + 1,
+                        base + 2,
+                        base + 3,
+                        base + 4,
+                        base + 5,
+                        base + 6
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let values = chunk.iter().flat_map(|c| {
+                [
+                    scan_id.into(),
+                    c.id.into(),
+                    c.login.clone().into(),
+                    c.avatar_url.clone().into(),
+                    c.contributions.into(),
+                    c.email.clone().into(),
+                ]
+            });
+            let sql = format!(
+                "INSERT INTO {table} AS s (scan_id, author_id, login, avatar_url, contributions, email)
+                VALUES {placeholders}
+                ON CONFLICT (scan_id, author_id) DO UPDATE
+                SET contributions = s.contributions + EXCLUDED.contributions,
+                    email = COALESCE(s.email, EXCLUDED.email)"
+            );
+            self.conn.execute(self.statement(&sql, values)).await?;
+        }
 
-        Ok(user.map(|u| u.id))
+        debug!(
+            "暂存 {} 名贡献者的部分统计: {}",
+            contributors.len(),
+            scan_id
+        );
+        Ok(())
     }
 
-    // 根据仓库所有者和名称获取仓库ID
-    pub async fn get_repository_id(
+    // 合并扫描结束时内存中剩余的统计与暂存表中的部分统计，读取后清除该次扫描的暂存记录
+    pub async fn take_staged_contributors(
         &self,
-        owner: &str,
-        repo: &str,
-    ) -> Result<Option<String>, DbErr> {
-        info!("获取仓库ID: {}/{}", owner, repo);
-
-        // 直接查询github_url字段
-        let programs = program::Entity::find()
-            .filter(
-                program::Column::GithubUrl
-                    .contains(&format!("{}/{}", owner, repo))
-                    .or(program::Column::GithubUrl.contains(&format!("{}/{}.git", owner, repo))),
-            )
-            .all(&self.conn)
-            .await?;
+        scan_id: &str,
+        remaining: Vec<Contributor>,
+    ) -> Result<Vec<Contributor>, DbErr> {
+        self.stage_contributors(scan_id, remaining).await?;
 
-        if !programs.is_empty() {
-            info!("找到仓库 {}/{}, ID: {}", owner, repo, programs[0].id);
-            return Ok(Some(programs[0].id.clone()));
-        }
-
-        // 如果没有找到，尝试直接通过名称匹配
-        let programs_by_name = program::Entity::find()
-            .filter(program::Column::Name.eq(repo))
-            .all(&self.conn)
+        let sql = format!(
+            "DELETE FROM {} WHERE scan_id = $1
+            RETURNING author_id, login, avatar_url, contributions, email",
+            self.qualified("contributor_scan_staging")
+        );
+        let rows = self
+            .conn
+            .query_all(self.statement(&sql, [scan_id.into()]))
             .await?;
 
-        if !programs_by_name.is_empty() {
-            info!("通过名称找到仓库 {}, ID: {}", repo, programs_by_name[0].id);
-            return Ok(Some(programs_by_name[0].id.clone()));
-        }
+        let mut contributors = rows
+            .iter()
+            .map(|row| {
+                Ok(Contributor {
+                    id: row.try_get("", "author_id")?,
+                    login: row.try_get("", "login")?,
+                    avatar_url: row.try_get("", "avatar_url")?,
+                    contributions: row.try_get("", "contributions")?,
+                    email: row.try_get("", "email")?,
+                })
+            })
+            .collect::<Result<Vec<_>, DbErr>>()?;
+        contributors.sort_by_key(|c| std::cmp::Reverse(c.contributions));
 
-        warn!("未找到仓库 {}/{}", owner, repo);
-        Ok(None)
+        Ok(contributors)
     }
 
     // 存储仓库贡献者
@@ -119,46 +1329,58 @@ impl DbService {
         user_id: i32,
         contributions: i32,
     ) -> Result<(), DbErr> {
+        self.ensure_writable()?;
         info!(
             "存储贡献者关系: 仓库ID={}, 用户ID={}, 提交数={}",
             repository_id, user_id, contributions
         );
 
-        // 检查是否存在现有记录
-        let existing = repository_contributor::Entity::find()
-            .filter(repository_contributor::Column::RepositoryId.eq(repository_id))
-            .filter(repository_contributor::Column::UserId.eq(user_id))
-            .one(&self.conn)
-            .await?;
-
-        if let Some(existing) = existing {
-            // 已存在，更新贡献数
-            if existing.contributions != contributions {
-                let mut model: repository_contributor::ActiveModel = existing.clone().into();
-                model.contributions = Set(contributions);
-                model.updated_at = Set(chrono::Utc::now().naive_utc());
-                model.update(&self.conn).await?;
-                info!(
-                    "更新贡献者贡献数: {} -> {}",
-                    existing.contributions, contributions
-                );
-            } else {
-                info!("贡献者记录已存在且贡献数相同, 跳过更新");
+        // 贡献数异常时截断或拒绝
+        let contributions = if contributions > self.max_contributions {
+            if self.reject_excess_contributions {
+                return Err(DbErr::Custom(format!(
+                    "贡献数异常: 仓库ID={}, 用户ID={}, 提交数={} 超过上限 {}",
+                    repository_id, user_id, contributions, self.max_contributions
+                )));
             }
+            warn!(
+                "贡献数 {} 超过上限 {}，已截断: 仓库ID={}, 用户ID={}",
+                contributions, self.max_contributions, repository_id, user_id
+            );
+            self.max_contributions
         } else {
-            // 不存在，创建新记录
-            let now = chrono::Utc::now().naive_utc();
-            let contributor = repository_contributor::ActiveModel {
-                id: Default::default(),
-                repository_id: Set(repository_id.to_string()),
-                user_id: Set(user_id),
-                contributions: Set(contributions),
-                inserted_at: Set(now),
-                updated_at: Set(now),
-            };
+            contributions
+        };
+
+        // 单条语句完成插入或更新，并发分析同一贡献者时不会因先查询后插入而违反唯一约束；
+        // 贡献数相同时不更新，此时不返回记录
+        let sql = format!(
+            "INSERT INTO {} AS rc (repository_id, user_id, contributions, inserted_at, updated_at)
+            VALUES ($1, $2, $3, $4, $4)
+            ON CONFLICT (repository_id, user_id) DO UPDATE
+            SET contributions = EXCLUDED.contributions, updated_at = EXCLUDED.updated_at
+            WHERE rc.contributions IS DISTINCT FROM EXCLUDED.contributions
+            RETURNING (xmax = 0) AS inserted",
+            self.qualified("repository_contributors")
+        );
+        let now = chrono::Utc::now().naive_utc();
+        let result = self
+            .conn
+            .query_one(self.statement(
+                &sql,
+                [
+                    repository_id.into(),
+                    user_id.into(),
+                    contributions.into(),
+                    now.into(),
+                ],
+            ))
+            .await?;
 
-            contributor.insert(&self.conn).await?;
-            info!("创建新的贡献者记录");
+        match result {
+            Some(row) if row.try_get::<bool>("", "inserted")? => info!("创建新的贡献者记录"),
+            Some(_) => info!("更新贡献者贡献数为 {}", contributions),
+            None => info!("贡献者记录已存在且贡献数相同, 跳过更新"),
         }
 
         Ok(())
@@ -168,51 +1390,102 @@ impl DbService {
     pub async fn query_top_contributors(
         &self,
         repository_id: &str,
+        filter: &ContributorFilter,
     ) -> Result<Vec<ContributorDetail>, DbErr> {
         info!("查询仓库 ID={} 的顶级贡献者", repository_id);
 
+        // 根据过滤条件构建WHERE子句，参数从$2开始绑定
+        let mut conditions = vec!["rc.repository_id = $1".to_string()];
+        let mut values: Vec<sea_orm::Value> = vec![repository_id.into()];
+        if let Some(min) = filter.min_china_probability {
+            values.push(min.into());
+            conditions.push(format!("cl.china_probability >= ${}", values.len()));
+        }
+        if let Some(max) = filter.max_china_probability {
+            values.push(max.into());
+            conditions.push(format!("cl.china_probability <= ${}", values.len()));
+        }
+        match filter.classification {
+            ClassificationFilter::All => {}
+            ClassificationFilter::China => conditions.push("cl.is_from_china = true".to_string()),
+            ClassificationFilter::NonChina => {
+                conditions.push("cl.is_from_china = false".to_string())
+            }
+            ClassificationFilter::Unanalyzed => conditions.push("cl.id IS NULL".to_string()),
+        }
+        if let Some(region_code) = &filter.region_code {
+            values.push(region_code.to_uppercase().into());
+            conditions.push(format!("cl.region_code = ${}", values.len()));
+        }
+
+        // 排序字段来自枚举，拼接到SQL中的只有固定的列表达式和方向
+        let direction = filter.sort.direction.as_sql();
+        let order_by = match filter.sort.field {
+            ContributorSortField::Contributions => format!("rc.contributions {}", direction),
+            ContributorSortField::ChinaProbability => format!(
+                "cl.china_probability {} NULLS LAST, rc.contributions DESC",
+                direction
+            ),
+            ContributorSortField::ConfidenceScore => format!(
+                "ABS(cl.china_probability - 0.5) {} NULLS LAST, rc.contributions DESC",
+                direction
+            ),
+            ContributorSortField::Login => format!("LOWER(gu.login) {}", direction),
+            ContributorSortField::Followers => format!(
+                "gu.followers {} NULLS LAST, rc.contributions DESC",
+                direction
+            ),
+        };
+
         // 构建查询
-        let query = "
-            SELECT gu.github_id, gu.login, gu.name, rc.contributions, gu.location
-            FROM repository_contributors rc
-            JOIN github_users gu ON rc.user_id = gu.id
-            WHERE rc.repository_id = $1
-            ORDER BY rc.contributions DESC
+        let query = format!(
+            "
+            SELECT gu.id as user_id, gu.github_id, gu.login, gu.name, rc.contributions, gu.location,
+                cl.china_probability
+            FROM {} rc
+            JOIN {} gu ON rc.user_id = gu.id
+            LEFT JOIN {} cl ON cl.user_id = rc.user_id AND cl.repository_id = rc.repository_id
+            WHERE {}
+            ORDER BY {}
             LIMIT 20
-        ";
+        ",
+            self.qualified("repository_contributors"),
+            self.qualified("github_users"),
+            self.qualified("contributor_locations"),
+            conditions.join(" AND "),
+            order_by
+        );
 
         // 执行查询
         let result = self
-            .conn
-            .query_all(Statement::from_sql_and_values(
-                self.conn.get_database_backend(),
-                query,
-                [repository_id.into()],
-            ))
+            .reader()
+            .query_all(self.statement(&query, values))
             .await?;
 
         // 解析结果
         let mut contributors = Vec::new();
         for row in result {
-            let id: i64 = row.try_get("", "github_id")?;
-            let login: String = row.try_get("", "login")?;
-            let name: Option<String> = row.try_get("", "name")?;
-            let contributions: i32 = row.try_get("", "contributions")?;
-            let location: Option<String> = row.try_get("", "location")?;
-
-            contributors.push(ContributorDetail {
-                id,
-                login,
-                name,
-                contributions,
-                location,
-            });
+            contributors.push(contributor_detail_from_row(&row)?);
         }
 
         info!("找到 {} 个顶级贡献者", contributors.len());
         Ok(contributors)
     }
 
+    // 获取贡献者在仓库中已存储的贡献数，不是该仓库的贡献者时返回None
+    pub async fn get_repository_contributions(
+        &self,
+        repository_id: &str,
+        user_id: i32,
+    ) -> Result<Option<i32>, DbErr> {
+        Ok(repository_contributor::Entity::find()
+            .filter(repository_contributor::Column::RepositoryId.eq(repository_id))
+            .filter(repository_contributor::Column::UserId.eq(user_id))
+            .one(&self.conn)
+            .await?
+            .map(|rc| rc.contributions))
+    }
+
     // 存储贡献者位置信息
     pub async fn store_contributor_location(
         &self,
@@ -220,6 +1493,7 @@ impl DbService {
         user_id: i32,
         analysis: &crate::contributor_analysis::ContributorAnalysis,
     ) -> Result<(), DbErr> {
+        self.ensure_writable()?;
         info!(
             "存储贡献者位置信息: 仓库ID={}, 用户ID={}",
             repository_id, user_id
@@ -227,12 +1501,281 @@ impl DbService {
 
         // 通过conversion trait转换
         let cl = contributor_location::ActiveModel::from((repository_id, user_id, analysis));
-        cl.insert(&self.conn).await?;
 
+        // 单条语句完成插入或更新，并发分析同一贡献者时不会因先查询后插入而违反唯一约束
+        contributor_location::Entity::insert(cl)
+            .on_conflict(
+                OnConflict::columns([
+                    contributor_location::Column::RepositoryId,
+                    contributor_location::Column::UserId,
+                ])
+                .update_columns([
+                    contributor_location::Column::IsFromChina,
+                    contributor_location::Column::RegionCode,
+                    contributor_location::Column::CommonTimezone,
+                    contributor_location::Column::Uncertain,
+                    contributor_location::Column::ChinaProbability,
+                    contributor_location::Column::CommitHours,
+                    contributor_location::Column::CommitHoursNormalized,
+                    contributor_location::Column::TimezoneStats,
+                    contributor_location::Column::Signals,
+                    contributor_location::Column::DataSource,
+                    contributor_location::Column::AnalyzedAt,
+                    contributor_location::Column::ClassifiedAt,
+                ])
+                .to_owned(),
+            )
+            .exec(&self.conn)
+            .await?;
         info!("贡献者位置信息已存储");
+
+        Ok(())
+    }
+
+    // 获取贡献者在仓库中的位置信息
+    pub async fn get_contributor_location(
+        &self,
+        repository_id: &str,
+        user_id: i32,
+    ) -> Result<Option<contributor_location::Model>, DbErr> {
+        contributor_location::Entity::find()
+            .filter(contributor_location::Column::RepositoryId.eq(repository_id))
+            .filter(contributor_location::Column::UserId.eq(user_id))
+            .one(&self.conn)
+            .await
+    }
+
+    // 获取已存储的判定信号得分（信号名称, 得分），旧版本写入的记录没有信号时返回空列表
+    pub async fn get_classification_signals(
+        &self,
+        repository_id: &str,
+        user_id: i32,
+    ) -> Result<Vec<(String, f64)>, DbErr> {
+        let location = contributor_location::Entity::find()
+            .filter(contributor_location::Column::RepositoryId.eq(repository_id))
+            .filter(contributor_location::Column::UserId.eq(user_id))
+            .one(self.reader())
+            .await?;
+
+        Ok(location
+            .and_then(|l| l.signals)
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default())
+    }
+
+    // 获取所有贡献者位置记录及对应的用户，按仓库排序，用于基于已存储数据重新分类
+    pub async fn all_contributor_locations(
+        &self,
+    ) -> Result<Vec<(contributor_location::Model, Option<github_user::Model>)>, DbErr> {
+        contributor_location::Entity::find()
+            .find_also_related(github_user::Entity)
+            .order_by_asc(contributor_location::Column::RepositoryId)
+            .order_by_asc(contributor_location::Column::Id)
+            .all(&self.conn)
+            .await
+    }
+
+    // 只更新位置记录中的判定结果和判定时间，保留提交小时分布、数据来源和分析时间
+    pub async fn update_location_classification(
+        &self,
+        location_id: i32,
+        analysis: &crate::contributor_analysis::ContributorAnalysis,
+    ) -> Result<(), DbErr> {
+        self.ensure_writable()?;
+
+        contributor_location::ActiveModel {
+            id: Set(location_id),
+            is_from_china: Set(analysis.from_china),
+            region_code: Set(analysis.region_code().map(str::to_string)),
+            common_timezone: Set(Some(analysis.common_timezone.clone())),
+            uncertain: Set(analysis.uncertain),
+            china_probability: Set(Some(analysis.china_probability)),
+            signals: Set(serde_json::to_value(analysis.classification_signals()).ok()),
+            classified_at: Set(Some(chrono::Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(&self.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    // 重新判定结果没有变化的位置记录只更新判定时间
+    pub async fn touch_location_classified_at(&self, location_ids: &[i32]) -> Result<(), DbErr> {
+        self.ensure_writable()?;
+        if location_ids.is_empty() {
+            return Ok(());
+        }
+
+        contributor_location::Entity::update_many()
+            .col_expr(
+                contributor_location::Column::ClassifiedAt,
+                Expr::value(chrono::Utc::now().naive_utc()),
+            )
+            .filter(contributor_location::Column::Id.is_in(location_ids.iter().copied()))
+            .exec(&self.conn)
+            .await?;
+
         Ok(())
     }
 
+    // 获取贡献者参与的所有仓库（登录名不区分大小写），按贡献数降序，尚未分析位置的仓库判定为空
+    pub async fn get_contributor_repositories(
+        &self,
+        login: &str,
+    ) -> Result<Vec<ContributorRepoEntry>, DbErr> {
+        info!("查询用户 {} 参与的仓库", login);
+
+        let query = format!(
+            "
+            SELECT p.id as program_id, p.name, p.github_url, rc.contributions,
+                cl.is_from_china, cl.china_probability
+            FROM {} gu
+            JOIN {} rc ON rc.user_id = gu.id
+            JOIN {} p ON p.id = rc.repository_id
+            LEFT JOIN {} cl ON cl.user_id = rc.user_id AND cl.repository_id = rc.repository_id
+            WHERE LOWER(gu.login) = LOWER($1)
+            ORDER BY rc.contributions DESC, p.name
+        ",
+            self.qualified("github_users"),
+            self.qualified("repository_contributors"),
+            self.qualified("programs"),
+            self.qualified("contributor_locations")
+        );
+
+        let rows = self
+            .reader()
+            .query_all(self.statement(&query, [login.into()]))
+            .await?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(ContributorRepoEntry {
+                program_id: row.try_get("", "program_id")?,
+                name: row.try_get("", "name")?,
+                github_url: row.try_get("", "github_url")?,
+                contributions: row.try_get("", "contributions")?,
+                is_from_china: row.try_get("", "is_from_china")?,
+                china_probability: row.try_get("", "china_probability")?,
+            });
+        }
+
+        info!("用户 {} 参与了 {} 个仓库", login, entries.len());
+        Ok(entries)
+    }
+
+    // 获取中国贡献者比例最高的仓库，已分析贡献者少于min_contributors的仓库不参与排名
+    pub async fn get_top_repositories_by_china_ratio(
+        &self,
+        min_contributors: u32,
+        limit: u32,
+        topic: Option<&str>,
+    ) -> Result<Vec<RepoRanking>, DbErr> {
+        info!(
+            "查询中国贡献者比例最高的仓库 (至少 {} 名已分析贡献者)",
+            min_contributors
+        );
+
+        // 按user_id去重计数，同一贡献者即使有多条位置记录也只计一次；
+        // 指定主题时只统计带有该主题的仓库
+        let mut values: Vec<sea_orm::Value> =
+            vec![(min_contributors as i64).into(), (limit as i64).into()];
+        let topic_filter = match topic {
+            Some(topic) => {
+                values.push(topic.to_lowercase().into());
+                format!(
+                    "WHERE EXISTS (SELECT 1 FROM {} rt WHERE rt.repository_id = p.id AND rt.topic = $3)",
+                    self.qualified("repository_topics")
+                )
+            }
+            None => String::new(),
+        };
+
+        let query = format!(
+            "
+            SELECT p.id as program_id, p.name, p.github_url,
+                COUNT(DISTINCT cl.user_id) as total_contributors,
+                COUNT(DISTINCT cl.user_id) FILTER (WHERE cl.is_from_china) as china_contributors
+            FROM {} cl
+            JOIN {} p ON cl.repository_id = p.id
+            {}
+            GROUP BY p.id, p.name, p.github_url
+            HAVING COUNT(DISTINCT cl.user_id) >= $1
+            ORDER BY COUNT(DISTINCT cl.user_id) FILTER (WHERE cl.is_from_china)::float8
+                    / COUNT(DISTINCT cl.user_id) DESC,
+                COUNT(DISTINCT cl.user_id) DESC
+            LIMIT $2
+        ",
+            self.qualified("contributor_locations"),
+            self.qualified("programs"),
+            topic_filter
+        );
+
+        let rows = self
+            .reader()
+            .query_all(self.statement(&query, values))
+            .await?;
+
+        let mut rankings = Vec::new();
+        for row in rows {
+            let total_contributors: i64 = row.try_get("", "total_contributors")?;
+            let china_contributors: i64 = row.try_get("", "china_contributors")?;
+            rankings.push(RepoRanking {
+                program_id: row.try_get("", "program_id")?,
+                name: row.try_get("", "name")?,
+                github_url: row.try_get("", "github_url")?,
+                total_contributors,
+                china_contributors,
+                china_percentage: china_contributors as f64 / total_contributors as f64 * 100.0,
+            });
+        }
+
+        info!("找到 {} 个符合条件的仓库", rankings.len());
+        Ok(rankings)
+    }
+
+    // 跨所有已分析仓库统计中国贡献者的总贡献数和参与的仓库数，按总贡献数降序
+    pub async fn global_china_leaderboard(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<LeaderboardEntry>, DbErr> {
+        info!("查询所有仓库中贡献数最多的 {} 名中国贡献者", limit);
+
+        let query = format!(
+            "
+            SELECT gu.login, gu.name,
+                COALESCE(SUM(rc.contributions), 0)::BIGINT as total_contributions,
+                COUNT(DISTINCT cl.repository_id) as repo_count
+            FROM {} cl
+            JOIN {} gu ON cl.user_id = gu.id
+            JOIN {} rc ON cl.user_id = rc.user_id AND cl.repository_id = rc.repository_id
+            WHERE cl.is_from_china = true
+            GROUP BY gu.id, gu.login, gu.name
+            ORDER BY total_contributions DESC, repo_count DESC, gu.login
+            LIMIT $1
+        ",
+            self.qualified("contributor_locations"),
+            self.qualified("github_users"),
+            self.qualified("repository_contributors")
+        );
+
+        let rows = self
+            .reader()
+            .query_all(self.statement(&query, [(limit as i64).into()]))
+            .await?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(LeaderboardEntry {
+                login: row.try_get("", "login")?,
+                name: row.try_get("", "name")?,
+                total_contributions: row.try_get("", "total_contributions")?,
+                repo_count: row.try_get("", "repo_count")?,
+            });
+        }
+        Ok(entries)
+    }
+
     // 获取仓库的中国贡献者统计
     pub async fn get_repository_china_contributor_stats(
         &self,
@@ -241,21 +1784,25 @@ impl DbService {
         info!("获取仓库 ID={} 的中国贡献者统计", repository_id);
 
         // 查询中国贡献者统计
-        let stats_query = "
+        let stats_query = format!(
+            "
             SELECT 
                 COUNT(*) as total_contributors,
-                SUM(CASE WHEN is_from_china THEN 1 ELSE 0 END) as china_contributors
-            FROM contributor_locations
-            WHERE repository_id = $1
-        ";
+                COALESCE(SUM(CASE WHEN cl.is_from_china THEN 1 ELSE 0 END), 0) as china_contributors,
+                COALESCE(SUM(rc.contributions), 0)::BIGINT as total_commits,
+                COALESCE(SUM(CASE WHEN cl.is_from_china THEN rc.contributions ELSE 0 END), 0)::BIGINT
+                    as china_commits
+            FROM {} cl
+            LEFT JOIN {} rc ON cl.user_id = rc.user_id AND cl.repository_id = rc.repository_id
+            WHERE cl.repository_id = $1
+        ",
+            self.qualified("contributor_locations"),
+            self.qualified("repository_contributors")
+        );
 
         let maybe_result = self
-            .conn
-            .query_one(Statement::from_sql_and_values(
-                self.conn.get_database_backend(),
-                stats_query,
-                [repository_id.into()],
-            ))
+            .reader()
+            .query_one(self.statement(&stats_query, [repository_id.into()]))
             .await?;
 
         // 如果没有结果，返回空值
@@ -266,6 +1813,7 @@ impl DbService {
                     total_contributors: 0,
                     china_contributors: 0,
                     china_percentage: 0.0,
+                    china_commits_percentage: 0.0,
                     china_contributors_details: Vec::new(),
                 });
             }
@@ -273,55 +1821,552 @@ impl DbService {
 
         let total_contributors: i64 = stats_result.try_get("", "total_contributors")?;
         let china_contributors: i64 = stats_result.try_get("", "china_contributors")?;
+        let total_commits: i64 = stats_result.try_get("", "total_commits")?;
+        let china_commits: i64 = stats_result.try_get("", "china_commits")?;
 
         let china_percentage = if total_contributors > 0 {
             (china_contributors as f64 / total_contributors as f64) * 100.0
         } else {
             0.0
         };
+        let china_commits_percentage = if total_commits > 0 {
+            (china_commits as f64 / total_commits as f64) * 100.0
+        } else {
+            0.0
+        };
 
         // 查询中国贡献者详情
-        let china_details_query = "
-            SELECT gu.github_id, gu.login, gu.name, rc.contributions, gu.location
-            FROM contributor_locations cl
-            JOIN github_users gu ON cl.user_id = gu.id
-            JOIN repository_contributors rc ON cl.user_id = rc.user_id AND cl.repository_id = rc.repository_id
+        let china_details_query = format!(
+            "
+            SELECT gu.id as user_id, gu.github_id, gu.login, gu.name, rc.contributions, gu.location,
+                cl.china_probability
+            FROM {} cl
+            JOIN {} gu ON cl.user_id = gu.id
+            JOIN {} rc ON cl.user_id = rc.user_id AND cl.repository_id = rc.repository_id
             WHERE cl.repository_id = $1 AND cl.is_from_china = true
             ORDER BY rc.contributions DESC
             LIMIT 10
-        ";
+        ",
+            self.qualified("contributor_locations"),
+            self.qualified("github_users"),
+            self.qualified("repository_contributors")
+        );
 
         let china_details = self
-            .conn
-            .query_all(Statement::from_sql_and_values(
-                self.conn.get_database_backend(),
-                china_details_query,
-                [repository_id.into()],
-            ))
+            .reader()
+            .query_all(self.statement(&china_details_query, [repository_id.into()]))
             .await?;
 
         let mut china_contributors_details = Vec::new();
         for row in china_details {
-            let id: i64 = row.try_get("", "github_id")?;
-            let login: String = row.try_get("", "login")?;
-            let name: Option<String> = row.try_get("", "name")?;
-            let contributions: i32 = row.try_get("", "contributions")?;
-            let location: Option<String> = row.try_get("", "location")?;
-
-            china_contributors_details.push(ContributorDetail {
-                id,
-                login,
-                name,
-                contributions,
-                location,
-            });
+            china_contributors_details.push(contributor_detail_from_row(&row)?);
         }
 
         Ok(ChinaContributorStats {
             total_contributors,
             china_contributors,
             china_percentage,
+            china_commits_percentage,
             china_contributors_details,
         })
     }
+
+    // 按贡献者修改的文件语言统计中国贡献者比例
+    pub async fn get_language_china_breakdown(
+        &self,
+        repository_id: &str,
+    ) -> Result<Vec<LanguageStat>, DbErr> {
+        info!("获取仓库 ID={} 按语言的中国贡献者统计", repository_id);
+
+        let query = format!(
+            "
+            SELECT
+                cf.language,
+                COUNT(DISTINCT cf.user_id) as total_contributors,
+                COUNT(DISTINCT CASE WHEN cl.is_from_china THEN cf.user_id END) as china_contributors
+            FROM {} cf
+            JOIN {} cl ON cf.user_id = cl.user_id AND cf.repository_id = cl.repository_id
+            WHERE cf.repository_id = $1 AND cf.language IS NOT NULL
+            GROUP BY cf.language
+            ORDER BY total_contributors DESC
+        ",
+            self.qualified("commit_files"),
+            self.qualified("contributor_locations")
+        );
+
+        let result = self
+            .reader()
+            .query_all(self.statement(&query, [repository_id.into()]))
+            .await?;
+
+        let mut stats = Vec::new();
+        for row in result {
+            let language: String = row.try_get("", "language")?;
+            let total_contributors: i64 = row.try_get("", "total_contributors")?;
+            let china_contributors: i64 = row.try_get("", "china_contributors")?;
+
+            let china_percentage = if total_contributors > 0 {
+                (china_contributors as f64 / total_contributors as f64) * 100.0
+            } else {
+                0.0
+            };
+
+            stats.push(LanguageStat {
+                language,
+                total_contributors,
+                china_contributors,
+                china_percentage,
+            });
+        }
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{ConnectOptions, Database, Schema};
+
+    // 连接DATABASE_URL指向的数据库，在独立的schema中创建外部系统维护的表并执行迁移。
+    // 使用该函数的测试标记为ignore，通过`cargo test -- --ignored`运行
+    async fn test_db(name: &str) -> DbService {
+        let url = std::env::var("DATABASE_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .expect("数据库测试需要设置DATABASE_URL");
+        let schema = format!("github_handler_test_{}", name);
+        let mut options = ConnectOptions::new(url);
+        options
+            .set_schema_search_path(schema.clone())
+            .sqlx_logging(false);
+        let conn = Database::connect(options).await.unwrap();
+        conn.execute_unprepared(&format!(
+            "DROP SCHEMA IF EXISTS \"{0}\" CASCADE; CREATE SCHEMA \"{0}\"",
+            schema
+        ))
+        .await
+        .unwrap();
+
+        let backend = conn.get_database_backend();
+        let builder = Schema::new(backend);
+        for table in [
+            builder.create_table_from_entity(program::Entity),
+            builder.create_table_from_entity(github_user::Entity),
+            builder.create_table_from_entity(repository_contributor::Entity),
+            builder.create_table_from_entity(contributor_location::Entity),
+        ] {
+            conn.execute(backend.build(&table)).await.unwrap();
+        }
+        crate::migrations::setup_database(&conn, &schema)
+            .await
+            .unwrap();
+
+        DbService::new(conn, schema)
+    }
+
+    // 删除测试使用的schema
+    async fn drop_test_db(db: DbService) {
+        db.conn
+            .execute_unprepared(&format!("DROP SCHEMA \"{}\" CASCADE", db.schema))
+            .await
+            .unwrap();
+    }
+
+    async fn insert_program(db: &DbService, id: &str, name: &str, github_url: Option<&str>) {
+        program::ActiveModel {
+            id: Set(id.to_string()),
+            name: Set(name.to_string()),
+            github_url: Set(github_url.map(str::to_string)),
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+    }
+
+    async fn insert_user(db: &DbService, github_id: i64, login: &str) -> i32 {
+        let user = GitHubUser::from_contributor(&Contributor {
+            id: github_id,
+            login: login.to_string(),
+            avatar_url: String::new(),
+            contributions: 0,
+            email: None,
+        });
+        db.store_user(&user).await.unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn concurrent_store_contributor_keeps_one_row() {
+        let db = test_db("store_contributor").await;
+        insert_program(
+            &db,
+            "p1",
+            "crate-a",
+            Some("https://github.com/owner/crate-a"),
+        )
+        .await;
+        let user_id = insert_user(&db, 1, "alice").await;
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for contributions in 1..=10 {
+            let db = db.clone();
+            tasks.spawn(async move { db.store_contributor("p1", user_id, contributions).await });
+        }
+        while let Some(result) = tasks.join_next().await {
+            result.unwrap().unwrap();
+        }
+
+        let rows = repository_contributor::Entity::find()
+            .filter(repository_contributor::Column::RepositoryId.eq("p1"))
+            .filter(repository_contributor::Column::UserId.eq(user_id))
+            .all(&db.conn)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!((1..=10).contains(&rows[0].contributions));
+
+        drop_test_db(db).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn concurrent_store_contributor_location_keeps_one_row() {
+        let db = test_db("store_location").await;
+        insert_program(
+            &db,
+            "p1",
+            "crate-a",
+            Some("https://github.com/owner/crate-a"),
+        )
+        .await;
+        let user_id = insert_user(&db, 1, "alice").await;
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for commits in 1..=10 {
+            let db = db.clone();
+            tasks.spawn(async move {
+                let analysis = crate::contributor_analysis::build_analysis_from_timezone_stats(
+                    "alice@example.com",
+                    HashMap::from([("+08:00".to_string(), commits)]),
+                );
+                db.store_contributor_location("p1", user_id, &analysis)
+                    .await
+            });
+        }
+        while let Some(result) = tasks.join_next().await {
+            result.unwrap().unwrap();
+        }
+
+        let rows = contributor_location::Entity::find()
+            .filter(contributor_location::Column::RepositoryId.eq("p1"))
+            .filter(contributor_location::Column::UserId.eq(user_id))
+            .all(&db.conn)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].common_timezone.as_deref(), Some("+08:00"));
+
+        drop_test_db(db).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn get_repository_id_prefers_exact_url_among_substring_matches() {
+        let db = test_db("repository_id").await;
+        // p1的地址同样包含owner/repo子串
+        insert_program(
+            &db,
+            "p1",
+            "repo-cli",
+            Some("https://github.com/owner/repo-cli"),
+        )
+        .await;
+        insert_program(&db, "p2", "repo", Some("https://github.com/owner/repo")).await;
+
+        let found = db
+            .get_repository_id("owner", "repo")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, "p2");
+        assert!(found.other_candidates.is_empty());
+
+        // 宽松模式下两条都匹配，精确匹配优先于较小的ID
+        let loose = db.clone().with_loose_url_match(true);
+        let found = loose
+            .get_repository_id("owner", "repo")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, "p2");
+        assert_eq!(found.other_candidates, vec!["p1".to_string()]);
+
+        // 都是精确匹配时选择ID较小的记录
+        insert_program(&db, "p0", "repo", Some("https://github.com/owner/repo.git")).await;
+        let found = db
+            .get_repository_id("owner", "repo")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, "p0");
+        assert_eq!(found.other_candidates, vec!["p2".to_string()]);
+
+        drop_test_db(db).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn oversized_contribution_counts_are_capped_or_rejected() {
+        let db = test_db("max_contributions").await;
+        insert_program(
+            &db,
+            "p1",
+            "crate-a",
+            Some("https://github.com/owner/crate-a"),
+        )
+        .await;
+        let user_id = insert_user(&db, 1, "alice").await;
+        let stored = |db: DbService| async move {
+            repository_contributor::Entity::find()
+                .filter(repository_contributor::Column::RepositoryId.eq("p1"))
+                .one(&db.conn)
+                .await
+                .unwrap()
+                .map(|row| row.contributions)
+        };
+
+        let capped = db.clone().with_contribution_limit(1000, false);
+        capped
+            .store_contributor("p1", user_id, i32::MAX)
+            .await
+            .unwrap();
+        assert_eq!(stored(db.clone()).await, Some(1000));
+
+        let rejecting = db.clone().with_contribution_limit(500, true);
+        assert!(rejecting
+            .store_contributor("p1", user_id, 501)
+            .await
+            .is_err());
+        assert_eq!(stored(db.clone()).await, Some(1000));
+        rejecting
+            .store_contributor("p1", user_id, 500)
+            .await
+            .unwrap();
+        assert_eq!(stored(db.clone()).await, Some(500));
+
+        drop_test_db(db).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn china_stats_for_repository_without_locations_are_zero() {
+        let db = test_db("empty_stats").await;
+        insert_program(
+            &db,
+            "p1",
+            "crate-a",
+            Some("https://github.com/owner/crate-a"),
+        )
+        .await;
+        // 有贡献记录但还没有位置分析结果
+        let user_id = insert_user(&db, 1, "alice").await;
+        db.store_contributor("p1", user_id, 3).await.unwrap();
+
+        for repository_id in ["p1", "missing"] {
+            let stats = db
+                .get_repository_china_contributor_stats(repository_id)
+                .await
+                .unwrap();
+            assert_eq!(
+                (
+                    stats.total_contributors,
+                    stats.china_contributors,
+                    stats.china_percentage
+                ),
+                (0, 0, 0.0)
+            );
+            assert_eq!(stats.china_commits_percentage, 0.0);
+            assert!(stats.china_contributors_details.is_empty());
+        }
+
+        drop_test_db(db).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn region_query_applies_probability_filter_and_sort() {
+        let db = test_db("region_query").await;
+        insert_program(
+            &db,
+            "p1",
+            "crate-a",
+            Some("https://github.com/owner/crate-a"),
+        )
+        .await;
+        for (github_id, login, contributions, region_code, probability) in [
+            (1, "alice", 10, Some("CN"), 0.9),
+            (2, "bob", 20, Some("CN"), 0.6),
+            (3, "carol", 30, Some("US"), 0.1),
+            (4, "dave", 5, Some("CN"), 0.95),
+        ] {
+            let user_id = insert_user(&db, github_id, login).await;
+            db.store_contributor("p1", user_id, contributions)
+                .await
+                .unwrap();
+            contributor_location::ActiveModel {
+                repository_id: Set("p1".to_string()),
+                user_id: Set(user_id),
+                is_from_china: Set(region_code == Some("CN")),
+                region_code: Set(region_code.map(str::to_string)),
+                uncertain: Set(false),
+                china_probability: Set(Some(probability)),
+                data_source: Set("git".to_string()),
+                analyzed_at: Set(chrono::Utc::now().naive_utc()),
+                ..Default::default()
+            }
+            .insert(&db.conn)
+            .await
+            .unwrap();
+        }
+
+        let filter = ContributorFilter {
+            min_china_probability: Some(0.8),
+            region_code: Some("cn".to_string()),
+            sort: ContributorSort {
+                field: ContributorSortField::Login,
+                direction: SortDirection::Desc,
+            },
+            ..ContributorFilter::default()
+        };
+        let logins: Vec<String> = db
+            .query_top_contributors("p1", &filter)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| c.login)
+            .collect();
+        assert_eq!(logins, vec!["dave".to_string(), "alice".to_string()]);
+
+        drop_test_db(db).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn upsert_programs_shares_index_across_batches() {
+        let db = test_db("upsert_programs").await;
+        insert_program(
+            &db,
+            "p1",
+            "crate-a",
+            Some("https://github.com/owner/crate-a"),
+        )
+        .await;
+        let row = |name: &str, url: &str| ProgramRow {
+            name: name.to_string(),
+            github_url: url.to_string(),
+        };
+
+        let mut existing = db.load_program_index().await.unwrap();
+        let first = db
+            .upsert_programs(
+                &[
+                    row("crate-a", "https://github.com/Owner/crate-a"),
+                    row("crate-b", "https://github.com/owner/crate-b"),
+                ],
+                &mut existing,
+            )
+            .await
+            .unwrap();
+        assert_eq!((first.inserted, first.updated, first.unchanged), (1, 0, 1));
+
+        // 第二批中的仓库已由上一批创建，不会重复插入
+        let second = db
+            .upsert_programs(
+                &[
+                    row("crate-b-renamed", "https://github.com/owner/crate-b"),
+                    row("crate-b-renamed", "https://github.com/owner/crate-b"),
+                ],
+                &mut existing,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            (second.inserted, second.updated, second.unchanged),
+            (0, 1, 1)
+        );
+
+        let programs = db.list_programs_with_github_url().await.unwrap();
+        assert_eq!(programs.len(), 2);
+        assert!(programs.iter().any(|p| p.name == "crate-b-renamed"));
+
+        drop_test_db(db).await;
+    }
+
+    #[test]
+    fn contributors_are_estimated_from_stars() {
+        assert_eq!(estimated_contributors_from_stars(0), 1);
+        assert_eq!(estimated_contributors_from_stars(2500), 250);
+        assert_eq!(
+            estimated_contributors_from_stars(u64::MAX),
+            MAX_ESTIMATED_CONTRIBUTORS
+        );
+    }
+
+    #[test]
+    fn qualified_quotes_the_schema() {
+        let db = DbService::new(DatabaseConnection::Disconnected, "crates_sync".to_string());
+        assert_eq!(db.qualified("programs"), "\"crates_sync\".programs");
+
+        let db = DbService::new(DatabaseConnection::Disconnected, "we\"ird".to_string());
+        assert_eq!(db.qualified("programs"), "\"we\"\"ird\".programs");
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL"]
+    async fn migrations_run_in_a_non_default_schema() {
+        let db = test_db("custom_schema").await;
+
+        // 迁移创建的表和迁移记录表都在该schema中
+        let sql = "SELECT COUNT(*) AS count FROM information_schema.tables
+            WHERE table_schema = $1
+              AND table_name IN ('seaql_migrations', 'repository_sync_status', 'analysis_runs')";
+        let count = db
+            .conn
+            .query_one(db.statement(sql, [db.schema.clone().into()]))
+            .await
+            .unwrap()
+            .unwrap()
+            .try_get::<i64>("", "count")
+            .unwrap();
+        assert_eq!(count, 3);
+
+        // 手写SQL通过qualified()访问该schema中的表
+        insert_program(
+            &db,
+            "p1",
+            "crate-a",
+            Some("https://github.com/owner/crate-a"),
+        )
+        .await;
+        let user_id = insert_user(&db, 1, "alice").await;
+        db.store_contributor("p1", user_id, 5).await.unwrap();
+        let count = db
+            .conn
+            .query_one(db.statement(
+                &format!(
+                    "SELECT COUNT(*) AS count FROM {}",
+                    db.qualified("repository_contributors")
+                ),
+                [],
+            ))
+            .await
+            .unwrap()
+            .unwrap()
+            .try_get::<i64>("", "count")
+            .unwrap();
+        assert_eq!(count, 1);
+
+        drop_test_db(db).await;
+    }
 }
@@ -1,13 +1,85 @@
+use base64::Engine as _;
+use once_cell::sync::Lazy;
 use reqwest::{header, Client};
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 
 // GitHub API URL
 const GITHUB_API_URL: &str = "https://api.github.com";
 
+// 用户所属公开组织的缓存（登录名 -> 组织登录名），避免同一用户在多个仓库中重复请求
+static USER_ORGS_CACHE: Lazy<Mutex<HashMap<String, Vec<String>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// 用户资料的缓存（登录名 -> 资料），批量分析时同一用户在多个仓库中只请求一次；
+// 条目超过`USER_DETAILS_CACHE_TTL`后重新请求，watch等长期运行的进程能看到资料的变化
+static USER_DETAILS_CACHE: Lazy<Mutex<UserDetailsCache>> =
+    Lazy::new(|| Mutex::new(UserDetailsCache::default()));
+
+// 用户资料缓存条目的有效期
+const USER_DETAILS_CACHE_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+// 用户资料缓存的最大条目数，超过时先清理过期条目，仍然超过时淘汰最早缓存的条目
+const USER_DETAILS_CACHE_MAX_ENTRIES: usize = 20_000;
+
+// 带有效期和容量上限的用户资料缓存
+#[derive(Default)]
+struct UserDetailsCache {
+    entries: HashMap<String, (Instant, GitHubUser)>,
+}
+
+impl UserDetailsCache {
+    fn get(&mut self, login: &str, now: Instant) -> Option<GitHubUser> {
+        match self.entries.get(login) {
+            Some((cached_at, user)) if now.duration_since(*cached_at) < USER_DETAILS_CACHE_TTL => {
+                Some(user.clone())
+            }
+            Some(_) => {
+                self.entries.remove(login);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, login: String, user: GitHubUser, now: Instant) {
+        if self.entries.len() >= USER_DETAILS_CACHE_MAX_ENTRIES
+            && !self.entries.contains_key(&login)
+        {
+            self.entries.retain(|_, (cached_at, _)| {
+                now.duration_since(*cached_at) < USER_DETAILS_CACHE_TTL
+            });
+            if self.entries.len() >= USER_DETAILS_CACHE_MAX_ENTRIES {
+                let oldest = self
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, (cached_at, _))| *cached_at)
+                    .map(|(login, _)| login.clone());
+                if let Some(oldest) = oldest {
+                    self.entries.remove(&oldest);
+                }
+            }
+        }
+        self.entries.insert(login, (now, user));
+    }
+}
+
+tokio::task_local! {
+    // 当前任务正在分析的仓库的请求预算，未设置时不限制
+    static REQUEST_BUDGET: Arc<RequestBudget>;
+}
+
+use crate::services::cache::{save_etag_cache, ETAG_CACHE};
+
 // 使用main中定义的函数获取GitHub令牌
-use crate::config::get_github_token;
+use crate::config::{
+    full_jitter, get_github_token, get_user_agent, has_available_token, mark_token_rate_limited,
+    request_delay, retry_backoff,
+};
 
 // GitHub用户信息结构
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -27,6 +99,27 @@ pub struct GitHubUser {
     pub updated_at: Option<String>,
 }
 
+impl GitHubUser {
+    /// 只用提交扫描中得到的信息构建用户，不请求用户资料（没有位置、公司等字段）
+    pub fn from_contributor(contributor: &Contributor) -> Self {
+        GitHubUser {
+            id: contributor.id,
+            login: contributor.login.clone(),
+            avatar_url: Some(contributor.avatar_url.clone()),
+            name: None,
+            email: contributor.email.clone(),
+            company: None,
+            location: None,
+            bio: None,
+            public_repos: None,
+            followers: None,
+            following: None,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+}
+
 // 贡献者信息结构
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Contributor {
@@ -37,9 +130,326 @@ pub struct Contributor {
     pub email: Option<String>,
 }
 
+// 仓库最近14天的访问与克隆统计
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TrafficStats {
+    pub views_count: i64,
+    pub unique_visitors: i64,
+    pub clone_count: i64,
+    pub unique_cloners: i64,
+}
+
+// 仓库元数据，目前只关心是否为fork、仓库大小和星标数
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RepoMetadata {
+    pub full_name: String,
+    #[serde(default)]
+    pub fork: bool,
+    /// 仓库大小（KB）
+    #[serde(default)]
+    pub size: u64,
+    /// 星标数
+    #[serde(default)]
+    pub stargazers_count: u64,
+    /// fork的上游仓库
+    pub parent: Option<RepoParent>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RepoParent {
+    pub full_name: String,
+}
+
+// 令牌的身份与权限信息
+#[derive(Debug, Clone)]
+pub struct TokenPermissions {
+    pub login: String,
+    pub scopes: Vec<String>,
+    pub rate_limit_remaining: Option<u32>,
+}
+
+// 包含其他权限范围的权限范围（父范围 -> 被包含的子范围），如勾选repo的令牌同样可以访问public_repo
+const SCOPE_IMPLICATIONS: [(&str, &[&str]); 7] = [
+    (
+        "repo",
+        &[
+            "repo:status",
+            "repo_deployment",
+            "public_repo",
+            "repo:invite",
+            "security_events",
+        ],
+    ),
+    ("admin:org", &["write:org", "read:org"]),
+    ("write:org", &["read:org"]),
+    ("admin:public_key", &["write:public_key", "read:public_key"]),
+    ("write:public_key", &["read:public_key"]),
+    ("admin:repo_hook", &["write:repo_hook", "read:repo_hook"]),
+    ("user", &["read:user", "user:email", "user:follow"]),
+];
+
+/// 令牌已有的权限范围是否满足`required`：直接具有该范围，或具有包含该范围的父范围
+pub fn scope_granted(granted: &[String], required: &str) -> bool {
+    granted.iter().any(|scope| {
+        scope == required
+            || SCOPE_IMPLICATIONS
+                .iter()
+                .any(|(parent, implied)| scope == parent && implied.contains(&required))
+    })
+}
+
+/// 解析GitHub仓库URL，返回(owner, repo)
+///
+/// 支持 `https://github.com/owner/repo(.git)`、`git@github.com:owner/repo.git`
+/// 以及简写形式 `owner/repo`。
+pub fn parse_github_repo_url(url: &str) -> Option<(String, String)> {
+    let url = url.trim().trim_end_matches('/');
+
+    let path = if let Some(pos) = url.find("github.com") {
+        url[pos + "github.com".len()..].trim_start_matches([':', '/'])
+    } else if url.contains("://") || url.contains('@') {
+        // 非GitHub地址
+        return None;
+    } else {
+        url
+    };
+
+    let mut parts = path.split('/').filter(|p| !p.is_empty());
+    let owner = parts.next()?;
+    let repo = parts.next()?.trim_end_matches(".git");
+
+    if owner.is_empty() || repo.is_empty() {
+        return None;
+    }
+
+    Some((owner.to_string(), repo.to_string()))
+}
+
+/// 严格解析GitHub仓库地址：主机必须恰好是github.com（或www.github.com），
+/// 支持`https://github.com/owner/repo`、`git@github.com:owner/repo.git`、`ssh://git@github.com/owner/repo`
+/// 和不带协议的`github.com/owner/repo`，仓库名之后只允许出现`.git`、路径分隔符、查询参数或锚点
+pub fn parse_github_repo_url_strict(url: &str) -> Option<(String, String)> {
+    let url = url.trim();
+    let rest = match url.split_once("://") {
+        Some((scheme, rest)) => {
+            if !["https", "http", "git", "ssh", "git+ssh"].contains(&scheme.to_lowercase().as_str())
+            {
+                return None;
+            }
+            rest
+        }
+        None => url,
+    };
+    // 去掉用户信息（如git@）
+    let rest = match rest.split_once('@') {
+        Some((user, host)) if !user.contains(['/', ':']) => host,
+        _ => rest,
+    };
+    let (host, path) = rest.split_once([':', '/'])?;
+    let host = host.to_lowercase();
+    if host != "github.com" && host != "www.github.com" {
+        return None;
+    }
+
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let mut parts = path.split('/').filter(|p| !p.is_empty());
+    let owner = parts.next()?;
+    let repo = parts.next()?;
+    let repo = repo.strip_suffix(".git").unwrap_or(repo);
+    if owner.is_empty() || repo.is_empty() {
+        return None;
+    }
+
+    Some((owner.to_string(), repo.to_string()))
+}
+
+// 重试获取用户详情时的基础退避时间，每次重试翻倍
+const USER_DETAIL_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+// 统计类接口返回202时的首次重试间隔和累计等待上限
+const STATS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const STATS_MAX_WAIT: Duration = Duration::from_secs(30);
+
+/// 是否为值得重试的临时错误：超时、连接失败、服务端错误或速率限制
+pub fn is_transient_error(e: &reqwest::Error) -> bool {
+    e.is_timeout()
+        || e.is_connect()
+        || e.status().is_some_and(|status| {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        })
+}
+
+// 执行请求，遇到临时错误时按`retry_backoff`退避后重试，最多重试`retries`次；`label`用于日志
+async fn retry_transient<T, F, Fut>(
+    retries: u32,
+    base_delay: Duration,
+    label: &str,
+    mut request_fn: F,
+) -> Result<T, reqwest::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, reqwest::Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        match request_fn().await {
+            Err(e) if attempt < retries && is_transient_error(&e) => {
+                attempt += 1;
+                let wait = retry_backoff(base_delay, attempt);
+                warn!(
+                    "获取 {} 失败，{}毫秒后第 {} 次重试: {}",
+                    label,
+                    wait.as_millis(),
+                    attempt,
+                    e
+                );
+                tokio::time::sleep(wait).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 单个仓库可以消耗的GitHub请求数，防止一个超大仓库用完整个速率限制窗口的额度
+///
+/// 只统计消耗速率限制额度的请求，命中ETag缓存的304响应不计入，
+/// 因此推迟后重新分析时已获取过的提交页面不会再次占用预算。
+#[derive(Debug)]
+pub struct RequestBudget {
+    cap: u64,
+    used: AtomicU64,
+}
+
+impl RequestBudget {
+    pub fn new(cap: u64) -> Self {
+        RequestBudget {
+            cap,
+            used: AtomicU64::new(0),
+        }
+    }
+
+    /// 已消耗的请求数
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    /// 已达到请求数上限
+    pub fn exhausted(&self) -> bool {
+        self.used() >= self.cap
+    }
+}
+
+/// 在指定的请求预算下执行`future`，其中通过任何GitHubApiClient发出的请求都计入该预算
+pub async fn with_request_budget<F: std::future::Future>(
+    budget: Arc<RequestBudget>,
+    future: F,
+) -> F::Output {
+    REQUEST_BUDGET.scope(budget, future).await
+}
+
+/// 当前任务的请求预算已用完时返回true，没有设置预算时总是返回false
+pub fn request_budget_exhausted() -> bool {
+    REQUEST_BUDGET
+        .try_with(|budget| budget.exhausted())
+        .unwrap_or(false)
+}
+
+// GitHub网页界面提交使用的身份
+const WEB_FLOW_LOGIN: &str = "web-flow";
+const WEB_FLOW_EMAIL: &str = "noreply@github.com";
+
+// 是否保留web-flow身份，默认排除
+static INCLUDE_WEB_FLOW: AtomicBool = AtomicBool::new(false);
+
+/// 设置是否在贡献者统计中保留web-flow身份
+pub fn set_include_web_flow(include: bool) {
+    INCLUDE_WEB_FLOW.store(include, Ordering::Relaxed);
+}
+
+/// 登录名或邮箱属于GitHub网页界面提交的web-flow身份（`GitHub <noreply@github.com>`），
+/// 且没有通过`--include-web-flow`保留时返回true
+pub fn is_excluded_web_flow(login: Option<&str>, email: Option<&str>) -> bool {
+    if INCLUDE_WEB_FLOW.load(Ordering::Relaxed) {
+        return false;
+    }
+    login.is_some_and(|l| l.eq_ignore_ascii_case(WEB_FLOW_LOGIN))
+        || email.is_some_and(|e| e.trim().eq_ignore_ascii_case(WEB_FLOW_EMAIL))
+}
+
+/// 解析分页响应的`Link`头，返回 rel -> URL 的映射
+///
+/// 格式为 `<url>; rel="next", <url>; rel="last"`，容忍多余空格、单引号或无引号的rel值，
+/// 以及一个链接带多个以空格分隔的rel（如 `rel="next last"`）。
+pub fn parse_link_header(value: &str) -> HashMap<String, String> {
+    let mut links = HashMap::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>').map(|i| start + i) else {
+            break;
+        };
+        let url = rest[start + 1..end].trim();
+
+        // 参数部分到下一个链接的'<'为止
+        let after = &rest[end + 1..];
+        let params_end = after.find('<').unwrap_or(after.len());
+        for param in after[..params_end].split(';') {
+            let Some((key, val)) = param.split_once('=') else {
+                continue;
+            };
+            if !key.trim().eq_ignore_ascii_case("rel") {
+                continue;
+            }
+            let val = val.trim().trim_end_matches(',').trim();
+            let val = val.trim_matches(|c| c == '"' || c == '\'');
+            for rel in val.split_whitespace() {
+                links
+                    .entry(rel.to_ascii_lowercase())
+                    .or_insert_with(|| url.to_string());
+            }
+        }
+
+        rest = &after[params_end..];
+    }
+
+    links
+}
+
+/// 从分页URL中提取`page`查询参数
+pub fn page_from_url(url: &str) -> Option<u32> {
+    let query = url.split_once('?')?.1;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "page")
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+/// 从GitHub的隐私邮箱中解析登录名
+///
+/// 支持 `<id>+<login>@users.noreply.github.com` 和旧格式 `<login>@users.noreply.github.com`，
+/// 其他邮箱返回None。
+pub fn parse_noreply_login(email: &str) -> Option<String> {
+    let (local, domain) = email.trim().rsplit_once('@')?;
+    if !domain.eq_ignore_ascii_case("users.noreply.github.com") {
+        return None;
+    }
+
+    let login = match local.split_once('+') {
+        Some((id, login)) if !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()) => login,
+        Some(_) => return None,
+        None => local,
+    };
+
+    (!login.is_empty()).then(|| login.to_string())
+}
+
 // GitHub API客户端
+#[derive(Clone)]
 pub struct GitHubApiClient {
     client: Client,
+    // 显式指定的令牌，为None时从配置文件或环境变量的令牌池中轮换
+    token: Option<String>,
 }
 
 impl GitHubApiClient {
@@ -48,41 +458,588 @@ impl GitHubApiClient {
         // 初始化为不带认证的Client
         let client = Client::builder()
             .timeout(Duration::from_secs(30))
-            .user_agent("github-handler")
+            .user_agent(get_user_agent())
             .build()
             .unwrap_or_else(|_| Client::new());
 
-        GitHubApiClient { client }
+        GitHubApiClient {
+            client,
+            token: None,
+        }
+    }
+
+    // 使用显式指定的令牌，不再读取配置文件和环境变量，也不参与令牌轮换
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    // 本次请求使用的令牌
+    fn token(&self) -> String {
+        self.token.clone().unwrap_or_else(get_github_token)
     }
 
     // 创建带有认证头的请求构建器
-    fn authorized_request(&self, url: &str) -> reqwest::RequestBuilder {
-        let token = get_github_token();
+    fn authorized_request(&self, url: &str, token: &str) -> reqwest::RequestBuilder {
         let mut builder = self.client.get(url);
 
         if !token.is_empty() {
             builder = builder.header(header::AUTHORIZATION, format!("token {}", token));
         }
 
-        builder.header(header::USER_AGENT, "github-handler")
+        builder
+    }
+
+    // 发送带认证的GET请求；令牌速率限制耗尽时记录其重置时间，并换用其他令牌重试
+    async fn send_authorized(&self, url: &str) -> Result<reqwest::Response, reqwest::Error> {
+        self.send_authorized_if_none_match(url, None).await
+    }
+
+    // 发送带认证的GET请求，提供ETag时附带If-None-Match头，数据未变化时GitHub返回304
+    async fn send_authorized_if_none_match(
+        &self,
+        url: &str,
+        etag: Option<&str>,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let mut headers = header::HeaderMap::new();
+        if let Some(etag) = etag.and_then(|etag| header::HeaderValue::from_str(etag).ok()) {
+            headers.insert(header::IF_NONE_MATCH, etag);
+        }
+        self.send_authorized_with_headers(url, headers).await
+    }
+
+    // 发送带认证和额外请求头的GET请求；令牌速率限制耗尽时换用其他令牌重试
+    async fn send_authorized_with_headers(
+        &self,
+        url: &str,
+        headers: header::HeaderMap,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        self.send_with_token_rotation(|token| {
+            self.authorized_request(url, token).headers(headers.clone())
+        })
+        .await
+    }
+
+    // 所有GitHub请求（REST和GraphQL）的发送入口：`build`用给定的令牌构建请求。
+    // 请求计入当前任务的请求预算；令牌速率限制耗尽时记录其重置时间，并换用其他令牌重试
+    async fn send_with_token_rotation<B>(
+        &self,
+        build: B,
+    ) -> Result<reqwest::Response, reqwest::Error>
+    where
+        B: Fn(&str) -> reqwest::RequestBuilder,
+    {
+        loop {
+            let token = self.token();
+            let response = build(&token).send().await?;
+
+            // 304响应不消耗速率限制额度，不计入请求预算
+            if response.status() != reqwest::StatusCode::NOT_MODIFIED {
+                let _ =
+                    REQUEST_BUDGET.try_with(|budget| budget.used.fetch_add(1, Ordering::SeqCst));
+            }
+
+            let exhausted = response
+                .headers()
+                .get("x-ratelimit-remaining")
+                .and_then(|v| v.to_str().ok())
+                == Some("0");
+            // 显式指定的令牌无法换用其他令牌
+            if !exhausted || self.token.is_some() {
+                return Ok(response);
+            }
+
+            let reset_at = response
+                .headers()
+                .get("x-ratelimit-reset")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or_default();
+            mark_token_rate_limited(&token, reset_at);
+
+            // 只有请求被拒绝、令牌确实被标记为耗尽且还有可用令牌时才重试
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let rejected = matches!(
+                response.status(),
+                reqwest::StatusCode::FORBIDDEN | reqwest::StatusCode::TOO_MANY_REQUESTS
+            );
+            if !rejected || reset_at <= now || !has_available_token() {
+                return Ok(response);
+            }
+
+            info!("切换到下一个GitHub令牌重试请求");
+        }
+    }
+
+    // 轮询统计类接口直到数据就绪：GitHub在后台计算统计数据时返回202，需要稍后重试。
+    // 退避上限从interval开始翻倍，实际等待时间在0到上限之间随机取值，累计等待超过max_wait后放弃
+    pub async fn poll_until_ready<F, Fut>(
+        &self,
+        mut request_fn: F,
+        max_wait: Duration,
+        interval: Duration,
+    ) -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<reqwest::Response, reqwest::Error>>,
+    {
+        let started = std::time::Instant::now();
+        let mut delay = interval;
+
+        loop {
+            let response = request_fn().await?;
+            if response.status() != reqwest::StatusCode::ACCEPTED {
+                return Ok(response);
+            }
+
+            let elapsed = started.elapsed();
+            if elapsed >= max_wait {
+                return Err(format!(
+                    "GitHub统计数据在{}秒内仍未生成完成: {}",
+                    max_wait.as_secs(),
+                    response.url()
+                )
+                .into());
+            }
+
+            let wait = full_jitter(delay.min(max_wait - elapsed));
+            debug!(
+                "GitHub正在生成统计数据，{}毫秒后重试: {}",
+                wait.as_millis(),
+                response.url()
+            );
+            tokio::time::sleep(wait).await;
+            delay = delay.saturating_mul(2);
+        }
+    }
+
+    // 获取仓库最近52周每周的提交数（按时间从早到晚），统计尚未生成时等待GitHub生成完成
+    pub async fn get_repository_participation(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> Result<Vec<i64>, Box<dyn std::error::Error + Send + Sync>> {
+        #[derive(Debug, Deserialize)]
+        struct Participation {
+            all: Vec<i64>,
+        }
+
+        let url = format!(
+            "{}/repos/{}/{}/stats/participation",
+            GITHUB_API_URL, owner, repo
+        );
+        debug!("请求仓库提交活跃度: {}", url);
+        let participation: Participation = self
+            .poll_until_ready(
+                || self.send_authorized(&url),
+                STATS_MAX_WAIT,
+                STATS_POLL_INTERVAL,
+            )
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(participation.all)
+    }
+
+    // 检查当前令牌是否有效且具有所需的权限范围（通过GET /user返回的X-OAuth-Scopes响应头）
+    pub async fn verify_token_permissions(
+        &self,
+        required_scopes: &[&str],
+    ) -> Result<TokenPermissions, Box<dyn std::error::Error + Send + Sync>> {
+        #[derive(Debug, Deserialize)]
+        struct AuthenticatedUser {
+            login: String,
+        }
+
+        let token = self.token();
+        if token.is_empty() {
+            return Err("未配置GitHub令牌，无法校验令牌权限".into());
+        }
+
+        let url = format!("{}/user", GITHUB_API_URL);
+        let response = self.send_authorized(&url).await?;
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err("GitHub令牌无效或已过期，请在配置文件或GITHUB_TOKEN中更新令牌".into());
+        }
+        let response = response.error_for_status()?;
+
+        let header_value = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        // 细粒度令牌不返回X-OAuth-Scopes，此时无法检查权限范围
+        let scopes_header = header_value("x-oauth-scopes");
+        let rate_limit_remaining =
+            header_value("x-ratelimit-remaining").and_then(|v| v.parse().ok());
+
+        let scopes: Vec<String> = scopes_header
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let user: AuthenticatedUser = response.json().await?;
+
+        if scopes_header.is_none() {
+            if !required_scopes.is_empty() {
+                warn!(
+                    "令牌 {} 未返回权限范围（可能是细粒度令牌），跳过权限检查",
+                    user.login
+                );
+            }
+        } else {
+            let missing: Vec<&str> = required_scopes
+                .iter()
+                .copied()
+                .filter(|required| !scope_granted(&scopes, required))
+                .collect();
+            if !missing.is_empty() {
+                return Err(format!(
+                    "GitHub令牌（用户 {}）缺少权限范围: {}，请在 https://github.com/settings/tokens 为令牌勾选这些权限",
+                    user.login,
+                    missing.join(", ")
+                )
+                .into());
+            }
+        }
+
+        Ok(TokenPermissions {
+            login: user.login,
+            scopes,
+            rate_limit_remaining,
+        })
     }
 
     // 获取GitHub用户详细信息
     pub async fn get_user_details(&self, username: &str) -> Result<GitHubUser, reqwest::Error> {
+        if let Some(user) = USER_DETAILS_CACHE
+            .lock()
+            .unwrap()
+            .get(username, Instant::now())
+        {
+            return Ok(user);
+        }
+
         let url = format!("{}/users/{}", GITHUB_API_URL, username);
         debug!("请求用户信息: {}", url);
 
-        let response = self
-            .authorized_request(&url)
-            .send()
-            .await?
-            .error_for_status()?;
+        let response = self.send_authorized(&url).await?.error_for_status()?;
 
         let user: GitHubUser = response.json().await?;
 
+        USER_DETAILS_CACHE.lock().unwrap().insert(
+            username.to_string(),
+            user.clone(),
+            Instant::now(),
+        );
         Ok(user)
     }
 
+    // 获取GitHub用户详细信息，遇到超时、连接失败或服务端错误等临时错误时退避后重试，最多重试`retries`次
+    pub async fn get_user_details_with_retries(
+        &self,
+        username: &str,
+        retries: u32,
+    ) -> Result<GitHubUser, reqwest::Error> {
+        let label = format!("用户 {} 详情", username);
+        retry_transient(retries, USER_DETAIL_RETRY_BASE_DELAY, &label, || {
+            self.get_user_details(username)
+        })
+        .await
+    }
+
+    // 获取仓库元数据
+    pub async fn get_repo_metadata(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> Result<RepoMetadata, reqwest::Error> {
+        let url = format!("{}/repos/{}/{}", GITHUB_API_URL, owner, repo);
+        debug!("请求仓库信息: {}", url);
+
+        self.send_authorized(&url)
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    // 获取仓库的README文本，仓库没有README时返回None
+    pub async fn get_repository_readme(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        #[derive(Debug, Deserialize)]
+        struct Readme {
+            content: String,
+        }
+
+        let url = format!("{}/repos/{}/{}/readme", GITHUB_API_URL, owner, repo);
+        debug!("请求仓库README: {}", url);
+
+        let response = self.send_authorized(&url).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let readme: Readme = response.error_for_status()?.json().await?;
+
+        // content为按行折断的base64，解码前去掉换行
+        let encoded: String = readme
+            .content
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(encoded)?;
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    // 获取仓库的主题标签
+    pub async fn get_repository_topics(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> Result<Vec<String>, reqwest::Error> {
+        #[derive(Debug, Deserialize)]
+        struct Topics {
+            names: Vec<String>,
+        }
+
+        let url = format!("{}/repos/{}/{}/topics", GITHUB_API_URL, owner, repo);
+        debug!("请求仓库主题: {}", url);
+
+        // 主题接口早期需要mercy预览版的Accept头，保留以兼容GitHub Enterprise的旧版本
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::ACCEPT,
+            header::HeaderValue::from_static("application/vnd.github.mercy-preview+json"),
+        );
+
+        let topics: Topics = self
+            .send_authorized_with_headers(&url, headers)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(topics.names)
+    }
+
+    // 获取仓库的访问与克隆统计（需要令牌具有仓库的push权限）
+    pub async fn get_repository_traffic(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> Result<TrafficStats, Box<dyn std::error::Error + Send + Sync>> {
+        // 访问统计和克隆统计的响应中都有总数和独立用户数
+        #[derive(Debug, Deserialize)]
+        struct TrafficCounts {
+            count: i64,
+            uniques: i64,
+        }
+
+        let views_url = format!("{}/repos/{}/{}/traffic/views", GITHUB_API_URL, owner, repo);
+        debug!("请求仓库访问统计: {}", views_url);
+        let views: TrafficCounts = self
+            .send_authorized(&views_url)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let clones_url = format!("{}/repos/{}/{}/traffic/clones", GITHUB_API_URL, owner, repo);
+        debug!("请求仓库克隆统计: {}", clones_url);
+        let clones: TrafficCounts = self
+            .send_authorized(&clones_url)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(TrafficStats {
+            views_count: views.count,
+            unique_visitors: views.uniques,
+            clone_count: clones.count,
+            unique_cloners: clones.uniques,
+        })
+    }
+
+    // 获取用户公开所属的组织（登录名），结果会被缓存
+    pub async fn get_user_orgs(&self, login: &str) -> Result<Vec<String>, reqwest::Error> {
+        if let Some(orgs) = USER_ORGS_CACHE.lock().unwrap().get(login) {
+            return Ok(orgs.clone());
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Org {
+            login: String,
+        }
+
+        let url = format!("{}/users/{}/orgs?per_page=100", GITHUB_API_URL, login);
+        debug!("请求用户组织: {}", url);
+
+        let orgs: Vec<Org> = self
+            .send_authorized(&url)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let orgs: Vec<String> = orgs.into_iter().map(|o| o.login).collect();
+
+        USER_ORGS_CACHE
+            .lock()
+            .unwrap()
+            .insert(login.to_string(), orgs.clone());
+
+        Ok(orgs)
+    }
+
+    // 通过GraphQL API获取作者在默认分支上最近max_commits个提交的时区分布（时区 -> 提交数）。
+    // REST API返回的提交时间已转换为UTC，只有GraphQL的authoredDate保留了作者的本地时区
+    pub async fn get_commit_author_timezone(
+        &self,
+        owner: &str,
+        repo: &str,
+        login: &str,
+        max_commits: u32,
+    ) -> Result<HashMap<String, usize>, Box<dyn std::error::Error + Send + Sync>> {
+        const USER_ID_QUERY: &str = "query($login: String!) { user(login: $login) { id } }";
+        const HISTORY_QUERY: &str = "
+            query($owner: String!, $repo: String!, $authorId: ID!, $first: Int!, $after: String) {
+              repository(owner: $owner, name: $repo) {
+                defaultBranchRef {
+                  target {
+                    ... on Commit {
+                      history(first: $first, after: $after, author: { id: $authorId }) {
+                        pageInfo { hasNextPage endCursor }
+                        nodes { authoredDate }
+                      }
+                    }
+                  }
+                }
+              }
+            }";
+
+        let user = self
+            .graphql(USER_ID_QUERY, serde_json::json!({ "login": login }))
+            .await?;
+        let author_id = user["user"]["id"]
+            .as_str()
+            .ok_or_else(|| format!("未找到GitHub用户: {}", login))?
+            .to_string();
+
+        let mut timezone_stats = HashMap::new();
+        let mut fetched = 0;
+        let mut cursor: Option<String> = None;
+
+        while fetched < max_commits {
+            let page_size = (max_commits - fetched).min(100);
+            let data = self
+                .graphql(
+                    HISTORY_QUERY,
+                    serde_json::json!({
+                        "owner": owner,
+                        "repo": repo,
+                        "authorId": author_id,
+                        "first": page_size,
+                        "after": cursor,
+                    }),
+                )
+                .await?;
+
+            let history = &data["repository"]["defaultBranchRef"]["target"]["history"];
+            let nodes = history["nodes"].as_array().cloned().unwrap_or_default();
+            if nodes.is_empty() {
+                break;
+            }
+            fetched += nodes.len() as u32;
+
+            for date in nodes.iter().filter_map(|n| n["authoredDate"].as_str()) {
+                match chrono::DateTime::parse_from_rfc3339(date) {
+                    Ok(dt) => {
+                        *timezone_stats
+                            .entry(dt.format("%:z").to_string())
+                            .or_insert(0) += 1
+                    }
+                    Err(e) => debug!("无法解析提交时间 {}: {}", date, e),
+                }
+            }
+
+            if history["pageInfo"]["hasNextPage"].as_bool() != Some(true) {
+                break;
+            }
+            cursor = history["pageInfo"]["endCursor"]
+                .as_str()
+                .map(str::to_string);
+        }
+
+        debug!(
+            "通过API获取到 {} 在 {}/{} 的 {} 个提交时间",
+            login, owner, repo, fetched
+        );
+        Ok(timezone_stats)
+    }
+
+    // 执行GraphQL查询，返回data字段（GraphQL API必须使用令牌）
+    async fn graphql(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        let token = self.token();
+        if token.is_empty() {
+            return Err("GitHub GraphQL API需要配置令牌".into());
+        }
+
+        let url = format!("{}/graphql", GITHUB_API_URL);
+        let body = serde_json::json!({ "query": query, "variables": variables });
+        loop {
+            // 与REST请求一样在相邻请求之间等待，避免连续的GraphQL分页请求触发限制
+            tokio::time::sleep(request_delay()).await;
+
+            let response = self
+                .send_with_token_rotation(|token| {
+                    self.client
+                        .post(&url)
+                        .header(header::AUTHORIZATION, format!("bearer {}", token))
+                        .json(&body)
+                })
+                .await?
+                .error_for_status()?;
+            let token_exhausted = response
+                .headers()
+                .get("x-ratelimit-remaining")
+                .and_then(|v| v.to_str().ok())
+                == Some("0");
+            let mut response: serde_json::Value = response.json().await?;
+
+            if let Some(errors) = response.get("errors").filter(|e| !e.is_null()) {
+                // GraphQL的速率限制以200响应中的RATE_LIMITED错误返回，令牌已在发送时标记为耗尽，
+                // 还有可用令牌时换用其他令牌重试
+                let rate_limited = errors
+                    .as_array()
+                    .is_some_and(|errors| errors.iter().any(|e| e["type"] == "RATE_LIMITED"));
+                if rate_limited && token_exhausted && self.token.is_none() && has_available_token()
+                {
+                    info!("GraphQL令牌速率限制已耗尽，切换到下一个GitHub令牌重试");
+                    continue;
+                }
+                return Err(format!("GraphQL查询失败: {}", errors).into());
+            }
+
+            return Ok(response["data"].take());
+        }
+    }
+
     // 获取所有仓库贡献者（通过Commits API）
     pub async fn get_all_repository_contributors(
         &self,
@@ -90,9 +1047,37 @@ impl GitHubApiClient {
         repo: &str,
     ) -> Result<Vec<Contributor>, Box<dyn std::error::Error + Send + Sync>> {
         info!("通过Commits API获取所有仓库贡献者: {}/{}", owner, repo);
+        self.get_repository_contributors_since(owner, repo, None)
+            .await
+    }
+
+    // 获取指定时间（ISO 8601）之后有提交的贡献者，贡献数只统计该时间之后的提交
+    pub async fn get_repository_contributors_since(
+        &self,
+        owner: &str,
+        repo: &str,
+        since: Option<&str>,
+    ) -> Result<Vec<Contributor>, Box<dyn std::error::Error + Send + Sync>> {
+        self.scan_repository_contributors(owner, repo, since, None, |_| async { Ok(()) })
+            .await
+    }
 
+    // 扫描提交统计贡献者；设置`flush_pages`时每处理该数量的页面就把已统计的部分交给`flush`
+    // （如写入数据库暂存表）并清空内存中的统计，返回值只包含最后一次交出后的部分
+    pub async fn scan_repository_contributors<F, Fut>(
+        &self,
+        owner: &str,
+        repo: &str,
+        since: Option<&str>,
+        flush_pages: Option<u32>,
+        mut flush: F,
+    ) -> Result<Vec<Contributor>, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: FnMut(Vec<Contributor>) -> Fut,
+        Fut: std::future::Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+    {
         // 使用HashMap统计每个贡献者的提交次数
-        let mut contributors_map = std::collections::HashMap::new();
+        let mut contributors_map = ContributorTally::new();
         let mut page = 1;
         let per_page = 100; // GitHub允许的最大值
 
@@ -100,14 +1085,37 @@ impl GitHubApiClient {
         let max_pages = 100;
 
         while page <= max_pages {
-            let url = format!(
+            let mut url = format!(
                 "{}/repos/{}/{}/commits?page={}&per_page={}",
                 GITHUB_API_URL, owner, repo, page, per_page
             );
+            if let Some(since) = since {
+                url.push_str(&format!("&since={}", since));
+            }
+
+            // 达到单仓库请求数上限时放弃本次扫描，由调用方推迟到之后继续
+            if request_budget_exhausted() {
+                save_etag_cache().await;
+                return Err(format!(
+                    "扫描仓库 {}/{} 的提交时达到单仓库请求数上限（第{}页）",
+                    owner, repo, page
+                )
+                .into());
+            }
 
             debug!("请求Commits API: {} (第{}页)", url, page);
 
-            let response = match self.authorized_request(&url).send().await {
+            // 完整的贡献者列表使用ETag缓存，页面未变化时不消耗速率限制额度
+            let cached = if since.is_none() {
+                ETAG_CACHE.lock().unwrap().get(&url)
+            } else {
+                None
+            };
+
+            let response = match self
+                .send_authorized_if_none_match(&url, cached.as_ref().map(|(etag, _)| etag.as_str()))
+                .await
+            {
                 Ok(resp) => resp,
                 Err(e) => {
                     warn!("获取提交页面 {} 失败: {}", page, e);
@@ -115,8 +1123,10 @@ impl GitHubApiClient {
                 }
             };
 
+            let not_modified = response.status() == reqwest::StatusCode::NOT_MODIFIED;
+
             // 检查状态码
-            if !response.status().is_success() {
+            if !response.status().is_success() && !not_modified {
                 warn!("获取提交页面 {} 失败: HTTP {}", page, response.status());
                 // 如果是速率限制，打印详细信息
                 if response.status() == reqwest::StatusCode::FORBIDDEN {
@@ -127,10 +1137,11 @@ impl GitHubApiClient {
                         );
                     }
                     if let Some(reset) = response.headers().get("x-ratelimit-reset") {
-                        let reset_time = match reset.to_str().unwrap_or("0").parse::<i64>() {
-                            Ok(t) => t,
-                            Err(_) => 0,
-                        };
+                        let reset_time = reset
+                            .to_str()
+                            .unwrap_or("0")
+                            .parse::<i64>()
+                            .unwrap_or_default();
                         let now = std::time::SystemTime::now()
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap_or_default()
@@ -146,13 +1157,33 @@ impl GitHubApiClient {
                 break;
             }
 
-            // 提取分页信息
-            let has_next_page = response
+            // 提取分页信息，304响应可能不带Link头
+            let links = response
                 .headers()
-                .get("link")
+                .get(header::LINK)
                 .and_then(|h| h.to_str().ok())
-                .map(|link| link.contains("rel=\"next\""))
-                .unwrap_or(false);
+                .map(parse_link_header);
+            if page == 1 {
+                if let Some(last_page) = links
+                    .as_ref()
+                    .and_then(|links| links.get("last"))
+                    .and_then(|url| page_from_url(url))
+                {
+                    info!(
+                        "仓库 {}/{} 的提交共约 {} 页（每页{}条）{}",
+                        owner,
+                        repo,
+                        last_page,
+                        per_page,
+                        if last_page > max_pages {
+                            format!("，超过上限只获取前{}页", max_pages)
+                        } else {
+                            String::new()
+                        }
+                    );
+                }
+            }
+            let link_has_next = links.map(|links| links.contains_key("next"));
 
             // 解析提交数据
             #[derive(Debug, Deserialize)]
@@ -164,7 +1195,6 @@ impl GitHubApiClient {
 
             #[derive(Debug, Deserialize)]
             struct CommitInfo {
-                author: Option<String>,
                 email: Option<String>,
             }
 
@@ -179,7 +1209,32 @@ impl GitHubApiClient {
                 commit: CommitDetail,
             }
 
-            let commits: Vec<CommitData> = match response.json().await {
+            let body = match (not_modified, cached) {
+                (true, Some((_, body))) => {
+                    debug!("提交页面 {} 未变化，使用缓存", page);
+                    body
+                }
+                _ => {
+                    let etag = response
+                        .headers()
+                        .get(header::ETAG)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    let body = match response.bytes().await {
+                        Ok(body) => body.to_vec(),
+                        Err(e) => {
+                            warn!("读取提交页面 {} 失败: {}", page, e);
+                            break;
+                        }
+                    };
+                    if let (None, Some(etag)) = (since, etag) {
+                        ETAG_CACHE.lock().unwrap().insert(&url, etag, body.clone());
+                    }
+                    body
+                }
+            };
+
+            let commits: Vec<CommitData> = match serde_json::from_slice(&body) {
                 Ok(c) => c,
                 Err(e) => {
                     warn!("解析提交数据失败: {}", e);
@@ -192,15 +1247,21 @@ impl GitHubApiClient {
                 break;
             }
 
+            let has_next_page = link_has_next.unwrap_or(commits.len() == per_page);
+
             // 统计贡献者信息
             for commit in commits {
                 // 获取提交中的电子邮箱
                 let email = commit.commit.author.as_ref().and_then(|a| a.email.clone());
 
                 if let Some(author) = commit.author {
+                    // 通过网页界面提交时的web-flow身份不是真实贡献者
+                    if is_excluded_web_flow(Some(&author.login), email.as_deref()) {
+                        continue;
+                    }
                     contributors_map
                         .entry(author.id)
-                        .and_modify(|e: &mut (String, String, i32, Option<String>)| {
+                        .and_modify(|e| {
                             e.2 += 1;
                             // 如果之前没有邮箱但现在有了，则更新
                             if e.3.is_none() && email.is_some() {
@@ -217,36 +1278,394 @@ impl GitHubApiClient {
                 contributors_map.len()
             );
 
+            // 定期交出部分统计结果，限制内存占用
+            if let Some(flush_pages) = flush_pages.filter(|&n| n > 0) {
+                if page % flush_pages == 0 && !contributors_map.is_empty() {
+                    let partial = into_contributors(std::mem::take(&mut contributors_map));
+                    debug!("交出 {} 名贡献者的部分统计", partial.len());
+                    flush(partial).await?;
+                }
+            }
+
             // 如果没有下一页，退出循环
             if !has_next_page {
                 break;
             }
 
             // 添加延迟避免触发GitHub API限制
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            tokio::time::sleep(request_delay()).await;
 
             page += 1;
         }
 
+        save_etag_cache().await;
+
         info!("通过Commits API找到 {} 名贡献者", contributors_map.len());
 
-        // 转换为Contributor结构
-        let mut commit_contributors = contributors_map
-            .into_iter()
-            .map(
-                |(id, (login, avatar_url, contributions, email))| Contributor {
-                    id,
-                    login,
-                    avatar_url,
-                    contributions,
-                    email,
+        // 转换为Contributor结构，按贡献数量排序
+        let mut commit_contributors = into_contributors(contributors_map);
+        commit_contributors.sort_by_key(|c| std::cmp::Reverse(c.contributions));
+
+        Ok(commit_contributors)
+    }
+}
+
+// 提交扫描中按作者ID统计的(登录名, 头像, 提交数, 邮箱)
+type ContributorTally = HashMap<i64, (String, String, i32, Option<String>)>;
+
+// 把提交扫描的统计结果转换为Contributor列表
+fn into_contributors(tally: ContributorTally) -> Vec<Contributor> {
+    tally
+        .into_iter()
+        .map(
+            |(id, (login, avatar_url, contributions, email))| Contributor {
+                id,
+                login,
+                avatar_url,
+                contributions,
+                email,
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn user(login: &str) -> GitHubUser {
+        GitHubUser::from_contributor(&Contributor {
+            id: 1,
+            login: login.to_string(),
+            avatar_url: String::new(),
+            contributions: 1,
+            email: None,
+        })
+    }
+
+    #[test]
+    fn parse_noreply_login_handles_both_formats() {
+        assert_eq!(
+            parse_noreply_login("123456+octocat@users.noreply.github.com").as_deref(),
+            Some("octocat")
+        );
+        assert_eq!(
+            parse_noreply_login("octocat@users.noreply.github.com").as_deref(),
+            Some("octocat")
+        );
+        assert_eq!(
+            parse_noreply_login(" 1+Octo-Cat@Users.NoReply.GitHub.com ").as_deref(),
+            Some("Octo-Cat")
+        );
+    }
+
+    #[test]
+    fn parse_noreply_login_rejects_other_emails() {
+        assert_eq!(parse_noreply_login("octocat@github.com"), None);
+        assert_eq!(parse_noreply_login("octocat@example.com"), None);
+        assert_eq!(parse_noreply_login("noreply@github.com"), None);
+        assert_eq!(
+            parse_noreply_login("abc+octocat@users.noreply.github.com"),
+            None
+        );
+        assert_eq!(parse_noreply_login("123+@users.noreply.github.com"), None);
+        assert_eq!(parse_noreply_login("not-an-email"), None);
+    }
+
+    #[test]
+    fn parse_link_header_with_last() {
+        let header = r#"<https://api.github.com/repositories/1/commits?page=2&per_page=100>; rel="next", <https://api.github.com/repositories/1/commits?page=34&per_page=100>; rel="last""#;
+        let links = parse_link_header(header);
+
+        assert_eq!(links.len(), 2);
+        assert_eq!(links.get("next").and_then(|u| page_from_url(u)), Some(2));
+        assert_eq!(links.get("last").and_then(|u| page_from_url(u)), Some(34));
+    }
+
+    #[test]
+    fn parse_link_header_without_last() {
+        // 最后一页只有prev和first
+        let header = r#"<https://api.github.com/repositories/1/commits?page=33>; rel="prev", <https://api.github.com/repositories/1/commits?page=1>; rel="first""#;
+        let links = parse_link_header(header);
+
+        assert!(!links.contains_key("last"));
+        assert!(!links.contains_key("next"));
+        assert_eq!(links.get("prev").and_then(|u| page_from_url(u)), Some(33));
+        assert_eq!(links.get("first").and_then(|u| page_from_url(u)), Some(1));
+    }
+
+    #[test]
+    fn parse_link_header_reordered_and_loosely_formatted() {
+        let header = "<https://api.github.com/x?per_page=100&page=9> ;  rel='last' ,<https://api.github.com/x?page=3&per_page=100>; REL=next";
+        let links = parse_link_header(header);
+
+        assert_eq!(links.get("last").and_then(|u| page_from_url(u)), Some(9));
+        assert_eq!(links.get("next").and_then(|u| page_from_url(u)), Some(3));
+
+        let links = parse_link_header(r#"<https://api.github.com/x?page=5>; rel="next last""#);
+        assert_eq!(links.get("next"), links.get("last"));
+    }
+
+    #[test]
+    fn page_from_url_reads_only_the_page_parameter() {
+        assert_eq!(
+            page_from_url("https://api.github.com/x?per_page=100&page=7"),
+            Some(7)
+        );
+        assert_eq!(page_from_url("https://api.github.com/x?per_page=100"), None);
+        assert_eq!(page_from_url("https://api.github.com/x?page=abc"), None);
+        assert_eq!(page_from_url("https://api.github.com/x"), None);
+    }
+
+    #[test]
+    fn parse_github_repo_url_strict_accepts_common_forms() {
+        let expected = Some(("owner".to_string(), "repo".to_string()));
+        for url in [
+            "https://github.com/owner/repo",
+            "https://github.com/owner/repo.git",
+            "https://github.com/owner/repo/",
+            "https://www.github.com/owner/repo",
+            "http://GitHub.com/owner/repo",
+            "git@github.com:owner/repo.git",
+            "ssh://git@github.com/owner/repo.git",
+            "git+ssh://git@github.com/owner/repo",
+            "github.com/owner/repo",
+            "https://github.com/owner/repo/tree/main/src",
+            "https://github.com/owner/repo?tab=readme#usage",
+            "  https://github.com/owner/repo  ",
+        ] {
+            assert_eq!(parse_github_repo_url_strict(url), expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn parse_github_repo_url_strict_rejects_other_hosts_and_incomplete_paths() {
+        for url in [
+            "https://gitlab.com/owner/repo",
+            "https://github.com.evil.com/owner/repo",
+            "https://notgithub.com/owner/repo",
+            "https://gist.github.com/owner/repo",
+            "ftp://github.com/owner/repo",
+            "https://github.com/owner",
+            "https://github.com/",
+            "https://github.com/owner/.git",
+            "owner/repo",
+            "",
+        ] {
+            assert_eq!(parse_github_repo_url_strict(url), None, "{}", url);
+        }
+    }
+
+    #[test]
+    fn broader_scopes_imply_narrower_ones() {
+        let granted = vec!["repo".to_string(), "admin:org".to_string()];
+        assert!(scope_granted(&granted, "repo"));
+        assert!(scope_granted(&granted, "public_repo"));
+        assert!(scope_granted(&granted, "read:org"));
+        assert!(scope_granted(&granted, "write:org"));
+        assert!(!scope_granted(&granted, "read:user"));
+
+        let granted = vec!["write:org".to_string(), "public_repo".to_string()];
+        assert!(scope_granted(&granted, "read:org"));
+        assert!(!scope_granted(&granted, "admin:org"));
+        assert!(!scope_granted(&granted, "repo"));
+    }
+
+    #[test]
+    fn user_details_cache_expires_entries() {
+        let mut cache = UserDetailsCache::default();
+        let now = Instant::now();
+        cache.insert("alice".to_string(), user("alice"), now);
+
+        assert!(cache.get("alice", now + Duration::from_secs(60)).is_some());
+        assert!(cache.get("alice", now + USER_DETAILS_CACHE_TTL).is_none());
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn user_details_cache_evicts_oldest_when_full() {
+        let mut cache = UserDetailsCache::default();
+        let now = Instant::now();
+        for i in 0..USER_DETAILS_CACHE_MAX_ENTRIES {
+            let login = format!("user{}", i);
+            cache.insert(
+                login.clone(),
+                user(&login),
+                now + Duration::from_millis(i as u64),
+            );
+        }
+        let later = now + Duration::from_secs(1);
+        cache.insert("newcomer".to_string(), user("newcomer"), later);
+
+        assert_eq!(cache.entries.len(), USER_DETAILS_CACHE_MAX_ENTRIES);
+        assert!(cache.get("user0", later).is_none());
+        assert!(cache.get("user1", later).is_some());
+        assert!(cache.get("newcomer", later).is_some());
+    }
+
+    #[tokio::test]
+    async fn retry_transient_succeeds_after_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/users/someone", listener.local_addr().unwrap());
+
+        // 第一个连接不响应直到客户端超时，第二个连接返回用户资料
+        tokio::spawn(async move {
+            let (stalled, _) = listener.accept().await.unwrap();
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let body = r#"{"id":1,"login":"someone"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            drop(stalled);
+        });
+
+        let client = Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let mut attempts = 0;
+        let user: GitHubUser = retry_transient(2, Duration::from_millis(1), "someone", || {
+            attempts += 1;
+            let request = client.get(&url).send();
+            async move { request.await?.error_for_status()?.json().await }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(user.login, "someone");
+        assert_eq!(attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn retry_transient_gives_up_after_retries() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        // 接受连接但从不响应
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let client = Client::builder()
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let mut attempts = 0;
+        let result = retry_transient(1, Duration::from_millis(1), "stalled", || {
+            attempts += 1;
+            client.get(&url).send()
+        })
+        .await;
+
+        assert!(result.unwrap_err().is_timeout());
+        assert_eq!(attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn poll_until_ready_retries_while_accepted() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/stats", listener.local_addr().unwrap());
+
+        // 前两次返回202，第三次返回统计数据
+        tokio::spawn(async move {
+            for status in ["202 Accepted", "202 Accepted", "200 OK"] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await.unwrap();
+                let body = r#"{"all":[1,2,3]}"#;
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let github = GitHubApiClient::new().with_token("");
+        let mut attempts = 0;
+        let response = github
+            .poll_until_ready(
+                || {
+                    attempts += 1;
+                    github.client.get(&url).send()
                 },
+                Duration::from_secs(5),
+                Duration::from_millis(1),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn poll_until_ready_gives_up_after_max_wait() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/stats", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket
+                    .write_all(
+                        b"HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    )
+                    .await;
+            }
+        });
+
+        let github = GitHubApiClient::new().with_token("");
+        let result = github
+            .poll_until_ready(
+                || github.client.get(&url).send(),
+                Duration::from_millis(50),
+                Duration::from_millis(10),
             )
-            .collect::<Vec<_>>();
+            .await;
 
-        // 按贡献数量排序
-        commit_contributors.sort_by(|a, b| b.contributions.cmp(&a.contributions));
+        assert!(result.unwrap_err().to_string().contains("仍未生成完成"));
+    }
 
-        Ok(commit_contributors)
+    #[tokio::test]
+    async fn post_requests_count_against_the_request_budget() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}",
+                    )
+                    .await;
+            }
+        });
+
+        let github = GitHubApiClient::new().with_token("test");
+        let budget = Arc::new(RequestBudget::new(10));
+        with_request_budget(Arc::clone(&budget), async {
+            for _ in 0..2 {
+                github
+                    .send_with_token_rotation(|_| github.client.post(&url).body("{}"))
+                    .await
+                    .unwrap();
+            }
+        })
+        .await;
+
+        assert_eq!(budget.used(), 2);
     }
 }
@@ -1,2 +1,3 @@
+pub mod cache;
 pub mod database;
 pub mod github_api;